@@ -0,0 +1,122 @@
+use serde::{Serialize, Deserialize};
+use serde_json::{from_str, to_string};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::stake::{Standby, Validator, ValidatorSet};
+
+// Master replicas elect a leader through a lease log on the MasterLease topic. Every replica reads the
+// whole log and applies the same rules to it, so all of them agree on the holder after each record
+// whatever their clocks say. Clocks only decide when a replica tries to take an expired lease over.
+
+pub fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).expect("Clock before the Unix epoch").as_millis() as u64
+}
+
+/// Committee decided for an epoch, recorded in the lease before any of it is published
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Selection {
+    pub validator_set: ValidatorSet,
+    pub standby: Standby,
+    pub primary: Validator,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Lease {
+    pub holder: String,
+    pub term: u64,
+    /// Milliseconds since the Unix epoch when the holder wrote the record
+    pub issued: u64,
+    pub expires: u64,
+    /// Latest epoch a committee was decided for, if any
+    #[serde(default)]
+    pub epoch: Option<u64>,
+    /// Committee of `epoch` while it is being published, a new holder publishes it again
+    #[serde(default)]
+    pub selection: Option<Selection>,
+}
+
+impl Lease {
+    pub fn serialize(&self) -> String {
+        to_string(&self).expect("Failed to serialize")
+    }
+
+    pub fn deserialize(json_str: &str) -> Option<Self> {
+        from_str(json_str).ok()
+    }
+
+    /// Whether both are the same record of the log
+    pub fn same(&self, other: &Lease) -> bool {
+        self.holder == other.holder && self.term == other.term && self.issued == other.issued
+    }
+}
+
+#[derive(Default)]
+pub struct LeaseLog {
+    pub current: Option<Lease>,
+}
+
+impl LeaseLog {
+    /// Applies the next record of the log, returning false when it does not take effect. The holder
+    /// renews within its term without going back an epoch or reopening a published committee; anyone
+    /// else takes the next term once the lease expired at the time their record was issued, and
+    /// inherits the decided epoch and any committee still being published.
+    pub fn apply(&mut self, lease: Lease) -> bool {
+        let next = match &self.current {
+            None => Some(lease),
+            Some(current) if lease.term == current.term => {
+                let renewal = lease.holder == current.holder
+                    && (lease.epoch > current.epoch
+                        || (lease.epoch == current.epoch && (lease.selection.is_none() || current.selection.is_some())));
+                renewal.then_some(lease)
+            }
+            Some(current) => (lease.term == current.term + 1 && lease.issued >= current.expires)
+                .then(|| Lease { epoch: current.epoch, selection: current.selection.clone(), ..lease })
+        };
+        match next {
+            Some(lease) => {
+                self.current = Some(lease);
+                true
+            }
+            None => false
+        }
+    }
+
+    pub fn holds(&self, me: &str, now: u64) -> bool {
+        self.current.as_ref().is_some_and(|current| current.holder == me && now < current.expires)
+    }
+
+    /// Latest epoch a committee was decided for, none is published again for it or an earlier one
+    pub fn epoch(&self) -> Option<u64> {
+        self.current.as_ref().and_then(|current| current.epoch)
+    }
+
+    /// Next record to keep or take the lease with: a renewal while `me` holds it, a claim of the next
+    /// term once it expired, otherwise none
+    pub fn next(&self, me: &str, now: u64, duration: u64) -> Option<Lease> {
+        match &self.current {
+            None => Some(Lease { holder: me.to_string(), term: 1, issued: now, expires: now + duration, epoch: None,
+                selection: None }),
+            Some(current) if current.holder == me => Some(Lease { issued: now, expires: now + duration, ..current.clone() }),
+            Some(current) if now >= current.expires => Some(Lease { holder: me.to_string(), term: current.term + 1,
+                issued: now, expires: now + duration, ..current.clone() }),
+            Some(_) => None
+        }
+    }
+
+    /// Record deciding `selection`, when `me` holds the lease and no committee was decided for its epoch yet
+    pub fn reserve(&self, me: &str, now: u64, duration: u64, selection: Selection) -> Option<Lease> {
+        let epoch = selection.validator_set.epoch;
+        if !self.holds(me, now) || self.epoch().is_some_and(|decided| epoch <= decided) {
+            return None;
+        }
+        self.next(me, now, duration).map(|lease| Lease { epoch: Some(epoch), selection: Some(selection), ..lease })
+    }
+
+    /// Record marking the committee of `epoch` as published
+    pub fn settle(&self, me: &str, now: u64, duration: u64, epoch: u64) -> Option<Lease> {
+        let current = self.current.as_ref()?;
+        if current.holder != me || current.epoch != Some(epoch) || current.selection.is_none() {
+            return None;
+        }
+        self.next(me, now, duration).map(|lease| Lease { selection: None, ..lease })
+    }
+}
@@ -0,0 +1,231 @@
+use serde::{Serialize, Deserialize};
+use bls_signatures::{PublicKey, Serialize as _, Signature};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use crate::stake::{Stake, ValidatorSet};
+
+/// Consensus message as node_pod sends it. Only the signed block inside `msg_type` is read.
+#[derive(Deserialize)]
+pub struct ConsensusMessage {
+    pub msg_type: MessageType,
+    pub signature: String,
+    pub sender_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, std::fmt::Debug, PartialEq)]
+pub enum Phase {
+    PrePrepare,
+    Prepare,
+    Commit,
+}
+
+// Prefixes node_pod signs each phase's block behind, so a signature only counts for its own phase
+pub const PREPREPARE_DOMAIN: &str = "node_pod preprepare:";
+pub const PREPARE_DOMAIN: &str = "node_pod prepare:";
+pub const COMMIT_DOMAIN: &str = "node_pod commit:";
+
+impl Phase {
+    pub fn domain(&self) -> &'static str {
+        match self {
+            Phase::PrePrepare => PREPREPARE_DOMAIN,
+            Phase::Prepare => PREPARE_DOMAIN,
+            Phase::Commit => COMMIT_DOMAIN,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub enum MessageType {
+    PrePrepare(String),
+    Prepare(String),
+    Commit(String),
+    Reply(String),
+}
+
+/// Header fields of the signed block liveness is accounted by
+#[derive(Deserialize, Clone)]
+pub struct BlockRef {
+    pub hash: String,
+    pub index: u64,
+    #[serde(default)]
+    pub proposer: String,
+    #[serde(default)]
+    pub validator_set_hash: String,
+}
+
+impl ConsensusMessage {
+    pub fn deserialize(json_str: &str) -> Option<Self> {
+        serde_json::from_str(json_str).ok()
+    }
+
+    fn signed(&self) -> Option<(Phase, &String)> {
+        match &self.msg_type {
+            MessageType::PrePrepare(block) => Some((Phase::PrePrepare, block)),
+            MessageType::Prepare(block) => Some((Phase::Prepare, block)),
+            MessageType::Commit(block) => Some((Phase::Commit, block)),
+            MessageType::Reply(_) => None
+        }
+    }
+
+    /// Phase and block the message claims to sign, before its signature is checked
+    pub fn block(&self) -> Option<(Phase, BlockRef)> {
+        let (phase, signed) = self.signed()?;
+        let block = signed.strip_prefix(phase.domain())?;
+        serde_json::from_str(block).ok().map(|block| (phase, block))
+    }
+
+    pub fn verify(&self, public_key: &str) -> bool {
+        let Some((phase, signed)) = self.signed() else {
+            return false;
+        };
+        if !signed.starts_with(phase.domain()) {
+            return false;
+        }
+        let public_key = match hex::decode(public_key).ok().and_then(|bytes| PublicKey::from_bytes(&bytes).ok()) {
+            Some(public_key) => public_key,
+            None => { return false; }
+        };
+        match hex::decode(&self.signature).ok().and_then(|bytes| Signature::from_bytes(&bytes).ok()) {
+            Some(signature) => public_key.verify(signature, signed),
+            None => false
+        }
+    }
+}
+
+/// Validators seen signing each phase for one block
+#[derive(Serialize, Clone, Default, std::fmt::Debug)]
+pub struct CommitCertificate {
+    pub height: u64,
+    pub block_hash: String,
+    pub proposer: String,
+    pub preprepares: BTreeSet<String>,
+    pub prepares: BTreeSet<String>,
+    pub commits: BTreeSet<String>,
+}
+
+impl CommitCertificate {
+    /// Whether more than two thirds of a committee of `size` committed the block
+    pub fn is_committed(&self, size: usize) -> bool {
+        3 * self.commits.len() > 2 * size
+    }
+}
+
+/// Phases a validator missed over the committed blocks of an epoch
+#[derive(Serialize, Clone, Default, std::fmt::Debug, PartialEq)]
+pub struct ValidatorLiveness {
+    pub node_id: String,
+    pub blocks: u64,
+    pub missed_preprepares: u64,
+    pub missed_prepares: u64,
+    pub missed_commits: u64,
+    /// Share of the messages expected from the validator that it sent, 1 without committed blocks
+    pub liveness: f64,
+}
+
+#[derive(Serialize, Clone, Default, std::fmt::Debug)]
+pub struct EpochLiveness {
+    pub epoch: u64,
+    pub blocks: u64,
+    pub validators: Vec<ValidatorLiveness>,
+    /// Validators jailed after this epoch
+    pub jailed: Vec<String>,
+}
+
+/// Builds commit certificates from the consensus messages of the committees the master published,
+/// and keeps validators whose liveness fell below a threshold out of selection for some epochs
+#[derive(Default)]
+pub struct LivenessTracker {
+    pub sets: HashMap<String, ValidatorSet>,
+    pub certificates: BTreeMap<u64, BTreeMap<(u64, String), CommitCertificate>>,
+    /// First epoch each jailed validator can be selected in again
+    pub jailed: HashMap<String, u64>,
+}
+
+impl LivenessTracker {
+    pub fn add_set(&mut self, set: ValidatorSet) {
+        self.sets.insert(set.hash.clone(), set);
+    }
+
+    /// Counts a message for the block it signs, returning false when the block names no known
+    /// committee or the sender is not in it or its signature does not verify
+    pub fn record(&mut self, message: &ConsensusMessage) -> bool {
+        let Some((phase, block)) = message.block() else {
+            return false;
+        };
+        let Some(set) = self.sets.get(&block.validator_set_hash) else {
+            return false;
+        };
+        let signed = set.validators.iter()
+            .any(|validator| validator.node_id == message.sender_id && message.verify(&validator.public_key));
+        if !signed {
+            return false;
+        }
+        let epoch = set.epoch;
+
+        let certificate = self.certificates.entry(epoch).or_default()
+            .entry((block.index, block.hash.clone()))
+            .or_insert_with(|| CommitCertificate { height: block.index, block_hash: block.hash.clone(),
+                proposer: block.proposer.clone(), ..Default::default() });
+        let signers = match phase {
+            Phase::PrePrepare => &mut certificate.preprepares,
+            Phase::Prepare => &mut certificate.prepares,
+            Phase::Commit => &mut certificate.commits,
+        };
+        signers.insert(message.sender_id.clone());
+        true
+    }
+
+    /// Accounts the committed blocks of `epoch`: the proposer of each was expected to preprepare
+    /// it and every member to prepare and commit it. Validators below `threshold` are jailed for
+    /// `jail_epochs` epochs from the next one, whose committee is being selected now.
+    pub fn close(&mut self, epoch: u64, threshold: f64, jail_epochs: u64) -> Option<EpochLiveness> {
+        let set = self.sets.values().find(|set| set.epoch == epoch)?.clone();
+        let certificates = self.certificates.remove(&epoch).unwrap_or_default();
+        let committed: Vec<&CommitCertificate> = certificates.values()
+            .filter(|certificate| certificate.is_committed(set.validators.len()))
+            .collect();
+
+        let mut report = EpochLiveness { epoch, blocks: committed.len() as u64, ..Default::default() };
+        for validator in &set.validators {
+            let id = &validator.node_id;
+            let mut stats = ValidatorLiveness { node_id: id.clone(), blocks: committed.len() as u64, ..Default::default() };
+            let mut expected = 0;
+            for certificate in &committed {
+                if &certificate.proposer == id {
+                    expected += 1;
+                    stats.missed_preprepares += u64::from(!certificate.preprepares.contains(id));
+                }
+                expected += 2;
+                stats.missed_prepares += u64::from(!certificate.prepares.contains(id));
+                stats.missed_commits += u64::from(!certificate.commits.contains(id));
+            }
+            let missed = stats.missed_preprepares + stats.missed_prepares + stats.missed_commits;
+            stats.liveness = match expected {
+                0 => 1.0,
+                expected => 1.0 - missed as f64 / expected as f64
+            };
+
+            if stats.liveness < threshold {
+                self.jailed.insert(id.clone(), epoch + 1 + jail_epochs);
+                report.jailed.push(id.clone());
+            }
+            report.validators.push(stats);
+        }
+
+        self.sets.retain(|_, kept| kept.epoch > epoch);
+        self.certificates.retain(|kept, _| *kept > epoch);
+        Some(report)
+    }
+
+    pub fn is_jailed(&self, node_id: &str, epoch: u64) -> bool {
+        self.jailed.get(node_id).is_some_and(|until| epoch < *until)
+    }
+
+    /// Stakes of the validators not jailed in `epoch`, or all of them when every one is jailed
+    pub fn eligible(&self, stakes: Vec<Stake>, epoch: u64) -> Vec<Stake> {
+        let free: Vec<Stake> = stakes.iter().filter(|stake| !self.is_jailed(&stake.node_id, epoch)).cloned().collect();
+        match free.is_empty() {
+            true => stakes,
+            false => free
+        }
+    }
+}
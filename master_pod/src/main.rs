@@ -1,23 +1,32 @@
 use rdkafka::consumer::{Consumer, StreamConsumer};
-use rdkafka::producer::{BaseProducer, BaseRecord};
+use rdkafka::producer::{BaseProducer, BaseRecord, Producer};
 use stake::Validator;
 use rdkafka::ClientConfig;
-use std::time::Duration;
-use serde::Deserialize;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
 use rdkafka::Message;
 use rand::Rng;
 use log::info;
-use rand_distr::{Distribution, WeightedIndex};
 use futures_util::stream::StreamExt;
 use tokio::time::timeout;
-use std::collections::HashSet;
-use tokio::fs;
-use std::thread;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 pub mod stake;
-use crate::stake::Stake;
-
-#[derive(Deserialize)]
+pub use pod_config as config;
+pub mod lease;
+pub mod selection;
+pub mod liveness;
+pub mod test_stake;
+pub mod test_lease;
+pub mod test_selection;
+pub mod test_liveness;
+use crate::stake::{Candidates, Rejection, Stake, StakeFilter, Standby, ValidatorSet};
+use crate::lease::{now_ms, Lease, LeaseLog, Selection};
+use crate::selection::{SelectionStrategy, Strategy};
+use crate::liveness::{ConsensusMessage, LivenessTracker};
+
+#[derive(Deserialize, Serialize)]
 pub struct ConsumerConfig {
     pub server: String,
     pub autocommit: String,
@@ -25,7 +34,7 @@ pub struct ConsumerConfig {
     pub acks: String
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct ProducerConfig {
     pub server: String,
     pub autocommit: String,
@@ -35,42 +44,148 @@ pub struct ProducerConfig {
     pub acks: String
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct PerformanceConfig {
     pub timeout: u64,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct StakingConfig {
-    pub validators: usize
+    pub validators: usize,
+    /// Number of node pods, stake collection closes early once all of them staked
+    #[serde(default)]
+    pub replicas: Option<usize>,
+    /// Milliseconds stakes are collected for after the first one of an epoch arrives
+    #[serde(default = "default_deadline")]
+    pub deadline: u64,
+    /// How the committee is picked from the candidates: weighted, top_k, round_robin or reputation
+    #[serde(default)]
+    pub strategy: Strategy,
+    /// Share of the reputation strategy's score that comes from staking in earlier epochs
+    #[serde(default = "default_reputation_weight")]
+    pub reputation_weight: f64
+}
+
+fn default_deadline() -> u64 {
+    2000
+}
+
+fn default_reputation_weight() -> f64 {
+    0.5
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ElectionConfig {
+    /// Milliseconds a leader holds the lease for without renewing it, followers take over after that
+    pub lease: u64
+}
+
+impl Default for ElectionConfig {
+    fn default() -> Self {
+        ElectionConfig { lease: 3000 }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct LivenessConfig {
+    /// Validators that sent a smaller share of the consensus messages expected from them in an epoch are jailed
+    pub threshold: f64,
+    /// Epochs a jailed validator is left out of selection for
+    pub jail_epochs: u64
+}
+
+impl Default for LivenessConfig {
+    fn default() -> Self {
+        LivenessConfig { threshold: 0.5, jail_epochs: 2 }
+    }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct Config {
     consumer: ConsumerConfig,
     producer: ProducerConfig,
     performance: PerformanceConfig,
-    staking: StakingConfig
+    staking: StakingConfig,
+    #[serde(default)]
+    election: ElectionConfig,
+    #[serde(default)]
+    liveness: LivenessConfig
 }
 
-pub async fn load_config() -> Option<Config> {
-    info!("Loading config");
-
-    let path = "src/config.yaml";
+impl Config {
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
 
-    let config_content = fs::read_to_string(&path).await.expect("Failed to read file");
+        if self.consumer.server.is_empty() || self.producer.server.is_empty() {
+            problems.push(String::from("consumer.server and producer.server must name the Kafka bootstrap servers"));
+        }
+        if self.performance.timeout == 0 {
+            problems.push(String::from("performance.timeout must be at least 1 millisecond"));
+        }
+        if self.staking.deadline == 0 {
+            problems.push(String::from("staking.deadline must be at least 1 millisecond"));
+        }
+        if !(0.0..=1.0).contains(&self.staking.reputation_weight) {
+            problems.push(String::from("staking.reputation_weight must be between 0 and 1"));
+        }
+        if !(0.0..=1.0).contains(&self.liveness.threshold) {
+            problems.push(String::from("liveness.threshold must be between 0 and 1"));
+        }
+        if self.election.lease < 3 {
+            problems.push(String::from("election.lease must be at least 3 milliseconds"));
+        }
+        if self.staking.validators == 0 {
+            problems.push(String::from("staking.validators must select at least 1 validator"));
+        }
+        if let Some(replicas) = self.staking.replicas {
+            if self.staking.validators > replicas {
+                problems.push(format!("staking.validators is {} but only {} node replicas can stake", self.staking.validators, replicas));
+            }
+        }
+        problems
+    }
+}
 
-    let config =serde_yaml::from_str(&config_content).expect("Failed to parse yaml file");
+pub fn load_config(path: &str) -> Result<Config, String> {
+    info!("Loading config");
 
-    config
+    let config: Config = config::load(path, "MASTER", &[("BOOTSTRAP_SERVERS", &["consumer.server", "producer.server"])])?;
+    let problems = config.validate();
+    config::check(config, path, problems)
 }
 
 
-pub async fn listen_stake(consumer: &StreamConsumer, time_out: u64,
-vals: &usize) -> Option<Vec<Stake>> {
+/// Adds a stake to the epoch being collected unless `filter` rejects it or its epoch is over.
+/// The deadline restarts when nodes turn out to stake for a later epoch.
+fn admit(candidates: &mut Candidates, filter: &mut StakeFilter, stake: Stake, opened: &mut Option<Instant>) {
+    let (node_id, stake_epoch, collecting) = (stake.node_id.clone(), stake.epoch, candidates.epoch);
+    if let Err(rejection) = filter.check(&stake) {
+        info!("Rejected the stake claiming {}: {:?}", node_id, rejection);
+        return;
+    }
+    if !candidates.follow(stake_epoch) {
+        filter.rejects.record(Rejection::Stale);
+        info!("Dropped the stake of {} for past epoch {}", node_id, stake_epoch);
+        return;
+    }
+    if candidates.epoch != collecting {
+        info!("Nodes stake for epoch {}, collecting for it instead", candidates.epoch);
+        *opened = None;
+    }
+    if !candidates.insert(stake) {
+        info!("Replaced the earlier stake of {} in epoch {}", node_id, candidates.epoch);
+    }
+    opened.get_or_insert_with(Instant::now);
+}
+
+/// Collects the stakes of one epoch. Collection closes once every replica staked, or `deadline`
+/// milliseconds after the first stake arrived, and later stakes are left for the next epoch.
+pub async fn listen_stake(consumer: &StreamConsumer, filter: &Mutex<StakeFilter>, time_out: u64, deadline: u64,
+replicas: Option<usize>, epoch: u64) -> Candidates {
 
-    info!("Listening for the stakes");
-    let mut stakes: Vec<Stake> = vec![];
+    info!("Listening for the stakes of epoch {}", epoch);
+    let mut candidates = Candidates::new(epoch);
+    let mut opened: Option<Instant> = None;
 
     let mut msg_stream = consumer.stream();
 
@@ -82,9 +197,8 @@ vals: &usize) -> Option<Vec<Stake>> {
                 Ok(message) => {
                     if let Some(payload) = message.payload() {
                         match serde_json::from_slice::<Stake>(payload){
-                            Ok(user) => {
-                                stakes.push(user);
-                            }
+                            Ok(stake) => admit(&mut candidates, &mut filter.lock().expect("Stake filter poisoned"), stake,
+                                &mut opened),
                             Err(e) => {
                                 eprintln!("Failed to deserialize message: {}", e);
                             }
@@ -96,129 +210,354 @@ vals: &usize) -> Option<Vec<Stake>> {
         Ok(_) => {
             panic!["stream ended"];
         }
-        Err(_) => {
-            if stakes.len() == *vals {
-                break;
-            } else if stakes.len() < *vals && !stakes.is_empty() {
-                msg_stream = consumer.stream();
-                continue;
-            } else {
-                continue;
-            }
+        Err(_) => {}
         }
+
+        let everyone_staked = replicas.is_some_and(|replicas| candidates.len() >= replicas);
+        let closed = opened.is_some_and(|opened| opened.elapsed() >= Duration::from_millis(deadline));
+        if everyone_staked || closed {
+            break;
         }
     }
-    if stakes.is_empty() {
-        return None;
+
+    info!("Received {} stakes for epoch {}", candidates.len(), candidates.epoch);
+    let rejects = filter.lock().expect("Stake filter poisoned").rejects.clone();
+    if rejects.total() > 0 {
+        println!("Stakes rejected by epoch {}: {}", candidates.epoch,
+            serde_json::to_string(&rejects).expect("Failed to serialize reject metrics"));
     }
-    else {
-        Some(stakes)
+    candidates
+}
+
+/// Feeds the tracker the committees published on the Validators topic and the consensus messages
+/// signed for their blocks
+pub async fn track_liveness(consumer: StreamConsumer, tracker: Arc<Mutex<LivenessTracker>>) {
+    let mut msg_stream = consumer.stream();
+
+    while let Some(message_result) = msg_stream.next().await {
+        match message_result {
+            Err(e) => eprintln!("Error while receiving message: {}", e),
+            Ok(message) => {
+                let Some(payload) = message.payload().and_then(|payload| std::str::from_utf8(payload).ok()) else {
+                    continue;
+                };
+                let mut tracker = tracker.lock().expect("Liveness tracker poisoned");
+                if message.topic() == "Validators" {
+                    match serde_json::from_str::<ValidatorSet>(payload) {
+                        Ok(set) => tracker.add_set(set),
+                        Err(e) => eprintln!("Failed to deserialize the validator set: {}", e)
+                    }
+                } else if !ConsensusMessage::deserialize(payload).is_some_and(|message| tracker.record(&message)) {
+                    info!("Left a {} message out of the commit certificates", message.topic());
+                }
+            }
+        }
     }
+    panic!["stream ended"];
 }
 
-fn validator_selection(
-    stakes: &[Stake],
-    vals: usize,
-    producer: &BaseProducer,
-    producer2: &BaseProducer,
-    time_out: u64,
-) {
+/// Committee size that stakers holding more than two thirds of the stake report from their chains,
+/// capped at the number of stakers, or `fallback` when no size has that quorum. Governance passes a
+/// size with the same share of stake, so a minority can not pick one however many nodes it runs.
+pub fn committee_size(stakes: &[Stake], fallback: usize) -> usize {
+    let total: f64 = stakes.iter().map(|s| s.stake).sum();
+    let mut reported: HashMap<usize, f64> = HashMap::new();
+    for stake in stakes {
+        if let Some(size) = stake.committee_size {
+            *reported.entry(size).or_insert(0.0) += stake.stake;
+        }
+    }
+
+    let size = reported.into_iter()
+        .find(|(_, weight)| 3.0 * weight > 2.0 * total)
+        .map(|(size, _)| size)
+        .unwrap_or(fallback);
 
-    info!("Selecting validators");
+    size.min(stakes.len()).max(1)
+}
 
-    let weights: Vec<f64> = stakes.iter().map(|s| s.stake).collect();
-    let mut rng = rand::thread_rng();
-    let dist = WeightedIndex::new(&weights).expect("Invalid weights");
+/// Picks the committee of `epoch` with `strategy`, the candidates left out stand by. None when it
+/// picks no one, as when no candidate is left to select from.
+pub fn validator_selection(strategy: &dyn SelectionStrategy, stakes: &[Stake], vals: usize, epoch: u64) -> Option<Selection> {
 
-    let mut selected_indices = HashSet::new();
-    let mut validators: Vec<Validator> = Vec::new();
+    info!("Selecting validators with the {} strategy", strategy.name());
 
-    while validators.len() < vals {
-        let index = dist.sample(&mut rng);
+    let selected = strategy.select(stakes, vals, epoch);
+    let validators: Vec<Validator> = selected.iter().map(|index| Validator::from_stake(&stakes[*index])).collect();
+    let primary = validators.first()?.clone();
 
-        if selected_indices.contains(&index) {
-            continue; 
-        }
+    let standby = Standby::from_candidates(stakes, &selected.into_iter().collect(), epoch);
+    let validator_set = ValidatorSet { strategy: strategy.name().to_string(), ..ValidatorSet::new(epoch, validators) };
 
-        selected_indices.insert(index);
-        validators.push(Validator::from_stake(&stakes[index]));
-    }
+    Some(Selection { validator_set, standby, primary })
+}
 
-    let primary_index = rng.gen_range(0..validators.len());
-    let primary = validators[primary_index].clone();
+async fn publish(selection: &Selection, producer: &BaseProducer, producer2: &BaseProducer, time_out: u64) {
+    let epoch = selection.validator_set.epoch;
 
-    for val in &validators {
-        let record_json = val.serialize();
-        info!("Validator: {}", record_json);
-        producer
-            .send(
-                BaseRecord::to("Validators")
-                    .payload(&record_json)
-                    .key(&val.node_id),
-            )
-            .expect("Failed to send validator message");
-    }
+    info!("Standby: {}", selection.standby.validators.len());
+    producer
+        .send(
+            BaseRecord::to("Standby")
+                .payload(&selection.standby.serialize())
+                .key(&epoch.to_string()),
+        )
+        .expect("Failed to send standby message");
+
+    let record_json = selection.validator_set.serialize();
+    info!("Validator set: {}", record_json);
+    producer
+        .send(
+            BaseRecord::to("Validators")
+                .payload(&record_json)
+                .key(&epoch.to_string()),
+        )
+        .expect("Failed to send validator set message");
 
-    thread::sleep(Duration::from_millis(time_out));
+    tokio::time::sleep(Duration::from_millis(time_out)).await;
 
-    let p_record_json = primary.serialize();
+    let p_record_json = selection.primary.serialize();
     producer2
         .send(
             BaseRecord::to("Primary")
                 .payload(&p_record_json)
-                .key(&primary.node_id),
+                .key(&selection.primary.node_id),
         )
         .expect("Failed to send primary message");
 }
 
+/// This replica's view of the lease log and the producers it writes the log and committees with
+pub struct Election {
+    pub me: String,
+    pub lease: u64,
+    pub time_out: u64,
+    pub log: Mutex<LeaseLog>,
+    /// Stakes checked by this replica, spent by every committee the log records
+    pub filter: Mutex<StakeFilter>,
+    producer: BaseProducer,
+    producer2: BaseProducer,
+    writing: tokio::sync::Mutex<()>,
+}
+
+impl Election {
+    /// Applies the lease records as they arrive on the MasterLease topic
+    async fn follow(&self, consumer: StreamConsumer) {
+        let mut msg_stream = consumer.stream();
+
+        while let Some(message_result) = msg_stream.next().await {
+            match message_result {
+                Err(e) => eprintln!("Error while receiving message: {}", e),
+                Ok(message) => {
+                    let lease = message.payload()
+                        .and_then(|payload| std::str::from_utf8(payload).ok())
+                        .and_then(Lease::deserialize);
+                    let Some(lease) = lease else {
+                        eprintln!("Failed to deserialize the lease");
+                        continue;
+                    };
+
+                    let mut log = self.log.lock().expect("Lease log poisoned");
+                    let term = log.current.as_ref().map(|current| current.term);
+                    if log.apply(lease) {
+                        let current = log.current.as_ref().expect("Applied lease missing");
+                        if let Some(selection) = &current.selection {
+                            let mut filter = self.filter.lock().expect("Stake filter poisoned");
+                            filter.commit(selection.validator_set.epoch, &selection.validator_set.validators);
+                            filter.commit(selection.validator_set.epoch, &selection.standby.validators);
+                        }
+                        if term != Some(current.term) {
+                            println!("Master {} leads term {}", current.holder, current.term);
+                        }
+                    }
+                }
+            }
+        }
+        panic!["stream ended"];
+    }
+
+    /// Writes the record `make` builds from the log and waits for the log to apply it. Returns the
+    /// lease in effect after it, or none when it was not written or did not take effect within a lease.
+    async fn write(&self, make: impl FnOnce(&LeaseLog, u64) -> Option<Lease>) -> Option<Lease> {
+        let _writing = self.writing.lock().await;
+        let lease = make(&self.log.lock().expect("Lease log poisoned"), now_ms())?;
+
+        self.producer
+            .send(
+                BaseRecord::to("MasterLease")
+                    .payload(&lease.serialize())
+                    .key("lease"),
+            )
+            .expect("Failed to send lease message");
+        if let Err(e) = self.producer.flush(Duration::from_millis(self.lease)) {
+            eprintln!("Failed to flush the lease: {}", e);
+        }
+
+        let written = Instant::now();
+        while written.elapsed() < Duration::from_millis(self.lease) {
+            if let Some(current) = &self.log.lock().expect("Lease log poisoned").current {
+                if current.same(&lease) {
+                    return Some(current.clone());
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(self.time_out)).await;
+        }
+        None
+    }
+
+    /// Publishes a committee recorded in the lease, then records that it went out
+    async fn publish(&self, selection: &Selection) {
+        let epoch = selection.validator_set.epoch;
+        publish(selection, &self.producer, &self.producer2, self.time_out).await;
+        if self.write(|log, now| log.settle(&self.me, now, self.lease, epoch)).await.is_none() {
+            info!("Lost the lease before settling epoch {}", epoch);
+        }
+    }
+
+    /// Renews the lease while holding it and claims it once it expired. A replica that takes over
+    /// publishes the committee its predecessor recorded but may not have sent.
+    async fn keep(&self) {
+        loop {
+            tokio::time::sleep(Duration::from_millis(self.lease / 3)).await;
+
+            let held = self.log.lock().expect("Lease log poisoned").holds(&self.me, now_ms());
+            let current = self.write(|log, now| log.next(&self.me, now, self.lease)).await;
+            if let Some(selection) = current.filter(|_| !held).and_then(|current| current.selection) {
+                info!("Publishing the committee of epoch {} recorded by the previous leader", selection.validator_set.epoch);
+                self.publish(&selection).await;
+            }
+        }
+    }
+
+    /// Records and publishes the committee of an epoch while holding the lease. Followers and a leader
+    /// that lost the lease leave the epoch alone, and no epoch gets a second committee.
+    async fn decide(&self, strategy: &dyn SelectionStrategy, stakes: &[Stake], vals: usize, epoch: u64) -> bool {
+        if !self.log.lock().expect("Lease log poisoned").holds(&self.me, now_ms()) {
+            return false;
+        }
+
+        let Some(selection) = validator_selection(strategy, stakes, vals, epoch) else {
+            info!("Selected no validators for epoch {}, skipping it", epoch);
+            return false;
+        };
+        let current = self.write(|log, now| log.reserve(&self.me, now, self.lease, selection)).await;
+        match current.and_then(|current| current.selection) {
+            Some(selection) => {
+                self.publish(&selection).await;
+                true
+            }
+            None => false
+        }
+    }
+}
+
+fn kafka_producer(config: &ProducerConfig) -> BaseProducer {
+    ClientConfig::new()
+        .set("bootstrap.servers", &config.server)
+        .set("group.id", "master_id")
+        .set("acks", &config.acks)
+        .set("linger.ms", &config.lingerms)
+        .set("batch.size", &config.batchsize)
+        .set("compression.type", &config.compressiontype)
+        .set("enable.auto.commit",&config.autocommit)
+        .create()
+        .expect("Failed to create producer")
+}
+
 #[tokio::main]
 async fn main() {
-    let config = load_config().await.unwrap();
+    let (config_flag, _) = config::parse_args(std::env::args().skip(1));
+    let config_path = config::resolve_path(config_flag, "MASTER_CONFIG");
+    let config = match load_config(&config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    println!("Effective config from {}:\n{}", config_path, config::effective(&config));
+
+    // Replicas are told apart by their pod name
+    let me = std::env::var("HOSTNAME").ok().filter(|name| !name.is_empty())
+        .unwrap_or_else(|| hex::encode(rand::thread_rng().gen::<[u8; 8]>()));
+    println!("Master replica {}", me);
 
+    // Every replica reads all the stakes so a follower is ready to take over mid-epoch
     let consumer: StreamConsumer = ClientConfig::new()
-        .set("bootstrap.servers", config.consumer.server)
-        .set("group.id", "master")
+        .set("bootstrap.servers", &config.consumer.server)
+        .set("group.id", format!("master-{}", me))
         .set("enable.auto.commit",&config.consumer.autocommit)
         .set("auto.offset.reset", &config.consumer.autooffset)
         .set("acks", &config.consumer.acks)
         .create()
         .expect("Failed to create stream consumer");
 
-    let producer: BaseProducer = ClientConfig::new()
-        .set("bootstrap.servers", &config.producer.server)
-        .set("group.id", "master_id")
-        .set("acks", &config.producer.acks)
-        .set("linger.ms", &config.producer.lingerms)
-        .set("batch.size", &config.producer.batchsize)
-        .set("compression.type", &config.producer.compressiontype)
-        .set("enable.auto.commit",&config.producer.autocommit)
+    let lease_consumer: StreamConsumer = ClientConfig::new()
+        .set("bootstrap.servers", &config.consumer.server)
+        .set("group.id", format!("master-lease-{}", me))
+        .set("enable.auto.commit", "false")
+        .set("auto.offset.reset", "earliest")
         .create()
-        .expect("Failed to create producer");
+        .expect("Failed to create stream consumer");
 
-    let producer2: BaseProducer = ClientConfig::new()
-        .set("bootstrap.servers", &config.producer.server)
-        .set("group.id", "master_id")
-        .set("acks", &config.producer.acks)
-        .set("linger.ms", &config.producer.lingerms)
-        .set("batch.size", &config.producer.batchsize)
-        .set("compression.type", &config.producer.compressiontype)
-        .set("enable.auto.commit",&config.producer.autocommit)
+    // Only consensus from now on is accounted, the sets of earlier epochs may not be known
+    let liveness_consumer: StreamConsumer = ClientConfig::new()
+        .set("bootstrap.servers", &config.consumer.server)
+        .set("group.id", format!("master-liveness-{}", me))
+        .set("enable.auto.commit", "false")
+        .set("auto.offset.reset", "latest")
         .create()
-        .expect("Failed to create producer");
+        .expect("Failed to create stream consumer");
 
     consumer.subscribe(&["Stakes"]).expect("Failed to subscribe to topic");
-    
+    lease_consumer.subscribe(&["MasterLease"]).expect("Failed to subscribe to topic");
+    liveness_consumer.subscribe(&["Validators", "Preprepare", "Prepare", "Commit"]).expect("Failed to subscribe to topic");
+
+    let election = Arc::new(Election {
+        me,
+        lease: config.election.lease,
+        time_out: config.performance.timeout,
+        log: Mutex::new(LeaseLog::default()),
+        filter: Mutex::new(StakeFilter::default()),
+        producer: kafka_producer(&config.producer),
+        producer2: kafka_producer(&config.producer),
+        writing: tokio::sync::Mutex::new(()),
+    });
+
+    let follower = Arc::clone(&election);
+    tokio::spawn(async move { follower.follow(lease_consumer).await });
+    let keeper = Arc::clone(&election);
+    tokio::spawn(async move { keeper.keep().await });
+    let liveness = Arc::new(Mutex::new(LivenessTracker::default()));
+    tokio::spawn(track_liveness(liveness_consumer, Arc::clone(&liveness)));
+
+    let mut epoch: u64 = 0;
+    let mut strategy = selection::strategy(config.staking.strategy, config.staking.reputation_weight);
     loop {
-        let stakes: Option<Vec<Stake>> = listen_stake(&consumer, config.performance.timeout, &config.staking.validators).await;
+        let candidates = listen_stake(&consumer, &election.filter, config.performance.timeout, config.staking.deadline,
+            config.staking.replicas, epoch).await;
+
+        let stakes = candidates.to_vec();
+        strategy.observe(&stakes, candidates.epoch);
+
+        // The epoch before is over once nodes stake for this one
+        let stakes = {
+            let mut tracker = liveness.lock().expect("Liveness tracker poisoned");
+            let report = candidates.epoch.checked_sub(1)
+                .and_then(|previous| tracker.close(previous, config.liveness.threshold, config.liveness.jail_epochs));
+            if let Some(report) = report {
+                println!("Liveness of epoch {}: {}", report.epoch,
+                    serde_json::to_string(&report).expect("Failed to serialize liveness metrics"));
+            }
+            tracker.eligible(stakes, candidates.epoch)
+        };
 
-        match stakes {
-            Option::None => continue,
-            Some(stakes_vec) => {
-                info!("Stakes received");
-                validator_selection(&stakes_vec.as_slice(), config.staking.validators, &producer,
-            &producer2,config.performance.timeout);
-                info!("Validators selected");
-        },
+        let vals = committee_size(&stakes, config.staking.validators);
+        match election.decide(strategy.as_ref(), &stakes, vals, candidates.epoch).await {
+            true => info!("Validators selected for epoch {}", candidates.epoch),
+            false => info!("Left epoch {} to the leader", candidates.epoch)
         }
+
+        epoch = candidates.epoch + 1;
     }
-}
\ No newline at end of file
+}
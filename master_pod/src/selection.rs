@@ -0,0 +1,159 @@
+use crate::stake::Stake;
+use serde::{Serialize, Deserialize};
+use rand::Rng;
+use rand_distr::{Distribution, WeightedIndex};
+use std::collections::{HashMap, HashSet};
+
+/// Committee selection strategies the master can be configured with
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// Stake-weighted sampling without replacement and a uniformly drawn primary
+    #[default]
+    Weighted,
+    /// The highest stakes, the highest of them is primary
+    TopK,
+    /// Candidates in node id order, taking turns epoch by epoch
+    RoundRobin,
+    /// The highest scores mixing stake and how often the node staked in earlier epochs
+    Reputation,
+}
+
+/// Picks the committee of an epoch from its candidates. `stakes` are in node id order and `vals`
+/// is at most their number.
+pub trait SelectionStrategy: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Indices into `stakes` of the committee, the first of them is the primary
+    fn select(&self, stakes: &[Stake], vals: usize, epoch: u64) -> Vec<usize>;
+
+    /// Sees the candidates of every epoch, whether or not this replica selects its committee
+    fn observe(&mut self, _stakes: &[Stake], _epoch: u64) {}
+}
+
+pub fn strategy(kind: Strategy, reputation_weight: f64) -> Box<dyn SelectionStrategy> {
+    match kind {
+        Strategy::Weighted => Box::new(Weighted),
+        Strategy::TopK => Box::new(TopK),
+        Strategy::RoundRobin => Box::new(RoundRobin),
+        Strategy::Reputation => Box::new(Reputation::new(reputation_weight)),
+    }
+}
+
+/// Indices of the `vals` highest scores, ties going to the lower node id
+fn highest(stakes: &[Stake], vals: usize, score: impl Fn(&Stake) -> f64) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..stakes.len()).collect();
+    indices.sort_by(|a, b| score(&stakes[*b]).total_cmp(&score(&stakes[*a]))
+        .then_with(|| stakes[*a].node_id.cmp(&stakes[*b].node_id)));
+    indices.truncate(vals);
+    indices
+}
+
+pub struct Weighted;
+
+impl SelectionStrategy for Weighted {
+    fn name(&self) -> &'static str {
+        "weighted"
+    }
+
+    // Stakes that can not be weighted are never drawn, so at most the positive ones are selected
+    fn select(&self, stakes: &[Stake], vals: usize, _epoch: u64) -> Vec<usize> {
+        let weights: Vec<f64> = stakes.iter()
+            .map(|s| if s.stake.is_finite() && s.stake > 0.0 { s.stake } else { 0.0 })
+            .collect();
+        let vals = vals.min(weights.iter().filter(|weight| **weight > 0.0).count());
+        if vals == 0 {
+            return vec![];
+        }
+
+        let mut rng = rand::thread_rng();
+        let dist = WeightedIndex::new(&weights).expect("Invalid weights");
+
+        let mut selected_indices = HashSet::new();
+        let mut selected = Vec::new();
+
+        while selected.len() < vals {
+            let index = dist.sample(&mut rng);
+
+            if selected_indices.insert(index) {
+                selected.push(index);
+            }
+        }
+
+        let primary_index = rng.gen_range(0..selected.len());
+        selected.swap(0, primary_index);
+        selected
+    }
+}
+
+pub struct TopK;
+
+impl SelectionStrategy for TopK {
+    fn name(&self) -> &'static str {
+        "top_k"
+    }
+
+    fn select(&self, stakes: &[Stake], vals: usize, _epoch: u64) -> Vec<usize> {
+        highest(stakes, vals, |stake| stake.stake)
+    }
+}
+
+/// Rotates through the candidates by `vals` every epoch. When candidates come and go the turns
+/// shift with them.
+pub struct RoundRobin;
+
+impl SelectionStrategy for RoundRobin {
+    fn name(&self) -> &'static str {
+        "round_robin"
+    }
+
+    fn select(&self, stakes: &[Stake], vals: usize, epoch: u64) -> Vec<usize> {
+        if stakes.is_empty() {
+            return vec![];
+        }
+        let start = (epoch as usize).wrapping_mul(vals) % stakes.len();
+        (0..vals).map(|offset| (start + offset) % stakes.len()).collect()
+    }
+}
+
+/// Scores candidates by `(1 - weight)` times their stake relative to the highest one plus `weight`
+/// times the share of observed epochs they staked in, so nodes that keep staking are preferred
+pub struct Reputation {
+    pub weight: f64,
+    pub epochs: u64,
+    pub staked: HashMap<String, u64>,
+}
+
+impl Reputation {
+    pub fn new(weight: f64) -> Self {
+        Reputation { weight, epochs: 0, staked: HashMap::new() }
+    }
+
+    pub fn reputation(&self, node_id: &str) -> f64 {
+        match self.epochs {
+            0 => 0.0,
+            epochs => self.staked.get(node_id).copied().unwrap_or(0) as f64 / epochs as f64
+        }
+    }
+}
+
+impl SelectionStrategy for Reputation {
+    fn name(&self) -> &'static str {
+        "reputation"
+    }
+
+    fn select(&self, stakes: &[Stake], vals: usize, _epoch: u64) -> Vec<usize> {
+        let max_stake = stakes.iter().map(|stake| stake.stake).fold(0.0, f64::max);
+        highest(stakes, vals, |stake| {
+            let relative = if max_stake > 0.0 { stake.stake / max_stake } else { 0.0 };
+            (1.0 - self.weight) * relative + self.weight * self.reputation(&stake.node_id)
+        })
+    }
+
+    fn observe(&mut self, stakes: &[Stake], _epoch: u64) {
+        self.epochs += 1;
+        for stake in stakes {
+            *self.staked.entry(stake.node_id.clone()).or_insert(0) += 1;
+        }
+    }
+}
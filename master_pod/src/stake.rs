@@ -1,10 +1,136 @@
 use serde::{Serialize as SerdeSerialize, Deserialize};
 use serde_json::{from_str, to_string};
+use openssl::sha::Sha256;
+use bls_signatures::{PublicKey, Serialize, Signature};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 #[derive(SerdeSerialize, Deserialize, Clone)]
 pub struct Stake {
     pub node_id: String,
     pub stake: f64,
+    /// Committee size in effect on the node's chain, when governance has set one
+    #[serde(default)]
+    pub committee_size: Option<usize>,
+    /// Epoch the node stakes for, from its chain height
+    #[serde(default)]
+    pub epoch: u64,
+    /// BLS key the node registers, the hex of which is its node id
+    #[serde(default)]
+    pub public_key: String,
+    /// Signature by `public_key` over `possession_message`
+    #[serde(default)]
+    pub proof_of_possession: String,
+    /// Milliseconds since the Unix epoch when the node signed the stake
+    #[serde(default)]
+    pub nonce: u64,
+    /// Signature by `public_key` over `stake_message`
+    #[serde(default)]
+    pub signature: String,
+}
+
+/// Prefix of the message a stake signature covers, the same as node_pod's
+pub const STAKE_DOMAIN: &str = "node_pod stake:";
+
+/// Rebuilds what the node signed, field by field as node_pod formats it
+pub fn stake_message(stake: &Stake) -> String {
+    let committee_size = stake.committee_size.map(|size| size.to_string()).unwrap_or_else(|| String::from("none"));
+    format!("{}{}:{}:{}:{}:{}", STAKE_DOMAIN, stake.node_id, stake.stake, stake.epoch, stake.nonce, committee_size)
+}
+
+/// Why a stake did not become a candidate
+#[derive(Clone, Copy, std::fmt::Debug, PartialEq)]
+pub enum Rejection {
+    Unsigned,
+    /// The signature does not verify under the key the node id names
+    Forged,
+    /// The proof of possession does not verify
+    Unproven,
+    /// The amount staked is not a positive finite number
+    Invalid,
+    /// The nonce is not above the last one accepted from the node
+    Replayed,
+    /// The epoch is already over
+    Stale,
+}
+
+/// Stakes rejected since the master started, by reason
+#[derive(SerdeSerialize, Default, Clone, std::fmt::Debug, PartialEq)]
+pub struct RejectMetrics {
+    pub unsigned: u64,
+    pub forged: u64,
+    pub unproven: u64,
+    pub invalid: u64,
+    pub replayed: u64,
+    pub stale: u64,
+}
+
+impl RejectMetrics {
+    pub fn record(&mut self, rejection: Rejection) {
+        match rejection {
+            Rejection::Unsigned => self.unsigned += 1,
+            Rejection::Forged => self.forged += 1,
+            Rejection::Unproven => self.unproven += 1,
+            Rejection::Invalid => self.invalid += 1,
+            Rejection::Replayed => self.replayed += 1,
+            Rejection::Stale => self.stale += 1,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.unsigned + self.forged + self.unproven + self.invalid + self.replayed + self.stale
+    }
+}
+
+/// Authenticates stakes before they are counted, remembering the highest nonce accepted per node
+#[derive(Default)]
+pub struct StakeFilter {
+    pub nonces: HashMap<String, u64>,
+    /// Latest epoch a committee was decided for, stakes for it or an earlier epoch are spent
+    pub decided: Option<u64>,
+    pub rejects: RejectMetrics,
+}
+
+impl StakeFilter {
+    pub fn check(&mut self, stake: &Stake) -> Result<(), Rejection> {
+        let outcome = if stake.signature.is_empty() {
+            Err(Rejection::Unsigned)
+        } else if !stake.verify_signature() {
+            Err(Rejection::Forged)
+        } else if !stake.verify_possession() {
+            Err(Rejection::Unproven)
+        } else if !stake.stake.is_finite() || stake.stake <= 0.0 {
+            Err(Rejection::Invalid)
+        } else if self.decided.is_some_and(|decided| stake.epoch <= decided) {
+            Err(Rejection::Stale)
+        } else if self.nonces.get(&stake.node_id).is_some_and(|nonce| stake.nonce <= *nonce) {
+            Err(Rejection::Replayed)
+        } else {
+            self.nonces.insert(stake.node_id.clone(), stake.nonce);
+            Ok(())
+        };
+
+        if let Err(rejection) = outcome {
+            self.rejects.record(rejection);
+        }
+        outcome
+    }
+
+    /// Spends the stakes a committee decided for `epoch` was picked from. Every replica applies the
+    /// committees the lease log records, so one that restarted or follows refuses them as well.
+    pub fn commit(&mut self, epoch: u64, validators: &[Validator]) {
+        self.decided = self.decided.max(Some(epoch));
+        for validator in validators {
+            let nonce = self.nonces.entry(validator.node_id.clone()).or_default();
+            *nonce = validator.nonce.max(*nonce);
+        }
+    }
+}
+
+/// Prefix of the message a proof of possession signs, the same as node_pod's
+pub const POSSESSION_DOMAIN: &str = "node_pod proof of possession:";
+
+pub fn possession_message(public_key: &str) -> String {
+    format!("{}{}", POSSESSION_DOMAIN, public_key)
 }
 
 impl Stake {
@@ -17,17 +143,50 @@ impl Stake {
         let stake: Stake = from_str(&json_str).expect("Failed to deserialize");
         stake
     }
+
+    /// Whether the stake was signed by the key its node id names
+    pub fn verify_signature(&self) -> bool {
+        let public_key = match hex::decode(&self.node_id).ok().and_then(|bytes| PublicKey::from_bytes(&bytes).ok()) {
+            Some(public_key) => public_key,
+            None => { return false; }
+        };
+        match hex::decode(&self.signature).ok().and_then(|bytes| Signature::from_bytes(&bytes).ok()) {
+            Some(signature) => public_key.verify(signature, stake_message(self)),
+            None => false
+        }
+    }
+
+    /// Whether the stake proves its sender holds the key it registers. Without the proof a node
+    /// could register a key built from other validators' keys and forge their aggregate signatures.
+    pub fn verify_possession(&self) -> bool {
+        let public_key = match hex::decode(&self.public_key).ok().and_then(|bytes| PublicKey::from_bytes(&bytes).ok()) {
+            Some(public_key) => public_key,
+            None => { return false; }
+        };
+        let proof = match hex::decode(&self.proof_of_possession).ok().and_then(|bytes| Signature::from_bytes(&bytes).ok()) {
+            Some(proof) => proof,
+            None => { return false; }
+        };
+
+        self.node_id == self.public_key && public_key.verify(proof, possession_message(&self.public_key))
+    }
 }
 
 #[derive(SerdeSerialize, Deserialize, Clone)]
 pub struct Validator {
     pub node_id: String,
-    pub public_key: String
+    pub public_key: String,
+    pub stake: f64,
+    pub proof_of_possession: String,
+    /// Nonce of the stake the validator was picked with, not covered by the set's hash
+    #[serde(default)]
+    pub nonce: u64,
 }
 
 impl Validator {
     pub fn from_stake(stake: &Stake) -> Self {
-        Validator { node_id: stake.node_id.clone(), public_key: stake.node_id.clone() }
+        Validator { node_id: stake.node_id.clone(), public_key: stake.public_key.clone(), stake: stake.stake,
+            proof_of_possession: stake.proof_of_possession.clone(), nonce: stake.nonce }
     }
 
     pub fn serialize(&self) -> String {
@@ -39,4 +198,102 @@ impl Validator {
         let validator: Validator = from_str(&json_str).expect("Failed to deserialize");
         validator
     }
-}
\ No newline at end of file
+}
+
+/// Stakes collected for one epoch, keyed by node id so a node that stakes twice is only a candidate once
+pub struct Candidates {
+    pub epoch: u64,
+    pub stakes: BTreeMap<String, Stake>,
+}
+
+impl Candidates {
+    pub fn new(epoch: u64) -> Self {
+        Candidates { epoch, stakes: BTreeMap::new() }
+    }
+
+    /// Moves collection on when a node stakes for a later epoch, as after a master restart the
+    /// chain is ahead of the master. Returns false for stakes for an epoch that is already over.
+    pub fn follow(&mut self, epoch: u64) -> bool {
+        if epoch > self.epoch {
+            self.epoch = epoch;
+            self.stakes.clear();
+        }
+        epoch == self.epoch
+    }
+
+    /// Returns false when the node already staked this epoch, its latest stake replaces the earlier one
+    pub fn insert(&mut self, stake: Stake) -> bool {
+        self.stakes.insert(stake.node_id.clone(), stake).is_none()
+    }
+
+    pub fn len(&self) -> usize {
+        self.stakes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stakes.is_empty()
+    }
+
+    /// Stakes in node id order, so selection does not depend on the order stakes arrived in
+    pub fn to_vec(&self) -> Vec<Stake> {
+        self.stakes.values().cloned().collect()
+    }
+}
+
+/// Candidates that were not selected for an epoch, highest stake first, which can step in for the committee
+#[derive(SerdeSerialize, Deserialize, Clone)]
+pub struct Standby {
+    pub epoch: u64,
+    pub validators: Vec<Validator>,
+}
+
+impl Standby {
+    pub fn from_candidates(stakes: &[Stake], selected: &HashSet<usize>, epoch: u64) -> Self {
+        let mut standby: Vec<&Stake> = stakes.iter().enumerate()
+            .filter(|(index, _)| !selected.contains(index))
+            .map(|(_, stake)| stake)
+            .collect();
+        standby.sort_by(|a, b| b.stake.total_cmp(&a.stake).then_with(|| a.node_id.cmp(&b.node_id)));
+
+        Standby { epoch, validators: standby.into_iter().map(Validator::from_stake).collect() }
+    }
+
+    pub fn serialize(&self) -> String {
+        to_string(&self).expect("Failed to serialize")
+    }
+}
+
+/// Committee of an epoch, published as one message so nodes can tell which epoch it belongs to.
+/// `hash` is computed the same way the nodes do and is committed on chain.
+#[derive(SerdeSerialize, Deserialize, Clone)]
+pub struct ValidatorSet {
+    pub epoch: u64,
+    pub validators: Vec<Validator>,
+    pub hash: String,
+    /// Selection strategy that picked the committee, not covered by `hash`
+    #[serde(default)]
+    pub strategy: String,
+}
+
+impl ValidatorSet {
+    pub fn new(epoch: u64, validators: Vec<Validator>) -> Self {
+        let mut sorted: Vec<&Validator> = validators.iter().collect();
+        sorted.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+
+        let mut hasher = Sha256::new();
+        hasher.update(&epoch.to_be_bytes());
+        for validator in sorted {
+            hasher.update(&(validator.node_id.len() as u64).to_be_bytes());
+            hasher.update(validator.node_id.as_bytes());
+            hasher.update(&(validator.public_key.len() as u64).to_be_bytes());
+            hasher.update(validator.public_key.as_bytes());
+            hasher.update(&validator.stake.to_be_bytes());
+        }
+
+        ValidatorSet { epoch, validators, hash: hex::encode(hasher.finish()), strategy: String::new() }
+    }
+
+    pub fn serialize(&self) -> String {
+        to_string(&self).expect("Failed to serialize")
+    }
+}
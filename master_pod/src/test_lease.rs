@@ -0,0 +1,86 @@
+#[cfg(test)]
+mod tests {
+    use crate::lease::{Lease, LeaseLog, Selection};
+    use crate::stake::{Standby, Validator, ValidatorSet};
+
+    fn selection(epoch: u64) -> Selection {
+        let validator = Validator { node_id: String::from("a"), public_key: String::from("a"), stake: 1.0,
+            proof_of_possession: String::new(), nonce: 0 };
+        Selection { validator_set: ValidatorSet::new(epoch, vec![validator.clone()]),
+            standby: Standby { epoch, validators: vec![] }, primary: validator }
+    }
+
+    fn lease(holder: &str, term: u64, issued: u64, epoch: Option<u64>) -> Lease {
+        Lease { holder: holder.to_string(), term, issued, expires: issued + 100, epoch, selection: None }
+    }
+
+    #[test]
+    fn test_takeover_after_expiry() {
+        let mut log = LeaseLog::default();
+        assert!(log.apply(log.next("m1", 0, 100).unwrap()));
+        assert!(log.holds("m1", 50) && !log.holds("m2", 50));
+        assert!(log.next("m2", 50, 100).is_none());
+
+        // Both claim the first term, the log keeps whichever came first
+        assert!(!log.apply(lease("m2", 1, 10, None)));
+        assert!(!log.apply(lease("m2", 2, 99, None)));
+        assert!(log.apply(log.next("m1", 90, 100).unwrap()));
+        assert!(!log.holds("m1", 190));
+
+        let claim = log.next("m2", 190, 100).unwrap();
+        assert_eq!(claim.term, 2);
+        assert!(log.apply(claim));
+        assert!(log.holds("m2", 200));
+
+        // The old leader's renewal comes too late
+        assert!(!log.apply(lease("m1", 1, 195, None)));
+        assert_eq!(log.current.as_ref().unwrap().holder, "m2");
+    }
+
+    #[test]
+    fn test_one_committee_per_epoch() {
+        let mut log = LeaseLog::default();
+        log.apply(log.next("m1", 0, 100).unwrap());
+        assert!(log.reserve("m2", 10, 100, selection(0)).is_none());
+
+        let reserved = log.reserve("m1", 10, 100, selection(0)).unwrap();
+        assert!(log.apply(reserved));
+        assert!(log.reserve("m1", 20, 100, selection(0)).is_none());
+        assert_eq!(log.epoch(), Some(0));
+
+        assert!(log.apply(log.settle("m1", 30, 100, 0).unwrap()));
+        assert!(log.settle("m1", 40, 100, 0).is_none());
+        // A renewal written before the committee was settled does not reopen it
+        assert!(!log.apply(Lease { selection: Some(selection(0)), ..lease("m1", 1, 35, Some(0)) }));
+        assert!(!log.apply(lease("m1", 1, 35, None)));
+
+        assert!(log.apply(log.reserve("m1", 50, 100, selection(1)).unwrap()));
+        assert_eq!(log.epoch(), Some(1));
+    }
+
+    #[test]
+    fn test_takeover_inherits_committee() {
+        let mut log = LeaseLog::default();
+        log.apply(log.next("m1", 0, 100).unwrap());
+        log.apply(log.reserve("m1", 10, 100, selection(4)).unwrap());
+
+        // m2 missed the reservation, claiming takes it over anyway
+        assert!(log.apply(lease("m2", 2, 110, Some(3))));
+        let current = log.current.as_ref().unwrap();
+        assert_eq!(current.epoch, Some(4));
+        assert_eq!(current.selection.as_ref().unwrap().validator_set.hash, selection(4).validator_set.hash);
+
+        assert!(log.reserve("m2", 120, 100, selection(4)).is_none());
+        assert!(log.settle("m1", 120, 100, 4).is_none());
+        assert!(log.apply(log.settle("m2", 120, 100, 4).unwrap()));
+    }
+
+    #[test]
+    fn test_lease_roundtrip() {
+        let written = Lease { selection: Some(selection(2)), ..lease("m1", 3, 7, Some(2)) };
+        let read = Lease::deserialize(&written.serialize()).unwrap();
+        assert!(read.same(&written));
+        assert_eq!(read.selection.unwrap().validator_set.epoch, 2);
+        assert!(Lease::deserialize("{}").is_none());
+    }
+}
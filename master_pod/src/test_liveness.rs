@@ -0,0 +1,99 @@
+#[cfg(test)]
+mod tests {
+    use crate::liveness::{ConsensusMessage, LivenessTracker, COMMIT_DOMAIN, PREPARE_DOMAIN, PREPREPARE_DOMAIN};
+    use crate::stake::{Stake, Validator, ValidatorSet};
+    use bls_signatures::{PrivateKey, Serialize};
+
+    fn committee(size: usize, epoch: u64) -> (Vec<PrivateKey>, ValidatorSet) {
+        let keys: Vec<PrivateKey> = (0..size).map(|_| PrivateKey::generate(&mut rand::thread_rng())).collect();
+        let validators = keys.iter().map(|key| {
+            let public_key = hex::encode(key.public_key().as_bytes());
+            Validator { node_id: public_key.clone(), public_key, stake: 1.0, proof_of_possession: String::new(),
+                nonce: 0 }
+        }).collect();
+        (keys, ValidatorSet::new(epoch, validators))
+    }
+
+    // Shaped like node_pod's NodeMessage, whose signature covers the block inside msg_type behind the phase's domain
+    fn signed_as(key: &PrivateKey, phase: &str, domain: &str, set: &ValidatorSet, height: u64, proposer: &str) -> ConsensusMessage {
+        let block = serde_json::json!({ "hash": format!("block{}", height), "index": height, "proposer": proposer,
+            "validator_set_hash": set.hash, "transactions": [] }).to_string();
+        let signed = format!("{}{}", domain, block);
+        let json = serde_json::json!({ "msg_type": { phase: signed }, "block": {}, "seq_num": 0,
+            "signature": hex::encode(key.sign(&signed).as_bytes()),
+            "sender_id": hex::encode(key.public_key().as_bytes()) });
+        ConsensusMessage::deserialize(&json.to_string()).unwrap()
+    }
+
+    fn message(key: &PrivateKey, phase: &str, set: &ValidatorSet, height: u64, proposer: &str) -> ConsensusMessage {
+        let domain = match phase {
+            "PrePrepare" => PREPREPARE_DOMAIN,
+            "Prepare" => PREPARE_DOMAIN,
+            _ => COMMIT_DOMAIN
+        };
+        signed_as(key, phase, domain, set, height, proposer)
+    }
+
+    #[test]
+    fn test_missed_phases() {
+        let (keys, set) = committee(4, 3);
+        let ids: Vec<String> = set.validators.iter().map(|validator| validator.node_id.clone()).collect();
+        let mut tracker = LivenessTracker::default();
+        tracker.add_set(set.clone());
+
+        for height in 1..=2 {
+            assert!(tracker.record(&message(&keys[0], "PrePrepare", &set, height, &ids[0])));
+            for key in &keys[..3] {
+                tracker.record(&message(key, "Prepare", &set, height, &ids[0]));
+                tracker.record(&message(key, "Commit", &set, height, &ids[0]));
+            }
+        }
+        // Two commits of four do not commit a block
+        for key in &keys[..2] {
+            tracker.record(&message(key, "Commit", &set, 3, &ids[0]));
+        }
+
+        let report = tracker.close(3, 0.5, 2).unwrap();
+        assert_eq!(report.blocks, 2);
+        assert_eq!((report.validators[0].missed_preprepares, report.validators[0].liveness), (0, 1.0));
+        assert_eq!((report.validators[3].missed_prepares, report.validators[3].missed_commits), (2, 2));
+        assert_eq!(report.validators[3].liveness, 0.0);
+        assert_eq!(report.jailed, vec![ids[3].clone()]);
+
+        assert!(tracker.is_jailed(&ids[3], 4) && tracker.is_jailed(&ids[3], 5));
+        assert!(!tracker.is_jailed(&ids[3], 6) && !tracker.is_jailed(&ids[0], 4));
+        assert!(tracker.close(3, 0.5, 2).is_none());
+    }
+
+    #[test]
+    fn test_rejected_messages() {
+        let (keys, set) = committee(2, 1);
+        let (outsiders, other) = committee(1, 1);
+        let mut tracker = LivenessTracker::default();
+        tracker.add_set(set.clone());
+
+        assert!(!tracker.record(&message(&outsiders[0], "Prepare", &set, 1, "")));
+        assert!(!tracker.record(&message(&keys[0], "Prepare", &other, 1, "")));
+        let mut forged = message(&keys[0], "Prepare", &set, 1, "");
+        forged.sender_id = set.validators[1].node_id.clone();
+        assert!(!tracker.record(&forged));
+        // A Prepare signature does not count as a Commit, nor does a block signed without a domain
+        assert!(!tracker.record(&signed_as(&keys[0], "Commit", PREPARE_DOMAIN, &set, 1, "")));
+        assert!(!tracker.record(&signed_as(&keys[0], "Commit", "", &set, 1, "")));
+        assert!(tracker.record(&message(&keys[1], "Prepare", &set, 1, "")));
+    }
+
+    #[test]
+    fn test_eligible() {
+        let mut tracker = LivenessTracker::default();
+        tracker.jailed.insert(String::from("a"), 5);
+        let stake = |node_id: &str| Stake { node_id: node_id.to_string(), stake: 1.0, committee_size: None, epoch: 4,
+            public_key: node_id.to_string(), proof_of_possession: String::new(), nonce: 0, signature: String::new() };
+
+        let eligible = tracker.eligible(vec![stake("a"), stake("b")], 4);
+        assert_eq!(eligible.len(), 1);
+        assert_eq!(eligible[0].node_id, "b");
+        assert_eq!(tracker.eligible(vec![stake("a")], 4).len(), 1);
+        assert_eq!(tracker.eligible(vec![stake("a"), stake("b")], 5).len(), 2);
+    }
+}
@@ -0,0 +1,78 @@
+#[cfg(test)]
+mod tests {
+    use crate::selection::{strategy, Reputation, RoundRobin, SelectionStrategy, Strategy, TopK, Weighted};
+    use crate::stake::Stake;
+    use crate::{committee_size, validator_selection};
+    use std::collections::HashSet;
+
+    fn stakes(amounts: &[(&str, f64)]) -> Vec<Stake> {
+        amounts.iter().map(|(node_id, stake)| Stake { node_id: node_id.to_string(), stake: *stake, committee_size: None,
+            epoch: 0, public_key: node_id.to_string(), proof_of_possession: String::new(), nonce: 0,
+            signature: String::new() }).collect()
+    }
+
+    #[test]
+    fn test_weighted() {
+        let stakes = stakes(&[("a", 1.0), ("b", 5.0), ("c", 2.0), ("d", 0.5)]);
+        let selected = Weighted.select(&stakes, 3, 0);
+        assert_eq!(selected.iter().collect::<HashSet<_>>().len(), 3);
+        assert!(selected.iter().all(|index| *index < stakes.len()));
+    }
+
+    #[test]
+    fn test_weighted_without_enough_weight() {
+        let stakes = stakes(&[("a", 0.0), ("b", 5.0), ("c", f64::NAN), ("d", -1.0)]);
+        assert_eq!(Weighted.select(&stakes, 3, 0), vec![1]);
+        assert!(Weighted.select(&stakes[..1], 1, 0).is_empty());
+    }
+
+    #[test]
+    fn test_top_k() {
+        let stakes = stakes(&[("a", 1.0), ("b", 5.0), ("c", 2.0), ("d", 2.0)]);
+        assert_eq!(TopK.select(&stakes, 3, 0), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let stakes = stakes(&[("a", 1.0), ("b", 5.0), ("c", 2.0)]);
+        assert_eq!(RoundRobin.select(&stakes, 2, 0), vec![0, 1]);
+        assert_eq!(RoundRobin.select(&stakes, 2, 1), vec![2, 0]);
+        assert_eq!(RoundRobin.select(&stakes, 2, 2), vec![1, 2]);
+        assert!(RoundRobin.select(&[], 1, 3).is_empty());
+    }
+
+    #[test]
+    fn test_reputation() {
+        let mut reputation = Reputation::new(0.5);
+        let steady = stakes(&[("a", 4.0), ("b", 5.0)]);
+        reputation.observe(&steady[..1], 0);
+        reputation.observe(&steady[..1], 1);
+
+        // b stakes more but a staked in every epoch so far
+        reputation.observe(&steady, 2);
+        assert_eq!(reputation.reputation("a"), 1.0);
+        assert_eq!(reputation.select(&steady, 1, 2), vec![0]);
+        assert_eq!(Reputation::new(0.0).select(&steady, 1, 2), vec![1]);
+    }
+
+    #[test]
+    fn test_strategy_recorded() {
+        let stakes = stakes(&[("a", 1.0), ("b", 5.0), ("c", 2.0)]);
+        let top_k = strategy(Strategy::TopK, 0.5);
+        let selection = validator_selection(top_k.as_ref(), &stakes, 2, 6).unwrap();
+
+        assert_eq!(selection.validator_set.strategy, "top_k");
+        assert_eq!(selection.primary.node_id, "b");
+        assert_eq!(selection.standby.validators.len(), 1);
+        assert_eq!(serde_yaml::from_str::<Strategy>("round_robin").unwrap(), Strategy::RoundRobin);
+    }
+
+    #[test]
+    fn test_nothing_selected() {
+        let unweighted = stakes(&[("a", 0.0)]);
+        assert!(validator_selection(&Weighted, &unweighted, 1, 2).is_none());
+        for kind in [Strategy::Weighted, Strategy::TopK, Strategy::RoundRobin, Strategy::Reputation] {
+            assert!(validator_selection(strategy(kind, 0.5).as_ref(), &[], committee_size(&[], 3), 2).is_none());
+        }
+    }
+}
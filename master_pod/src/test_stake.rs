@@ -0,0 +1,158 @@
+#[cfg(test)]
+mod tests {
+    use crate::stake::{possession_message, stake_message, Candidates, RejectMetrics, Rejection, Stake, StakeFilter, Standby,
+        Validator, ValidatorSet};
+    use bls_signatures::{PrivateKey, Serialize};
+    use crate::committee_size;
+    use std::collections::HashSet;
+
+    fn stake(node_id: &str, stake: f64, committee_size: Option<usize>) -> Stake {
+        Stake { node_id: node_id.to_string(), stake, committee_size, epoch: 0, public_key: node_id.to_string(),
+            proof_of_possession: String::new(), nonce: 0, signature: String::new() }
+    }
+
+    #[test]
+    fn test_duplicate_stakes() {
+        let mut candidates = Candidates::new(3);
+        assert!(candidates.insert(stake("b", 10.0, None)));
+        assert!(candidates.insert(stake("a", 20.0, None)));
+        assert!(!candidates.insert(stake("b", 30.0, None)));
+
+        let stakes = candidates.to_vec();
+        assert_eq!(stakes.len(), 2);
+        assert_eq!((stakes[0].node_id.as_str(), stakes[1].node_id.as_str()), ("a", "b"));
+        assert_eq!(stakes[1].stake, 30.0);
+    }
+
+    #[test]
+    fn test_follow_epoch() {
+        let mut candidates = Candidates::new(3);
+        candidates.insert(stake("a", 20.0, None));
+
+        assert!(!candidates.follow(2));
+        assert!(candidates.follow(3));
+        assert_eq!(candidates.len(), 1);
+
+        assert!(candidates.follow(5));
+        assert_eq!(candidates.epoch, 5);
+        assert!(candidates.is_empty());
+    }
+
+    #[test]
+    fn test_committee_size() {
+        let stakes = vec![stake("a", 5.0, Some(3)), stake("b", 2.0, Some(3)), stake("c", 1.0, Some(2)), stake("d", 1.0, None)];
+        assert_eq!(committee_size(&stakes, 1), 3);
+        assert_eq!(committee_size(&stakes[3..], 2), 1);
+        assert_eq!(committee_size(&stakes[1..], 5), 3);
+
+        // Many small stakers reporting the same size are outweighed
+        let sybils = vec![stake("a", 10.0, Some(3)), stake("x", 1.0, Some(4)), stake("y", 1.0, Some(4)), stake("z", 1.0, Some(4))];
+        assert_eq!(committee_size(&sybils, 1), 3);
+        let split = vec![stake("a", 2.0, Some(3)), stake("x", 1.0, Some(4)), stake("y", 1.0, Some(4)), stake("z", 1.0, Some(4))];
+        assert_eq!(committee_size(&split, 1), 1);
+    }
+
+    #[test]
+    fn test_standby() {
+        let stakes = vec![stake("a", 5.0, None), stake("b", 9.0, None), stake("c", 7.0, None), stake("d", 7.0, None)];
+        let selected: HashSet<usize> = [1].into_iter().collect();
+
+        let standby = Standby::from_candidates(&stakes, &selected, 4);
+        let ids: Vec<&str> = standby.validators.iter().map(|validator| validator.node_id.as_str()).collect();
+        assert_eq!(ids, vec!["c", "d", "a"]);
+        assert_eq!(standby.epoch, 4);
+    }
+
+    // Nodes check the hash and commit it on chain, node_pod's test_network pins the same value
+    #[test]
+    fn test_validator_set_hash() {
+        let validator = |node_id: &str, stake: f64| Validator { node_id: node_id.to_string(), public_key: node_id.repeat(2), stake,
+            proof_of_possession: String::new(), nonce: 0 };
+        let set = ValidatorSet::new(7, vec![validator("b", 2.5), validator("a", 1.0)]);
+        assert_eq!(set.hash, "ff40e2cab3ba69dda65c533ddb071e3250c2b399c39963c1dbef83060a1730f6");
+    }
+
+    fn signed_stake(key: &PrivateKey, nonce: u64) -> Stake {
+        signed_amount(key, nonce, 1.0)
+    }
+
+    fn signed_amount(key: &PrivateKey, nonce: u64, amount: f64) -> Stake {
+        let public_key = hex::encode(key.public_key().as_bytes());
+        let proof = hex::encode(key.sign(possession_message(&public_key)).as_bytes());
+        let mut stake = Stake { node_id: public_key.clone(), stake: amount, committee_size: None, epoch: 0, public_key,
+            proof_of_possession: proof, nonce, signature: String::new() };
+        stake.signature = hex::encode(key.sign(stake_message(&stake)).as_bytes());
+        stake
+    }
+
+    #[test]
+    fn test_proof_of_possession() {
+        let (key, other) = (PrivateKey::generate(&mut rand::thread_rng()), PrivateKey::generate(&mut rand::thread_rng()));
+        let stake = signed_stake(&key, 1);
+        assert!(stake.verify_possession());
+        assert_eq!(Validator::from_stake(&stake).proof_of_possession, stake.proof_of_possession);
+
+        let rogue = Stake { proof_of_possession: signed_stake(&other, 1).proof_of_possession, ..stake.clone() };
+        assert!(!rogue.verify_possession());
+        let renamed = Stake { node_id: String::from("someone"), ..stake.clone() };
+        assert!(!renamed.verify_possession());
+        let plain = Stake { proof_of_possession: hex::encode(key.sign(&stake.public_key).as_bytes()), ..stake.clone() };
+        assert!(!plain.verify_possession());
+    }
+
+    #[test]
+    fn test_stake_filter() {
+        let (key, other) = (PrivateKey::generate(&mut rand::thread_rng()), PrivateKey::generate(&mut rand::thread_rng()));
+        let mut filter = StakeFilter::default();
+        let stake = signed_stake(&key, 5);
+
+        assert_eq!(filter.check(&Stake { signature: String::new(), ..stake.clone() }), Err(Rejection::Unsigned));
+        assert_eq!(filter.check(&Stake { stake: 400.0, ..stake.clone() }), Err(Rejection::Forged));
+        let impostor = signed_stake(&other, 5);
+        assert_eq!(filter.check(&Stake { node_id: stake.node_id.clone(), ..impostor }), Err(Rejection::Forged));
+
+        assert_eq!(filter.check(&signed_amount(&key, 6, f64::NAN)), Err(Rejection::Invalid));
+        assert_eq!(filter.check(&signed_amount(&key, 6, 0.0)), Err(Rejection::Invalid));
+        assert_eq!(filter.check(&signed_amount(&key, 6, -5.0)), Err(Rejection::Invalid));
+
+        assert_eq!(filter.check(&stake), Ok(()));
+        assert_eq!(filter.check(&stake), Err(Rejection::Replayed));
+        assert_eq!(filter.check(&signed_stake(&key, 4)), Err(Rejection::Replayed));
+        assert_eq!(filter.check(&signed_stake(&key, 6)), Ok(()));
+
+        filter.rejects.record(Rejection::Stale);
+        assert_eq!(filter.rejects, RejectMetrics { unsigned: 1, forged: 2, unproven: 0, invalid: 3, replayed: 2,
+            stale: 1 });
+        assert_eq!(filter.rejects.total(), 9);
+    }
+
+    #[test]
+    fn test_committed_stakes() {
+        let key = PrivateKey::generate(&mut rand::thread_rng());
+        let for_epoch = |nonce: u64, epoch: u64| {
+            let mut stake = Stake { epoch, ..signed_stake(&key, nonce) };
+            stake.signature = hex::encode(key.sign(stake_message(&stake)).as_bytes());
+            stake
+        };
+        let counted = for_epoch(5, 2);
+
+        // A replica that restarted has only the committees in the lease log to go by
+        let mut filter = StakeFilter::default();
+        filter.commit(2, &[Validator::from_stake(&counted)]);
+        assert_eq!(filter.check(&counted), Err(Rejection::Stale));
+        assert_eq!(filter.check(&for_epoch(5, 1)), Err(Rejection::Stale));
+        assert_eq!(filter.check(&for_epoch(5, 3)), Err(Rejection::Replayed));
+        assert_eq!(filter.check(&for_epoch(6, 3)), Ok(()));
+
+        filter.commit(1, &[Validator::from_stake(&for_epoch(4, 1))]);
+        assert_eq!(filter.decided, Some(2));
+        assert_eq!(filter.nonces[&counted.node_id], 6);
+    }
+
+    // node_pod's test_network pins the same message
+    #[test]
+    fn test_stake_message() {
+        let fixed = Stake { nonce: 99, epoch: 3, ..stake("ab", 12.5, Some(4)) };
+        assert_eq!(stake_message(&fixed), "node_pod stake:ab:12.5:3:99:4");
+    }
+}
@@ -0,0 +1,19 @@
+use node_pod::definitions::block_header::{BlockChain, BlockChainMethods};
+use node_pod::explorer::{run, USAGE};
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.len() < 2 {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    }
+
+    let json = std::fs::read_to_string(&args[0]).expect("Failed to read chain file");
+    let chain = BlockChain::deserialize(&json);
+
+    if let Err(e) = run(&chain, &args[1..], &mut std::io::stdout().lock()) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
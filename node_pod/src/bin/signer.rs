@@ -0,0 +1,29 @@
+use node_pod::definitions::signer_header::{FileSigner, FileSignerMethods, RemoteSigner, RemoteSignerMethods};
+
+const USAGE: &str = "Usage: signer <key_file> <socket>
+
+Serves signing requests for the key in key_file on a Unix socket. The key is sealed
+with the passphrase in NODE_KEY_PASSPHRASE when that variable is set.";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.len() != 2 {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    }
+
+    let passphrase = std::env::var("NODE_KEY_PASSPHRASE").ok().filter(|passphrase| !passphrase.is_empty());
+    let signer = match FileSigner::open(&args[0], passphrase.as_deref()) {
+        Ok(signer) => signer,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = RemoteSigner::serve(&signer, &args[1]) {
+        eprintln!("Signer stopped: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -3,31 +3,130 @@ use openssl::sha;
 use hex;
 use serde_json::{to_string_pretty, from_str};
 
-use crate::definitions::transaction_header::Transaction;
-use crate::definitions::merkle_header::{MerkleTree, MerkleMethods};
-use crate::definitions::block_header::{Block, BlockMethods, BlockChain, BlockChainMethods};
+use crate::definitions::transaction_header::{Transaction, TransactionMethods};
+use crate::definitions::merkle_header::{MerkleTree, MerkleScheme, MultiProof, MerkleMethods, ProofError};
+use std::collections::HashSet;
+use crate::definitions::block_header::{Block, BlockHeader, BlockMethods, BlockChain, BlockChainMethods, ChainError, ChainFault, ChainProof, ChainRecord, HeaderMethods, StoredHeader, PROTOCOL_VERSION};
+use crate::definitions::mmr_header::{MerkleMountainRange, MmrMethods};
+use crate::definitions::state_header::{Account, SparseMerkleTree, StateDiff, StateProof, StateMethods};
+use crate::definitions::index_header::{AccountHistory, ChainIndex, IndexMethods, TransactionReceipt, TxLocation};
+use crate::definitions::governance_header::{Governance, GovernanceTransactionMethods};
+use crate::definitions::consensus_header::{ValidatorSet, ValidatorSetMethods};
+
+fn update_field(hasher: &mut sha::Sha256, bytes: &[u8]) {
+    hasher.update(&(bytes.len() as u64).to_be_bytes());
+    hasher.update(bytes);
+}
+
+/// Checks that `transactions` are the body `header` commits to
+fn validate_body(header: &BlockHeader, transactions: &[Transaction]) -> bool {
+    // A repeated id could pad a list to the same root under the legacy scheme
+    let mut ids = HashSet::new();
+    if !transactions.iter().all(|tx| ids.insert(&tx.id)) {
+        println!["Block contains duplicate transactions"];
+        return false;
+    }
+
+    if header.version > 0 && header.tx_count != transactions.len() as u64 {
+        println!["Transaction count does not match the header"];
+        return false;
+    }
+
+    let new_root = MerkleTree::generate_root(transactions, header.merkle_scheme);
+    let new_root_str = hex::encode(&new_root);
+
+    let correct_root = new_root_str == header.merkle_root;
+
+    if !correct_root { println!["Merkle roots do not match"] }
+
+    correct_root
+}
+
+impl std::fmt::Display for ChainError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match &self.fault {
+            ChainFault::Hash => String::from("hash does not match the header"),
+            ChainFault::PrevHash => String::from("previous hash does not match the parent"),
+            ChainFault::Index => String::from("index does not follow the parent"),
+            ChainFault::Timestamp => String::from("timestamp is before the parent"),
+            ChainFault::Body => String::from("transactions do not match the header"),
+            ChainFault::Signature(id) => format!("transaction {} has an invalid signature", id),
+            ChainFault::Balance(id) => format!("transaction {} overdraws its sender", id),
+            ChainFault::Nonce(id) => format!("transaction {} does not follow its sender's nonce", id),
+            ChainFault::Governance(id) => format!("governance transaction {} does not apply", id),
+            ChainFault::MmrRoot => String::from("mmr root does not match the previous blocks"),
+            ChainFault::StateRoot => String::from("state root does not match the replayed state"),
+            ChainFault::ValidatorSet => String::from("validator set does not follow the one in effect"),
+        };
+        write!(f, "block {}: {}", self.height, reason)
+    }
+}
+
+impl HeaderMethods for BlockHeader {
+    fn hash(&self) -> Result<Vec<u8>, hex::FromHexError> {
+        let mut hasher = sha::Sha256::new();
+
+        // Version 0 headers keep their original hash so old chains still link up
+        if self.version == 0 {
+            hasher.update(&self.index.to_be_bytes());
+            hasher.update(&self.timestamp.to_be_bytes());
+            hasher.update(&hex::decode(&self.merkle_root)?);
+            hasher.update(&hex::decode(&self.prev_hash)?);
+            hasher.update(&hex::decode(&self.mmr_root)?);
+            hasher.update(&hex::decode(&self.state_root)?);
+
+            if self.merkle_scheme == MerkleScheme::Tagged {
+                hasher.update(&[1u8]);
+            }
+
+            return Ok(hasher.finish().to_vec());
+        }
+
+        hasher.update(&self.version.to_be_bytes());
+        hasher.update(&self.index.to_be_bytes());
+        hasher.update(&self.timestamp.to_be_bytes());
+        hasher.update(&self.view.to_be_bytes());
+        hasher.update(&self.tx_count.to_be_bytes());
+        hasher.update(&[self.merkle_scheme as u8]);
+        update_field(&mut hasher, &hex::decode(&self.prev_hash)?);
+        update_field(&mut hasher, &hex::decode(&self.merkle_root)?);
+        update_field(&mut hasher, &hex::decode(&self.mmr_root)?);
+        update_field(&mut hasher, &hex::decode(&self.state_root)?);
+        update_field(&mut hasher, &hex::decode(&self.validator_set_hash)?);
+        update_field(&mut hasher, self.proposer.as_bytes());
+
+        Ok(hasher.finish().to_vec())
+    }
+}
 
 impl BlockMethods for Block {
     fn new(data: Vec<Transaction>, previous_hash: String, idx: u64) -> Self {
-        let root = hex::encode(MerkleTree::generate_root(&data));
+        let root = hex::encode(MerkleTree::generate_root(&data, MerkleScheme::Tagged));
 
         let mut ts: u64 = std::time::SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
         ts -= ts % 5;
 
-        let mut block = Block {
+        let header = BlockHeader {
+            version: PROTOCOL_VERSION,
             index: idx,
-            hash: String::new(),
             timestamp: ts,
             merkle_root: root,
             prev_hash: previous_hash,
-            transactions: data
+            merkle_scheme: MerkleScheme::Tagged,
+            tx_count: data.len() as u64,
+            ..BlockHeader::default()
         };
 
-        let block_hash = hex::encode(block.hash());
+        Block { hash: hex::encode(header.hash().expect("Failed to decode previous Block Hash")), header, transactions: data }
+    }
 
-        block.hash = block_hash;
+    fn from_parts(header: BlockHeader, transactions: Vec<Transaction>) -> Option<Block> {
+        let block = Block { hash: hex::encode(header.hash().ok()?), header, transactions: vec![] };
 
-        block
+        match block.validate(transactions.clone()) {
+            true => Some(Block { transactions, ..block }),
+            false => None
+        }
     }
 
     fn new_genesis (data: Vec<Transaction>, previous_hash: String, idx: u64) -> Self {
@@ -36,30 +135,22 @@ impl BlockMethods for Block {
         let mut ts = std::time::SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
         ts -= ts % 60;
 
-        let mut block = Block {
+        let header = BlockHeader {
+            version: PROTOCOL_VERSION,
             index: idx,
-            hash: String::new(),
             timestamp: ts,
             merkle_root: root,
             prev_hash: previous_hash,
-            transactions: data
+            merkle_scheme: MerkleScheme::Tagged,
+            tx_count: data.len() as u64,
+            ..BlockHeader::default()
         };
 
-        let block_hash = hex::encode(block.hash());
-
-        block.hash = block_hash;
-
-        block
+        Block { hash: hex::encode(header.hash().expect("Failed to decode previous Block Hash")), header, transactions: data }
     }
 
-    fn hash(&self) -> Vec<u8> {
-        let mut hasher = sha::Sha256::new();
-        hasher.update(&self.index.to_be_bytes());
-        hasher.update(&self.timestamp.to_be_bytes());
-        hasher.update(&hex::decode(&self.merkle_root).expect("Failed to decode merkle_root hash"));
-        hasher.update(&hex::decode(&self.prev_hash).expect("Failed to decode previous Block Hash"));
-
-        return hasher.finish().to_vec();
+    fn hash(&self) -> Result<Vec<u8>, hex::FromHexError> {
+        self.header.hash()
     }
 
     fn serialize_block(&self) -> String {
@@ -73,55 +164,359 @@ impl BlockMethods for Block {
     }
 
     fn validate(&self, transactions: Vec<Transaction>) -> bool {
-        let new_root = MerkleTree::generate_root(&transactions);
-        let new_root_str = hex::encode(&new_root);
-
-        let correct_root = new_root_str == self.merkle_root;
-
-        if !correct_root { println!["Merkle roots do not match"] }
-
-        correct_root
+        validate_body(&self.header, &transactions)
     }
 
     fn is_equal(&self, block: Block) -> bool {
-        let mut predicate: bool = self.index == block.index;
+        let mut predicate: bool = self.header.index == block.header.index;
         if !predicate { panic!["index not the same"] }
-        predicate = predicate && (self.timestamp == block.timestamp);
+        predicate = predicate && (self.header.timestamp == block.header.timestamp);
         if !predicate { panic!["timestamp not the same"] };
-        predicate = predicate && (self.merkle_root == block.merkle_root);
+        predicate = predicate && (self.header.merkle_root == block.header.merkle_root);
         if !predicate { panic![ "merkle_root not the same" ]}
-        predicate = predicate && (self.prev_hash == block.prev_hash);
+        predicate = predicate && (self.header.prev_hash == block.header.prev_hash);
         if !predicate { panic!["prev_hash not the same"] }
         predicate = predicate && (self.transactions == block.transactions);
         if !predicate { panic!["transactions not the same"] };
+        predicate = predicate && (self.header == block.header);
+        if !predicate { panic!["header not the same"] };
         predicate
     }
+
+    fn prove_transactions(&self, targets: &[Transaction]) -> Result<MultiProof, ProofError> {
+        MerkleTree::new(&self.transactions, self.header.merkle_scheme).generate_multi_proof(targets)
+    }
+
+    fn verify_transactions(&self, proof: &MultiProof, transactions: &[Transaction]) -> Result<bool, ProofError> {
+        let root = hex::decode(&self.header.merkle_root).map_err(|_| ProofError::InvalidRoot)?;
+        Ok(MerkleTree::validate_multi_proof(proof, transactions, &root, self.header.merkle_scheme))
+    }
+}
+
+/// Checks the block made of `stored` and `transactions` on top of `parent`, given the mountain range
+/// over the blocks before it, the state after its parent, which it is applied to, and the committee
+/// in effect, which it may replace. A block failing while its transactions are applied is left
+/// started on `state`, one whose state root does not match is undone.
+fn verify_block<'a>(stored: &'a StoredHeader, transactions: &[Transaction], parent: Option<&StoredHeader>,
+    mmr: &MerkleMountainRange, state: &mut SparseMerkleTree, validator_set: &mut Option<&'a ValidatorSet>,
+    replay_balances: bool) -> Result<StateDiff, ChainFault> {
+    let header = &stored.header;
+
+    if stored.hash != hex::encode(header.hash().map_err(|_| ChainFault::Hash)?) {
+        return Err(ChainFault::Hash);
+    }
+
+    match parent {
+        None => {
+            if header.index != 0 {
+                return Err(ChainFault::Index);
+            }
+        }
+        Some(parent) => {
+            if header.prev_hash != parent.hash {
+                return Err(ChainFault::PrevHash);
+            }
+            if header.index != parent.header.index + 1 {
+                return Err(ChainFault::Index);
+            }
+            if header.timestamp < parent.header.timestamp {
+                return Err(ChainFault::Timestamp);
+            }
+            if !header.mmr_root.is_empty() && header.mmr_root != hex::encode(mmr.root()) {
+                return Err(ChainFault::MmrRoot);
+            }
+        }
+    }
+
+    if !validate_body(header, transactions) {
+        return Err(ChainFault::Body);
+    }
+
+    match (&header.validator_set, *validator_set) {
+        (Some(set), previous) => {
+            if set.hash != set.compute_hash() || header.validator_set_hash != set.hash
+                || previous.is_some_and(|previous| set.epoch <= previous.epoch) {
+                return Err(ChainFault::ValidatorSet);
+            }
+            *validator_set = Some(set);
+        }
+        (None, Some(set)) if header.validator_set_hash != set.hash => {
+            return Err(ChainFault::ValidatorSet);
+        }
+        _ => {}
+    }
+
+    state.start_block(header.index);
+    for transaction in transactions {
+        if !transaction.verify_sender() {
+            return Err(ChainFault::Signature(transaction.id.clone()));
+        }
+        // Ballots declare their own stakes, so they only count when they match the committee in effect
+        let ballot_valid = validator_set.is_some_and(|set| transaction.check_governance(&set.validators));
+        if transaction.governance.is_some() && !ballot_valid {
+            return Err(ChainFault::Governance(transaction.id.clone()));
+        }
+        let expected = state.accounts.get(&transaction.from).map_or(1, |sender| sender.nonce + 1);
+        if replay_balances && transaction.nonce.is_some_and(|nonce| nonce != expected) {
+            return Err(ChainFault::Nonce(transaction.id.clone()));
+        }
+        let applied = state.apply_transaction(transaction);
+        if !applied && transaction.governance.is_some() {
+            return Err(ChainFault::Governance(transaction.id.clone()));
+        }
+        if !applied && replay_balances {
+            return Err(ChainFault::Balance(transaction.id.clone()));
+        }
+    }
+    let diff = state.finish_block();
+
+    if !header.state_root.is_empty() && header.state_root != hex::encode(state.root()) {
+        state.revert(&diff);
+        return Err(ChainFault::StateRoot);
+    }
+
+    Ok(diff)
+}
+
+impl From<ChainRecord> for BlockChain {
+    fn from(record: ChainRecord) -> Self {
+        let mut chain = BlockChain { headers: vec![], bodies: vec![], genesis_state: record.genesis_state.clone(),
+            mmr: MerkleMountainRange::new(), state: record.genesis_state, diffs: vec![], index: ChainIndex::new() };
+
+        for block in record.chain {
+            // The genesis block's state is the genesis state itself
+            if chain.headers.is_empty() {
+                chain.mmr.append(&block.hash().unwrap_or_default());
+                chain.index.insert(0, &block);
+                chain.diffs.push(StateDiff::default());
+                chain.headers.push(StoredHeader { hash: block.hash, header: block.header });
+                chain.bodies.push(block.transactions);
+            } else {
+                chain.add_block(block);
+            }
+        }
+
+        chain
+    }
+}
+
+impl From<BlockChain> for ChainRecord {
+    fn from(chain: BlockChain) -> Self {
+        let blocks = chain.headers.into_iter().zip(chain.bodies)
+            .map(|(stored, transactions)| Block { hash: stored.hash, header: stored.header, transactions })
+            .collect();
+
+        ChainRecord { chain: blocks, genesis_state: chain.genesis_state }
+    }
 }
 
 impl BlockChainMethods for BlockChain {
     fn new() -> Self {
-        let genesis_block = Block::new_genesis(vec![], hex::encode(vec![]), 0);
+        BlockChain::from_genesis_state(SparseMerkleTree::new())
+    }
 
-        return BlockChain { chain: vec![genesis_block] };
+    fn from_genesis_state(state: SparseMerkleTree) -> Self {
+        let mut genesis_block = Block::new_genesis(vec![], hex::encode(vec![]), 0);
+        genesis_block.header.state_root = hex::encode(state.root());
+        genesis_block.hash = hex::encode(genesis_block.hash().expect("Failed to hash genesis block"));
+
+        BlockChain::from(ChainRecord { chain: vec![genesis_block], genesis_state: state })
     }
 
-    fn add_block(&mut self, block: Block) {        
-        self.chain.push(block);
+    fn add_block(&mut self, block: Block) {
+        let diff = self.state.apply_block(block.header.index, &block.transactions);
+
+        // Loaded chains may hold headers that do not hash, which verify_chain reports at their height
+        self.mmr.append(&block.hash().unwrap_or_default());
+        self.index.insert(self.headers.len(), &block);
+        self.diffs.push(diff);
+        self.headers.push(StoredHeader { hash: block.hash, header: block.header });
+        self.bodies.push(block.transactions);
+    }
+
+    fn new_block(&mut self, data: Vec<Transaction>, proposer: String, validator_set_hash: String, view: u64) -> Block {
+        let tip = self.headers.last().expect("Chain has no genesis block");
+
+        let diff = self.state.apply_block(tip.header.index + 1, &data);
+        let state_root = hex::encode(self.state.root());
+        self.state.revert(&diff);
+
+        let mut block = Block::new(data, tip.hash.clone(), tip.header.index + 1);
+        block.header.mmr_root = hex::encode(self.mmr.root());
+        block.header.state_root = state_root;
+        block.header.proposer = proposer;
+        block.header.validator_set_hash = validator_set_hash;
+        block.header.view = view;
+        block.hash = hex::encode(block.hash().expect("Failed to hash new block"));
+
+        block
+    }
+
+    fn new_epoch_block(&mut self, data: Vec<Transaction>, proposer: String, validator_set: &ValidatorSet, view: u64) -> Block {
+        let mut block = self.new_block(data, proposer, validator_set.hash.clone(), view);
+        block.header.validator_set = Some(validator_set.clone());
+        block
+    }
+
+    fn header(&self, height: usize) -> Option<&BlockHeader> {
+        self.headers.get(height).map(|stored| &stored.header)
+    }
+
+    fn headers(&self, from: usize, to: usize) -> Vec<BlockHeader> {
+        self.headers.iter().skip(from).take(to.saturating_sub(from)).map(|stored| stored.header.clone()).collect()
+    }
+
+    fn body(&self, height: usize) -> Option<&Vec<Transaction>> {
+        self.bodies.get(height)
+    }
+
+    fn block_by_height(&self, height: usize) -> Option<Block> {
+        let stored = self.headers.get(height)?;
+        let transactions = self.bodies.get(height)?.clone();
+
+        Some(Block { hash: stored.hash.clone(), header: stored.header.clone(), transactions })
+    }
+
+    fn block_by_hash(&self, block_hash: &str) -> Option<Block> {
+        self.index.height_of(block_hash).and_then(|height| self.block_by_height(height))
+    }
+
+    fn transaction(&self, tx_id: &str) -> Option<TransactionReceipt> {
+        let (height, position) = self.index.locate(tx_id)?;
+        let stored = self.headers.get(height)?;
+        let transactions = self.bodies.get(height)?;
+        let transaction = transactions.get(position)?.clone();
+        let proof = MerkleTree::new(transactions, stored.header.merkle_scheme).generate_proof(&transaction);
+
+        Some(TransactionReceipt { height, block_hash: stored.hash.clone(), transaction, proof })
+    }
+
+    fn account_history(&self, account_id: &str) -> AccountHistory {
+        let lookup = |locations: &[TxLocation]| -> Vec<(usize, Transaction)> {
+            locations.iter()
+                .filter_map(|(height, position)| Some((*height, self.bodies.get(*height)?.get(*position)?.clone())))
+                .collect()
+        };
+
+        AccountHistory {
+            account_id: account_id.to_string(),
+            sent: lookup(self.index.sent_by(account_id)),
+            received: lookup(self.index.received_by(account_id)),
+        }
+    }
+
+    // Governance after the tip, whose parameters the next block is built under
+    fn governance(&self) -> &Governance {
+        &self.state.governance
+    }
+
+    fn account(&self, account_id: &str, height: usize) -> Option<Account> {
+        if height >= self.headers.len() {
+            return None;
+        }
+
+        // The first block after `height` to touch the account holds its value from before
+        match self.diffs.iter().skip(height + 1).find_map(|diff| diff.accounts.get(account_id)) {
+            Some(account) => account.clone(),
+            None => self.state.accounts.get(account_id).cloned()
+        }
+    }
+
+    fn validator_set(&self) -> Option<&ValidatorSet> {
+        self.headers.iter().rev().find_map(|stored| stored.header.validator_set.as_ref())
+    }
+
+    fn prove_inclusion(&self, tx_id: &str, height: usize, tip: usize) -> Option<ChainProof> {
+        // The tip commits to the blocks before it, so it can only vouch for its ancestors
+        if height >= tip || tip >= self.headers.len() {
+            return None;
+        }
+
+        let header = &self.headers[height].header;
+        let transactions = &self.bodies[height];
+        let transaction = transactions.iter().find(|tx| tx.id == tx_id)?;
+        let tx_proof = MerkleTree::new(transactions, header.merkle_scheme).generate_proof(transaction);
+        let mmr_proof = self.mmr.generate_proof(height, tip)?;
+
+        Some(ChainProof { header: header.clone(), tx_proof, mmr_proof })
+    }
+
+    fn verify_inclusion(proof: &ChainProof, transaction: &Transaction, tip: &BlockHeader) -> bool {
+        if proof.mmr_proof.leaf_index as u64 != proof.header.index || proof.mmr_proof.leaf_count as u64 != tip.index {
+            return false;
+        }
+
+        let (merkle_root, mmr_root) = match (hex::decode(&proof.header.merkle_root), hex::decode(&tip.mmr_root)) {
+            (Ok(merkle_root), Ok(mmr_root)) => (merkle_root, mmr_root),
+            _ => { return false; }
+        };
+
+        MerkleTree::validate_proof(&proof.tx_proof, transaction.clone(), &merkle_root, proof.header.merkle_scheme)
+            && proof.header.hash().is_ok_and(|hash| MerkleMountainRange::validate_proof(&proof.mmr_proof, &hash, &mmr_root))
+    }
+
+    fn prove_account(&self, account_id: &str, height: usize) -> Option<StateProof> {
+        if height >= self.headers.len() {
+            return None;
+        }
+        if height + 1 == self.headers.len() {
+            return Some(self.state.generate_proof(account_id));
+        }
+
+        let mut state = self.state.clone();
+        for diff in self.diffs[height + 1..].iter().rev() {
+            state.revert(diff);
+        }
+        Some(state.generate_proof(account_id))
+    }
+
+    fn verify_account(proof: &StateProof, header: &BlockHeader) -> bool {
+        match hex::decode(&header.state_root) {
+            Ok(state_root) => SparseMerkleTree::validate_proof(proof, &state_root),
+            Err(_) => false
+        }
     }
 
     fn validate_transaction(&self, data: Vec<Transaction>, block_index: usize) -> bool {
-        self.chain[block_index].validate(data)
+        validate_body(&self.headers[block_index].header, &data)
     }
 
-    fn verify_chain(&self) -> bool {
-        for i in 1..self.chain.len() {
-            let current_block = &self.chain[i];
-            let prev_block = &self.chain[i - 1];
-            if hex::decode(&current_block.prev_hash).expect("Failed to decode previous hash") != prev_block.hash(){
-                return false;
+    fn verify_chain(&self) -> Result<(), ChainError> {
+        // Chains saved without a genesis state can not be replayed, only their roots checked
+        let replay_balances = !self.genesis_state.accounts.is_empty();
+
+        let mut mmr = MerkleMountainRange::new();
+        let mut state = self.genesis_state.clone();
+        // Blocks before the first committed set were agreed without one on chain and are not checked
+        let mut validator_set: Option<&ValidatorSet> = None;
+
+        for (height, (stored, transactions)) in self.headers.iter().zip(&self.bodies).enumerate() {
+            let parent = height.checked_sub(1).map(|parent| &self.headers[parent]);
+            verify_block(stored, transactions, parent, &mmr, &mut state, &mut validator_set, replay_balances)
+                .map_err(|fault| ChainError { height, fault })?;
+            // The stored hash was just checked against the header's own
+            mmr.append(&hex::decode(&stored.hash).map_err(|_| ChainError { height, fault: ChainFault::Hash })?);
+        }
+
+        Ok(())
+    }
+
+    fn verify_next(&mut self, block: &Block) -> Result<(), ChainError> {
+        let replay_balances = !self.genesis_state.accounts.is_empty();
+        let stored = StoredHeader { hash: block.hash.clone(), header: block.header.clone() };
+        let mut validator_set = self.headers.iter().rev().find_map(|stored| stored.header.validator_set.as_ref());
+
+        match verify_block(&stored, &block.transactions, self.headers.last(), &self.mmr, &mut self.state,
+            &mut validator_set, replay_balances) {
+            Ok(diff) => {
+                self.state.revert(&diff);
+                Ok(())
+            }
+            Err(fault) => {
+                self.state.rollback();
+                Err(ChainError { height: self.headers.len(), fault })
             }
         }
-        true
     }
 
     fn serialize(&self) -> String {
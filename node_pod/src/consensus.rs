@@ -1,16 +1,28 @@
-use bls_signatures::{PublicKey, Serialize};
+use bls_signatures::{PublicKey, Serialize, Signature};
 use rdkafka::producer::{BaseProducer, BaseRecord};
 use std::collections::HashMap;
 use rand::Rng;
 use serde_json::{from_str, to_string};
-use log::info;
-use crate::definitions::{consensus_header::StakeMethods, network_header::{MessageType, MessageTypeMethods, Network, NodeMessage, NodeMessageMethods}, node_header::Node, transaction_header::Transaction};
+use log::{error, info};
+use openssl::sha;
+use crate::definitions::{consensus_header::StakeMethods, network_header::{ClientReply, ClientReplyMethods, MessageType, MessageTypeMethods, Network, NodeMessage, NodeMessageMethods}, node_header::{Node, NodeMethods}, transaction_header::Transaction};
 use crate::definitions::block_header::{Block, BlockMethods, BlockChainMethods};
-use crate::definitions::consensus_header::{PoS, Pbft, Stake, Validator, ValidatorMethods};
+use crate::definitions::signer_header::{Phase, SignerError};
+use crate::signer::possession_message;
+use crate::definitions::consensus_header::{PoS, Pbft, Stake, STAKE_DOMAIN, Validator, ValidatorMethods, ValidatorSet, ValidatorSetMethods};
 
 impl StakeMethods for Stake {
-    fn new(node: &Node, stake: f64) -> Self {
-        Stake { node_id: node.id.clone() , stake }
+    fn new(node: &Node, stake: f64, epoch: u64) -> Result<Self, SignerError> {
+        let public_key = node.signer.public_key();
+        let proof = node.sign_message(&possession_message(&public_key), Phase::Possession)?;
+        let nonce = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+            .expect("Clock is before the epoch").as_millis() as u64;
+
+        let committee_size = node.block_chain.read().expect("Chain lock poisoned").governance().parameters.committee_size;
+        let mut stake = Stake { node_id: node.id.clone() , stake, committee_size, epoch,
+            public_key: hex::encode(public_key.as_bytes()), proof_of_possession: proof, nonce, signature: String::new() };
+        stake.signature = node.sign_message(&stake_message(&stake), Phase::Stake)?;
+        Ok(stake)
     }
 
     fn serialize(&self) -> String {
@@ -24,6 +36,20 @@ impl StakeMethods for Stake {
 }
 
 impl ValidatorMethods for Validator {
+    // Node ids are the hex of the key, so a validator can not register a key under another id
+    fn verify_possession(&self) -> bool {
+        let public_key = match hex::decode(&self.public_key).ok().and_then(|bytes| PublicKey::from_bytes(&bytes).ok()) {
+            Some(public_key) => public_key,
+            None => { return false; }
+        };
+        let proof = match hex::decode(&self.proof_of_possession).ok().and_then(|bytes| Signature::from_bytes(&bytes).ok()) {
+            Some(proof) => proof,
+            None => { return false; }
+        };
+
+        self.node_id == self.public_key && public_key.verify(proof, possession_message(&public_key))
+    }
+
     fn serialize(&self) -> String {
         to_string(&self).expect("Failed to serialize")
     }
@@ -33,20 +59,85 @@ impl ValidatorMethods for Validator {
     }
 }
 
+impl ValidatorSetMethods for ValidatorSet {
+    fn new(epoch: u64, validators: Vec<Validator>) -> Self {
+        let mut set = ValidatorSet { epoch, validators, hash: String::new() };
+        set.hash = set.compute_hash();
+        set
+    }
+
+    // Independent of the order validators were selected in
+    fn compute_hash(&self) -> String {
+        let mut sorted: Vec<&Validator> = self.validators.iter().collect();
+        sorted.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+
+        let mut hasher = sha::Sha256::new();
+        hasher.update(&self.epoch.to_be_bytes());
+        for validator in sorted {
+            hasher.update(&(validator.node_id.len() as u64).to_be_bytes());
+            hasher.update(validator.node_id.as_bytes());
+            hasher.update(&(validator.public_key.len() as u64).to_be_bytes());
+            hasher.update(validator.public_key.as_bytes());
+            hasher.update(&validator.stake.to_be_bytes());
+        }
+
+        hex::encode(hasher.finish())
+    }
+
+    fn contains(&self, node_id: &str) -> bool {
+        self.validators.iter().any(|validator| validator.node_id == node_id)
+    }
+
+    fn serialize(&self) -> String {
+        to_string(&self).expect("Failed to serialize")
+    }
+
+    // Sets whose hash does not match their members are refused
+    fn deserialize(json_str: &str) -> Option<Self> {
+        from_str::<ValidatorSet>(json_str).ok().filter(|set| set.hash == set.compute_hash())
+    }
+}
+
+/// What a stake's signature covers. It is plain text rather than JSON so master_pod can rebuild
+/// it without sharing the struct's field order.
+pub fn stake_message(stake: &Stake) -> String {
+    let committee_size = stake.committee_size.map(|size| size.to_string()).unwrap_or_else(|| String::from("none"));
+    format!("{}{}:{}:{}:{}:{}", STAKE_DOMAIN, stake.node_id, stake.stake, stake.epoch, stake.nonce, committee_size)
+}
+
+/// Epoch the block at `height` belongs to, the genesis block sharing the first epoch
+pub fn epoch_of(height: u64, epoch_length: u64) -> u64 {
+    height.saturating_sub(1) / epoch_length.max(1)
+}
+
+/// Whether a consensus message was signed by a member of `set` over a block agreed under it.
+/// Messages from other epochs are dropped rather than counted. The block is the signed one in
+/// `msg_type`, whose set hash also binds the epoch, not the unsigned copy beside it.
+pub fn signed_in_epoch(set: &ValidatorSet, msg: &NodeMessage) -> bool {
+    set.contains(&msg.sender_id)
+        && msg.msg_type.block().is_some_and(|block| block.header.validator_set_hash == set.hash)
+}
+
 impl PoS for Node {
-    async fn propose_stake(&mut self, producer: &BaseProducer){
+    async fn propose_stake(&mut self, producer: &BaseProducer, epoch: u64){
         let dist = rand::distributions::Uniform::new(10.0, 500.0);
         let mut rng = rand::thread_rng();
         self.stake = rng.sample(dist);
 
-        let stake = Stake::new(self, self.stake);
+        let stake = match Stake::new(self, self.stake, epoch) {
+            Ok(stake) => stake,
+            Err(err) => {
+                error!("Not staking for epoch {}: {}", epoch, err);
+                return;
+            }
+        };
         let record_json = StakeMethods::serialize(&stake);
         let topic = String::from("Stakes");
         let record = BaseRecord::to(&topic)
             .payload(&record_json)
             .key("Node Stake");
 
-        info!("Sent stake of {}", self.stake);
+        info!("Sent stake of {} for epoch {}", self.stake, epoch);
         producer.send(record).expect("Failed to send the stake");
     }
 }
@@ -58,9 +149,17 @@ impl Pbft for Node {
 
         self.staging = pool.clone();
 
-        let block = Block::new(pool,
-            self.block_chain.chain[self.block_chain.chain.len() - 1].hash.clone(), 
-            self.block_chain.chain[self.block_chain.chain.len() - 1].index + 1);
+        // Every node builds the same block, so the proposer is the primary rather than ourselves
+        let proposer = self.primary.first().map(|leader| leader.node_id.clone()).unwrap_or_default();
+        // The first block agreed by a set commits it, later blocks of the epoch only name it
+        let block = {
+            let mut chain = self.block_chain.write().expect("Chain lock poisoned");
+            let committed = chain.validator_set().is_some_and(|set| set.hash == self.validator_set.hash);
+            match committed {
+                true => chain.new_block(pool, proposer, self.validator_set.hash.clone(), self.msg_idx[0] as u64),
+                false => chain.new_epoch_block(pool, proposer, &self.validator_set, self.msg_idx[0] as u64)
+            }
+        };
 
         let primary = self.primary.clone();
         let id = self.id.clone();
@@ -76,9 +175,14 @@ impl Pbft for Node {
 
             self.block_staging.push(block);
 
-            info!("Broadcasting Preprepare message");
+            match message {
+                Ok(message) => {
+                    info!("Broadcasting Preprepare message");
 
-            self.broadcast_kafka("Preprepare", message, producer).await;
+                    self.broadcast_kafka("Preprepare", message, producer).await;
+                }
+                Err(err) => error!("Not broadcasting Preprepare: {}", err)
+            }
         }
         else {
             info!("Pushed block to staging");
@@ -96,10 +200,15 @@ impl Pbft for Node {
         let mut messages: Vec<NodeMessage> = vec![];
         
         for msg in primary_msg {
-            messages.push(NodeMessage::deserialize_message(msg));
+            let msg = NodeMessage::deserialize_message(msg);
+            match (signed_in_epoch(&self.validator_set, &msg), pkey_store.contains_key(&msg.sender_id)) {
+                (true, true) => messages.push(msg),
+                (false, _) => info!("Dropped Preprepare from {} signed outside epoch {}", msg.sender_id, self.validator_set.epoch),
+                (true, false) => info!("Dropped Preprepare from {} whose key was not proven", msg.sender_id)
+            }
         }
 
-        let val = self.validators.clone();
+        let val = self.validator_set.validators.clone();
         let id = self.id.clone();
         let mut is_val = false;
         for validator in val {
@@ -124,18 +233,23 @@ impl Pbft for Node {
             if is_leader && is_preprepare && verify_leader {
                 info!("Verified Leader");
 
-                let new_block: Block = Block::deserialize_block(&msg.msg_type.unwrap());
+                let Some(new_block) = msg.msg_type.block() else {
+                    info!("Dropped Preprepare from {} whose block does not parse", msg.sender_id);
+                    continue;
+                };
                 let verify_block: bool = self.block_staging.last().unwrap().is_equal(new_block);
 
                 if verify_block {
                     info!("Verified Block");
 
-                    let kafka_message: NodeMessage = NodeMessage::new(self,
-                        &self.block_staging[msg.seq_num].clone(), String::from("Prepare"), self.msg_idx[1]);
+                    match NodeMessage::new(self, &self.block_staging[msg.seq_num].clone(), String::from("Prepare"), self.msg_idx[1]) {
+                        Ok(kafka_message) => {
+                            self.broadcast_kafka("Prepare", kafka_message, producer).await;
 
-                    self.broadcast_kafka("Prepare", kafka_message, producer).await;
-
-                    info!("Broadcasted Prepare message");
+                            info!("Broadcasted Prepare message");
+                        }
+                        Err(err) => error!("Not broadcasting Prepare: {}", err)
+                    }
 
                     self.msg_idx[1] += 1;
                 }
@@ -171,11 +285,18 @@ impl Pbft for Node {
         let mut b: f64 = 0.0;
 
         for msg in prepare_msg {
-            messages.push(NodeMessage::deserialize_message(msg));
-            a += 1.0;
+            let msg = NodeMessage::deserialize_message(msg);
+            match (signed_in_epoch(&self.validator_set, &msg), pkey_store.contains_key(&msg.sender_id)) {
+                (true, true) => {
+                    messages.push(msg);
+                    a += 1.0;
+                }
+                (false, _) => info!("Dropped Prepare from {} signed outside epoch {}", msg.sender_id, self.validator_set.epoch),
+                (true, false) => info!("Dropped Prepare from {} whose key was not proven", msg.sender_id)
+            }
         }
 
-        for _ in 0..self.validators.len() {
+        for _ in 0..self.validator_set.validators.len() {
             b += 1.0;
         }
 
@@ -189,7 +310,7 @@ impl Pbft for Node {
         }
 
         for msg in messages {
-            let is_validator = self.validators.iter().any(|validator| validator.node_id == msg.sender_id);
+            let is_validator = self.validator_set.contains(&msg.sender_id);
             let is_prepare = matches!(msg.msg_type, MessageType::Prepare(_));
 
             let pkey = pkey_store.get(&msg.sender_id).expect("Sender is not in the key store");
@@ -197,8 +318,11 @@ impl Pbft for Node {
                 &hex::decode(msg.signature).unwrap()).unwrap()
                 , msg.msg_type.unwrap());
 
-            let new_block: Block = Block::deserialize_block(&msg.msg_type.unwrap());
-            
+            let Some(new_block) = msg.msg_type.block() else {
+                info!("Dropped Prepare from {} whose block does not parse", msg.sender_id);
+                continue;
+            };
+
             if is_validator && is_prepare && verify_sender {
                 info!("Verified Validator");
 
@@ -251,9 +375,21 @@ impl Pbft for Node {
 
         let new_block: Block = blocks[max_idx].to_owned();
 
+        // Only the new block is checked against the tip, the chain below it was checked as it grew.
+        // A block that fails is neither voted for nor replied to.
+        let verified = self.block_chain.write().expect("Chain lock poisoned").verify_next(&new_block);
+        if let Err(err) = verified {
+            info!("Rejected the agreed block, it fails verification at {}", err);
+            return false;
+        }
 
-        let kafka_message: NodeMessage = NodeMessage::new(self,
-            &new_block.clone(), String::from("Commit"), self.msg_idx[2]);
+        let kafka_message: NodeMessage = match NodeMessage::new(self, &new_block.clone(), String::from("Commit"), self.msg_idx[2]) {
+            Ok(kafka_message) => kafka_message,
+            Err(err) => {
+                error!("Not broadcasting Commit: {}", err);
+                return false;
+            }
+        };
 
         self.broadcast_kafka("Commit", kafka_message, producer).await;
 
@@ -261,9 +397,19 @@ impl Pbft for Node {
 
         self.msg_idx[2] += 1;
 
-        self.block_chain.add_block(new_block);
-        
-        self.block_chain.verify_chain()
+        let reply = ClientReply::new(self, &new_block);
+
+        self.block_chain.write().expect("Chain lock poisoned").add_block(new_block);
+
+        match reply {
+            Ok(reply) => {
+                self.broadcast_reply(reply, producer).await;
+
+                info!("Broadcasted Reply message");
+            }
+            Err(err) => error!("Not broadcasting Reply: {}", err)
+        }
 
+        true
     }
 }
\ No newline at end of file
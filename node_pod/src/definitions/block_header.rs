@@ -1,28 +1,149 @@
 use serde::{Serialize, Deserialize};
+use std::sync::{Arc, RwLock};
 use crate::definitions::transaction_header::Transaction;
-
-#[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq)]
-pub struct Block {
+use crate::definitions::merkle_header::{MerkleScheme, MultiProof, Proof, ProofError};
+use crate::definitions::mmr_header::{MerkleMountainRange, MmrProof};
+use crate::definitions::state_header::{Account, SparseMerkleTree, StateDiff, StateProof};
+use crate::definitions::index_header::{AccountHistory, ChainIndex, TransactionReceipt};
+use crate::definitions::governance_header::Governance;
+use crate::definitions::consensus_header::ValidatorSet;
+
+/// Header layout produced by this build. Version 0 is the layout from before the header was
+/// split from the body, which only hashes the index, timestamp, previous hash and roots.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Headers and blocks refuse unknown fields, so no other signed message parses as one
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct BlockHeader {
+    #[serde(default)]
+    pub version: u32,
     pub index: u64,
-    pub hash: String,
     pub timestamp: u64,
     pub merkle_root: String,
     pub prev_hash: String,
+    /// Blocks from chains written before domain separation have no scheme and load as Legacy
+    #[serde(default)]
+    pub merkle_scheme: MerkleScheme,
+    /// Root of the mountain range over all previous block hashes, empty on older chains
+    #[serde(default)]
+    pub mmr_root: String,
+    /// Root of the account state after this block's transactions, empty on older chains
+    #[serde(default)]
+    pub state_root: String,
+    #[serde(default)]
+    pub proposer: String,
+    #[serde(default)]
+    pub validator_set_hash: String,
+    #[serde(default)]
+    pub view: u64,
+    #[serde(default)]
+    pub tx_count: u64,
+    /// Set only on the first block of an epoch. It is bound to the block hash through `validator_set_hash`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validator_set: Option<ValidatorSet>
+}
+
+/// The header is flattened so blocks serialize the same way they did before the split
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Block {
+    pub hash: String,
+    #[serde(flatten)]
+    pub header: BlockHeader,
     pub transactions: Vec<Transaction>
 }
 
+/// A header with the hash it is stored under, kept apart from the block's transactions
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq)]
+pub struct StoredHeader {
+    pub hash: String,
+    #[serde(flatten)]
+    pub header: BlockHeader
+}
+
+/// Serialized as a `ChainRecord`, so saved chains keep their list of whole blocks
 #[derive(Serialize, Deserialize, Clone, std::fmt::Debug)]
+#[serde(from = "ChainRecord", into = "ChainRecord")]
 pub struct BlockChain {
+    pub headers: Vec<StoredHeader>,
+    /// Transactions of the block at each height, only read when a body is asked for
+    pub bodies: Vec<Vec<Transaction>>,
+    /// Account state the genesis block starts from
+    pub genesis_state: SparseMerkleTree,
+    pub mmr: MerkleMountainRange,
+    /// State after the tip
+    pub state: SparseMerkleTree,
+    /// What each block changed by height, earlier states are recovered by undoing them from the tip
+    pub diffs: Vec<StateDiff>,
+    pub index: ChainIndex,
+}
+
+/// On-disk layout of a chain. The mountain range, state, diffs and index are rebuilt from it on load.
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug)]
+pub struct ChainRecord {
     pub chain: Vec<Block>,
+    #[serde(default)]
+    pub genesis_state: SparseMerkleTree,
+}
+
+/// The node's chain, which the RPC server reads in place rather than keeping a copy of
+pub type SharedChain = Arc<RwLock<BlockChain>>;
+
+/// Proof that a transaction is in the block described by `header` and that this block is
+/// an ancestor of the tip whose `mmr_root` the proof is checked against
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug)]
+pub struct ChainProof {
+    pub header: BlockHeader,
+    pub tx_proof: Proof,
+    pub mmr_proof: MmrProof,
+}
+
+/// Why `verify_chain` rejected a block
+#[derive(Clone, std::fmt::Debug, PartialEq)]
+pub enum ChainFault {
+    Hash,
+    PrevHash,
+    Index,
+    Timestamp,
+    /// The body does not match the header's merkle root or transaction count, or repeats a transaction
+    Body,
+    /// Id of the transaction whose signature does not verify
+    Signature(String),
+    /// Id of the transaction its sender can not pay for
+    Balance(String),
+    /// Id of the transaction whose nonce does not follow its sender's
+    Nonce(String),
+    /// Id of the governance transaction whose action does not apply or whose ballot does not match the committee in effect
+    Governance(String),
+    MmrRoot,
+    StateRoot,
+    /// The block commits a set that does not match its hash or goes back in epochs, or names a set other than the one in effect
+    ValidatorSet,
+}
+
+/// First block of a chain that fails verification
+#[derive(Clone, std::fmt::Debug, PartialEq)]
+pub struct ChainError {
+    pub height: usize,
+    pub fault: ChainFault,
+}
+
+pub trait HeaderMethods {
+
+    fn hash(&self) -> Result<Vec<u8>, hex::FromHexError>;
+
 }
 
 pub trait BlockMethods {
 
     fn new(data: Vec<Transaction>, previous_hash: String, idx: u64) -> Self;
 
+    fn from_parts(header: BlockHeader, transactions: Vec<Transaction>) -> Option<Block>;
+
     fn new_genesis (data: Vec<Transaction>, previous_hash: String, idx: u64) -> Self;
 
-    fn hash(&self) -> Vec<u8>;
+    fn hash(&self) -> Result<Vec<u8>, hex::FromHexError>;
 
     fn serialize_block(&self) -> String;
 
@@ -32,17 +153,62 @@ pub trait BlockMethods {
 
     fn is_equal(&self, block: Block) -> bool;
 
+    fn prove_transactions(&self, targets: &[Transaction]) -> Result<MultiProof, ProofError>;
+
+    fn verify_transactions(&self, proof: &MultiProof, transactions: &[Transaction]) -> Result<bool, ProofError>;
+
 }
 
 pub trait BlockChainMethods {
 
     fn new() -> Self;
 
+    fn from_genesis_state(state: SparseMerkleTree) -> Self;
+
     fn add_block(&mut self, block: Block);
 
+    /// Builds the next block, applying it to the state for its root and undoing it again
+    fn new_block(&mut self, data: Vec<Transaction>, proposer: String, validator_set_hash: String, view: u64) -> Block;
+
+    fn new_epoch_block(&mut self, data: Vec<Transaction>, proposer: String, validator_set: &ValidatorSet, view: u64) -> Block;
+
+    fn header(&self, height: usize) -> Option<&BlockHeader>;
+
+    fn headers(&self, from: usize, to: usize) -> Vec<BlockHeader>;
+
+    fn body(&self, height: usize) -> Option<&Vec<Transaction>>;
+
+    /// Header and body joined back into a block
+    fn block_by_height(&self, height: usize) -> Option<Block>;
+
+    fn block_by_hash(&self, block_hash: &str) -> Option<Block>;
+
+    fn transaction(&self, tx_id: &str) -> Option<TransactionReceipt>;
+
+    fn account_history(&self, account_id: &str) -> AccountHistory;
+
+    fn governance(&self) -> &Governance;
+
+    /// The account after the block at `height`, None when it did not exist then or the height is past the tip
+    fn account(&self, account_id: &str, height: usize) -> Option<Account>;
+
+    fn validator_set(&self) -> Option<&ValidatorSet>;
+
+    fn prove_inclusion(&self, tx_id: &str, height: usize, tip: usize) -> Option<ChainProof>;
+
+    fn verify_inclusion(proof: &ChainProof, transaction: &Transaction, tip: &BlockHeader) -> bool;
+
+    fn prove_account(&self, account_id: &str, height: usize) -> Option<StateProof>;
+
+    fn verify_account(proof: &StateProof, header: &BlockHeader) -> bool;
+
     fn validate_transaction(&self, data: Vec<Transaction>, block_index: usize) -> bool;
 
-    fn verify_chain(&self) -> bool;
+    fn verify_chain(&self) -> Result<(), ChainError>;
+
+    /// Checks `block` as the next one on top of the tip, without replaying the chain. The block is
+    /// applied to the state and undone again.
+    fn verify_next(&mut self, block: &Block) -> Result<(), ChainError>;
 
     fn serialize(&self) -> String;
 
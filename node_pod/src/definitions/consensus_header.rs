@@ -5,21 +5,58 @@ use std::future::Future;
 use crate::definitions::transaction_header::Transaction;
 use std::collections::HashMap;
 use bls_signatures::PublicKey;
+use crate::definitions::signer_header::SignerError;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Stake {
     pub node_id: String,
     pub stake: f64,
+    /// Committee size in effect on the node's chain, when governance has set one
+    #[serde(default)]
+    pub committee_size: Option<usize>,
+    /// Epoch the node stakes for, the master drops stakes for epochs that are over
+    #[serde(default)]
+    pub epoch: u64,
+    /// BLS key the node registers, the hex of which is its node id
+    #[serde(default)]
+    pub public_key: String,
+    /// Signature by `public_key` over its possession message, without which the key is not registered
+    #[serde(default)]
+    pub proof_of_possession: String,
+    /// Milliseconds since the Unix epoch when the stake was signed, the master only accepts increasing nonces per node
+    #[serde(default)]
+    pub nonce: u64,
+    /// Signature by `public_key` over `stake_message`
+    #[serde(default)]
+    pub signature: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+/// Prefix of the message a stake signature covers, which no block, reply or proof of possession starts with
+pub const STAKE_DOMAIN: &str = "node_pod stake:";
+
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq)]
 pub struct Validator {
     pub node_id: String,
-    pub public_key: String
+    pub public_key: String,
+    /// Stake the master selected this validator with, weighs its governance votes
+    #[serde(default)]
+    pub stake: f64,
+    /// Copied from the stake, so every node checks it before trusting the key
+    #[serde(default)]
+    pub proof_of_possession: String
+}
+
+/// Committee the master selected for an epoch, committed in the header of the epoch's first
+/// block. `hash` covers the epoch and every member, so a block names the set it was agreed by.
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq, Default)]
+pub struct ValidatorSet {
+    pub epoch: u64,
+    pub validators: Vec<Validator>,
+    pub hash: String,
 }
 
 pub trait StakeMethods {
-    fn new(node: &Node, stake: f64) -> Self;
+    fn new(node: &Node, stake: f64, epoch: u64) -> Result<Self, SignerError> where Self: Sized;
 
     fn serialize(&self) -> String;
 
@@ -28,15 +65,31 @@ pub trait StakeMethods {
 
 pub trait ValidatorMethods {
 
+    fn verify_possession(&self) -> bool;
+
     fn serialize(&self) -> String;
 
     fn deserialize(json_str: String) -> Self;
 
 }
 
+pub trait ValidatorSetMethods {
+
+    fn new(epoch: u64, validators: Vec<Validator>) -> Self;
+
+    fn compute_hash(&self) -> String;
+
+    fn contains(&self, node_id: &str) -> bool;
+
+    fn serialize(&self) -> String;
+
+    fn deserialize(json_str: &str) -> Option<Self> where Self: Sized;
+
+}
+
 pub trait PoS {
 
-    fn propose_stake(&mut self, producer: &BaseProducer) -> impl Future<Output = ()> + Send;
+    fn propose_stake(&mut self, producer: &BaseProducer, epoch: u64) -> impl Future<Output = ()> + Send;
 
 }
 
@@ -0,0 +1,109 @@
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use crate::definitions::consensus_header::Validator;
+use crate::definitions::signer_header::{Signer, SignerError};
+
+/// Account governance transactions are sent to, they move no funds and pay no fee
+pub const GOVERNANCE_ACCOUNT: &str = "governance";
+
+/// A proposal passes once approving stake exceeds this share of its electorate's stake
+pub const APPROVAL_THRESHOLD: f64 = 2.0 / 3.0;
+
+#[derive(Serialize, Deserialize, Clone, Copy, std::fmt::Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Parameter {
+    BlockSize,
+    TxTime,
+    Timeout,
+    CommitteeSize,
+    MinFee,
+}
+
+/// Protocol parameters set on chain. Unset ones fall back to each node's own configuration.
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq, Default)]
+pub struct Parameters {
+    pub block_size: Option<usize>,
+    pub tx_time: Option<u64>,
+    pub timeout: Option<u64>,
+    pub committee_size: Option<usize>,
+    pub min_fee: Option<f64>,
+}
+
+/// Carried by a governance transaction, which a validator signs with its BLS key and sends
+/// from its node id. `electorate` is the stake of each member of the committee the proposal
+/// is made under, checked against that committee when pooled.
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum GovernanceAction {
+    /// Also counts as the proposer's approving vote
+    Propose { parameter: Parameter, value: f64, activation_height: u64, electorate: BTreeMap<String, f64> },
+    /// A later vote from the same validator replaces its earlier one
+    Vote { proposal: String, approve: bool },
+}
+
+/// `stake` is the voter's stake in the proposal's electorate, whatever committee it votes under
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq)]
+pub struct Ballot {
+    pub approve: bool,
+    pub stake: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq)]
+pub struct Proposal {
+    pub parameter: Parameter,
+    pub value: f64,
+    /// First height built with the new value, the proposal lapses if it has not passed by then
+    pub activation_height: u64,
+    /// Stake of each member of the committee the proposal was made under, only they can vote on it
+    pub electorate: BTreeMap<String, f64>,
+    pub votes: BTreeMap<String, Ballot>,
+}
+
+/// Open proposals keyed by the id of the transaction that made them, and the parameters in
+/// effect after the block at `height`
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq, Default)]
+pub struct Governance {
+    pub height: u64,
+    pub parameters: Parameters,
+    pub proposals: BTreeMap<String, Proposal>,
+}
+
+pub trait ParameterMethods {
+
+    fn get(&self, parameter: Parameter) -> Option<f64>;
+
+    fn set(&mut self, parameter: Parameter, value: f64);
+
+    fn is_valid(parameter: Parameter, value: f64) -> bool;
+
+}
+
+pub trait ProposalMethods {
+
+    fn approving_stake(&self) -> f64;
+
+    fn electorate_stake(&self) -> f64;
+
+    fn passed(&self) -> bool;
+
+}
+
+pub trait GovernanceMethods {
+
+    fn apply_action(&mut self, sender: &str, tx_id: &str, action: &GovernanceAction) -> bool;
+
+    fn activate(&mut self, height: u64) -> Vec<Parameter>;
+
+    fn is_empty(&self) -> bool;
+
+    fn digest(&self) -> Vec<u8>;
+
+}
+
+pub trait GovernanceTransactionMethods: Sized {
+
+    fn governance(signer: &dyn Signer, action: GovernanceAction, timestamp: u64) -> Result<Self, SignerError>;
+
+    fn check_governance(&self, validators: &[Validator]) -> bool;
+
+}
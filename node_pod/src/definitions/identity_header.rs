@@ -0,0 +1,45 @@
+use serde::{Serialize, Deserialize};
+use bls_signatures::PrivateKey;
+
+/// A node's BLS key as kept on disk. Without `sealed` the key is stored as plain hex,
+/// otherwise it is encrypted with AES-256-GCM under a key derived from a passphrase.
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug)]
+pub struct KeyFile {
+    pub node_id: String,
+    #[serde(default)]
+    pub sealed: Option<Sealing>,
+    pub key: String,
+}
+
+/// PBKDF2-HMAC-SHA256 and AES-256-GCM parameters of a sealed key, all hex encoded
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug)]
+pub struct Sealing {
+    pub iterations: u32,
+    pub salt: String,
+    pub iv: String,
+    pub tag: String,
+}
+
+#[derive(std::fmt::Debug, PartialEq)]
+pub enum IdentityError {
+    Io(String),
+    Format(String),
+    /// The file is sealed and no passphrase was given, or the passphrase is wrong
+    Passphrase,
+    /// The key does not belong to the node id recorded next to it
+    Mismatch,
+}
+
+pub trait IdentityMethods {
+
+    fn seal(private_key: &PrivateKey, passphrase: Option<&str>) -> Self;
+
+    fn open(&self, passphrase: Option<&str>) -> Result<PrivateKey, IdentityError>;
+
+    fn load(path: &str, passphrase: Option<&str>) -> Result<PrivateKey, IdentityError>;
+
+    fn store(&self, path: &str) -> Result<(), IdentityError>;
+
+    fn load_or_create(path: &str, passphrase: Option<&str>) -> Result<PrivateKey, IdentityError>;
+
+}
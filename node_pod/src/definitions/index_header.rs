@@ -0,0 +1,50 @@
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use crate::definitions::block_header::Block;
+use crate::definitions::merkle_header::Proof;
+use crate::definitions::transaction_header::Transaction;
+
+/// Position of a transaction in the chain, as its block height and its place in that block
+pub type TxLocation = (usize, usize);
+
+/// Lookups over a chain, extended as blocks are added. Heights index the chain directly.
+#[derive(Debug, Clone, Default)]
+pub struct ChainIndex {
+    pub by_hash: HashMap<String, usize>,
+    pub by_transaction: HashMap<String, TxLocation>,
+    pub sent: HashMap<String, Vec<TxLocation>>,
+    pub received: HashMap<String, Vec<TxLocation>>,
+}
+
+/// A transaction with the block it is in and its proof against that block's merkle root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionReceipt {
+    pub height: usize,
+    pub block_hash: String,
+    pub transaction: Transaction,
+    pub proof: Proof,
+}
+
+/// Every transaction an account sent or received, oldest first, with the height of its block
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct AccountHistory {
+    pub account_id: String,
+    pub sent: Vec<(usize, Transaction)>,
+    pub received: Vec<(usize, Transaction)>,
+}
+
+pub trait IndexMethods {
+
+    fn new() -> Self;
+
+    fn insert(&mut self, height: usize, block: &Block);
+
+    fn height_of(&self, block_hash: &str) -> Option<usize>;
+
+    fn locate(&self, tx_id: &str) -> Option<TxLocation>;
+
+    fn sent_by(&self, account_id: &str) -> &[TxLocation];
+
+    fn received_by(&self, account_id: &str) -> &[TxLocation];
+
+}
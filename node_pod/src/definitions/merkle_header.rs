@@ -1,30 +1,71 @@
 use serde::{Serialize, Deserialize};
 use crate::definitions::transaction_header::Transaction;
 
+/// Hashing rules used to build a tree. `Legacy` matches chains produced before domain
+/// separation and is what a block without a scheme deserializes to.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum MerkleScheme {
+    /// Leaves are raw transaction ids, internal nodes are sha256(left || right) and an odd
+    /// node is paired with itself
+    #[default]
+    Legacy,
+    /// Leaves are sha256(0x00 || id), internal nodes are sha256(0x01 || left || right) and
+    /// an odd node is promoted to the next level unchanged
+    Tagged,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MerkleTree {
     pub merkle_root: Vec<u8>,
     pub leaves: Vec<Vec<u8>>,
     pub nodes: Vec<Vec<u8>>,
     pub depth: u32,
+    pub scheme: MerkleScheme,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Proof {
     pub path: Vec<Vec<u8>>,
+    /// One bit per entry in `path`, true when the sibling is hashed on the right
+    pub right_sibling: Vec<bool>,
     pub leaf_index: usize,
 }
 
+/// Inclusion proof for several leaves of one tree. `hashes` holds only the siblings that
+/// can not be computed from the proven leaves, level by level in ascending index order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiProof {
+    pub leaf_indices: Vec<usize>,
+    pub leaf_count: usize,
+    pub hashes: Vec<Vec<u8>>,
+}
+
+/// Why a multi-proof could not be built or checked
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProofError {
+    /// Id of a transaction that is not hex encoded
+    InvalidId(String),
+    /// Id of a target that is not a leaf of the tree
+    NotInTree(String),
+    /// The root the proof is checked against is not hex encoded
+    InvalidRoot,
+}
+
 pub trait MerkleMethods {
 
     fn new_genesis() -> Self;
 
-    fn new(data: &[Transaction]) -> Self;
+    fn new(data: &[Transaction], scheme: MerkleScheme) -> Self;
 
     fn generate_proof(&self, target: &Transaction) -> Proof;
 
-    fn generate_root(data: &[Transaction]) -> Vec<u8>;
+    fn generate_root(data: &[Transaction], scheme: MerkleScheme) -> Vec<u8>;
+
+    fn validate_proof(proof: &Proof, leaf: Transaction, merkle_root: &[u8], scheme: MerkleScheme) -> bool;
+
+    /// Proves each distinct target once, in the order they first appear
+    fn generate_multi_proof(&self, targets: &[Transaction]) -> Result<MultiProof, ProofError>;
 
-    fn validate_proof(proof: &Proof, leaf: Transaction, merkle_root: &[u8]) -> bool;
+    fn validate_multi_proof(proof: &MultiProof, leaves: &[Transaction], merkle_root: &[u8], scheme: MerkleScheme) -> bool;
 
 }
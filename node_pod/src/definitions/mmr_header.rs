@@ -0,0 +1,35 @@
+use serde::{Serialize, Deserialize};
+
+/// Append-only Merkle Mountain Range over block hashes. Nodes are stored in post order so
+/// the first nodes of a larger range are exactly the nodes of any smaller prefix.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MerkleMountainRange {
+    pub leaf_count: usize,
+    pub nodes: Vec<Vec<u8>>,
+}
+
+/// Proof that a leaf is in the range of `leaf_count` leaves. `path` climbs from the leaf to
+/// its peak, `peaks` holds every peak of the range from left to right.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MmrProof {
+    pub leaf_index: usize,
+    pub leaf_count: usize,
+    pub path: Vec<Vec<u8>>,
+    pub peaks: Vec<Vec<u8>>,
+}
+
+pub trait MmrMethods {
+
+    fn new() -> Self;
+
+    fn append(&mut self, leaf: &[u8]);
+
+    fn root(&self) -> Vec<u8>;
+
+    fn root_at(&self, leaf_count: usize) -> Vec<u8>;
+
+    fn generate_proof(&self, leaf_index: usize, leaf_count: usize) -> Option<MmrProof>;
+
+    fn validate_proof(proof: &MmrProof, leaf: &[u8], root: &[u8]) -> bool;
+
+}
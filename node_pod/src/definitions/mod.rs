@@ -5,4 +5,11 @@ pub mod network_header;
 pub mod node_header;
 pub mod transaction_header;
 pub mod merkle_header;
-pub mod consensus_header;
\ No newline at end of file
+pub mod consensus_header;
+pub mod mmr_header;
+pub mod state_header;
+pub mod index_header;
+pub mod rpc_header;
+pub mod identity_header;
+pub mod signer_header;
+pub mod governance_header;
\ No newline at end of file
@@ -1,6 +1,7 @@
 use serde::{Serialize, Deserialize};
 use crate::definitions::block_header::Block;
 use crate::definitions::node_header::Node;
+use crate::definitions::signer_header::{Phase, SignerError};
 use bls_signatures::PublicKey;
 use rdkafka::{producer::BaseProducer, consumer::StreamConsumer};
 
@@ -21,16 +22,41 @@ pub struct NodeMessage {
     pub seq_num: usize
 }
 
+/// Sent to clients once a block commits. `msg_type` is a Reply carrying the serialized
+/// `ReplyBody` behind `REPLY_DOMAIN`, which is what `signature` covers.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ClientReply {
+    pub msg_type: MessageType,
+    pub signature: String,
+    pub sender_id: String
+}
+
+/// Replies match when they name the same block, a client needs f + 1 of them from
+/// distinct validators to treat the transactions as final
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ReplyBody {
+    pub view: u64,
+    pub height: u64,
+    pub block_hash: String,
+    pub tx_ids: Vec<String>
+}
+
 pub trait MessageTypeMethods {
     
     fn unwrap(&self) -> String;
 
+    fn phase(&self) -> Phase;
+
+    /// The block behind the domain of the message's phase, which is what the signature covers
+    fn block(&self) -> Option<Block>;
+
 }
 
 #[allow(async_fn_in_trait)]
 pub trait NodeMessageMethods {
 
-    fn new(node: &Node, block: &Block, msg_type: String, idx: usize) -> Self;
+    fn new(node: &Node, block: &Block, msg_type: String, idx: usize) -> Result<Self, SignerError> where Self: Sized;
 
     fn deserialize_message(msg_json: String) -> NodeMessage;
 
@@ -39,9 +65,21 @@ pub trait NodeMessageMethods {
 
 }
 
+pub trait ClientReplyMethods {
+
+    fn new(node: &Node, block: &Block) -> Result<Self, SignerError> where Self: Sized;
+
+    fn body(&self) -> Option<ReplyBody>;
+
+    fn verify(&self, pub_key: &PublicKey) -> bool;
+
+}
+
 #[allow(async_fn_in_trait)]
 pub trait Network {
     fn broadcast_kafka(&self, topic: &str, message: NodeMessage, producer: &BaseProducer) -> impl std::future::Future<Output = ()> + Send;
+
+    async fn broadcast_reply(&self, reply: ClientReply, producer: &BaseProducer);
     
     async fn consume_kafka(id: String, hash: String, topic: &str, consumer: &StreamConsumer, producer: &BaseProducer,time_out: u64) -> Option<Vec<String>>;
 
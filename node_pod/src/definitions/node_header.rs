@@ -1,14 +1,16 @@
 use serde::{Serialize, Deserialize};
-use crate::definitions::block_header::{Block, BlockChain};
+use crate::definitions::block_header::{Block, SharedChain};
 use crate::definitions::transaction_header::Transaction;
-use crate::definitions::consensus_header::Validator;
+use crate::definitions::consensus_header::{Validator, ValidatorSet};
+use crate::definitions::signer_header::{Phase, Signer, SignerError};
 use bls_signatures::{PrivateKey, PublicKey};
 use rdkafka::{consumer::StreamConsumer, producer::BaseProducer};
 use std::sync::Arc;
 use std::collections::HashMap;
+use crate::definitions::state_header::Account;
 
 
-#[derive(Serialize, Deserialize, Clone, std::fmt::Debug)]
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq)]
 pub enum NodeState {
     Idle,
     PrePreparing,
@@ -19,15 +21,24 @@ pub enum NodeState {
 
 pub struct Node {
     pub id: String,
-    pub block_chain: BlockChain,
+    pub block_chain: SharedChain,
     pub stake: f64,
     pub state: NodeState,
     pub staging: Vec<Transaction>,
     pub block_staging: Vec<Block>,
-    pub validators: Vec<Validator>,
+    /// Committee of the current epoch
+    pub validator_set: ValidatorSet,
     pub primary: Vec<Validator>,
     pub msg_idx: Vec<usize>,
-    pub private_key: PrivateKey
+    pub signer: Arc<dyn Signer>
+}
+
+pub struct BlockPolicy {
+    pub max_size: usize,
+    pub max_bytes: usize,
+    pub max_wait: u64,
+    /// Transfers paying less are dropped, governance transactions pay no fee
+    pub min_fee: f64
 }
 
 pub struct PoolingMetrics {
@@ -51,10 +62,14 @@ pub trait NodeMethods {
     
     fn new() -> Self;
 
-    fn sign_message(&self, block: &Block) -> String;
+    fn from_key(private_key: PrivateKey) -> Self;
+
+    fn from_signer(signer: Arc<dyn Signer>) -> Self;
+
+    fn sign_message(&self, message: &str, phase: Phase) -> Result<String, SignerError>;
 
-    async fn pool_transactions(&mut self, consumer: &StreamConsumer, user_base: &mut HashMap<String, f64>,
-         residual: &mut Vec<Transaction>, time_out: u64, tx_time: u64, block_size: &usize) -> (Option<Vec<Transaction>>,Vec<Transaction>, Option<PoolingMetrics>);
+    async fn pool_transactions(&mut self, consumer: &StreamConsumer, user_base: &mut HashMap<String, Account>,
+         residual: &mut Vec<Transaction>, tx_time: u64, policy: &BlockPolicy) -> (Vec<Transaction>, Vec<Transaction>, PoolingMetrics);
 
     async fn concensus(&mut self, pool: Vec<Transaction>, pkey_store: HashMap<String, PublicKey>,
     prepre_con: Arc<StreamConsumer>, pre_con: Arc<StreamConsumer>,
@@ -0,0 +1,135 @@
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc::UnboundedSender, RwLock};
+use crate::definitions::block_header::{Block, SharedChain};
+use crate::definitions::index_header::TransactionReceipt;
+use crate::definitions::consensus_header::{Validator, ValidatorSet};
+use crate::definitions::node_header::{Node, NodeState};
+use crate::definitions::transaction_header::Transaction;
+use crate::definitions::signer_header::Signer;
+
+/// State the RPC server answers from. The chain is the node's own, the rest is copied from the
+/// node, which the main loop owns, when it publishes between the steps of a round.
+#[derive(Clone)]
+pub struct RpcState {
+    pub node_id: String,
+    pub block_chain: SharedChain,
+    /// Chain length at the last publish, subscribers are told when it grows
+    pub height: usize,
+    /// Ids waiting in the node's pool
+    pub pending: HashSet<String>,
+    /// Ids accepted over RPC that are not yet pending or committed
+    pub submitted: HashSet<String>,
+    /// Accepted transactions on their way to the Transactions topic
+    pub submissions: UnboundedSender<Transaction>,
+    /// Chain length after every publish that added blocks, for streaming subscribers
+    pub tips: broadcast::Sender<usize>,
+    pub validator_set: ValidatorSet,
+    pub primary: Vec<Validator>,
+    pub node_state: NodeState,
+    /// Signs the governance transactions this node is asked to send
+    pub signer: Arc<dyn Signer>,
+}
+
+pub type SharedRpcState = Arc<RwLock<RpcState>>;
+
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug)]
+pub struct RpcRequest {
+    pub jsonrpc: String,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+    #[serde(default)]
+    pub id: Value,
+}
+
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+/// Exactly one of `result` and `error` is set
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug)]
+pub struct RpcResponse {
+    pub jsonrpc: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+    pub id: Value,
+}
+
+/// Why a submitted transaction was turned away
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq)]
+pub enum Rejection {
+    Format(String),
+    Id,
+    Signature,
+    /// Its id is already committed, pending or submitted
+    Replay,
+    UnknownSender,
+    Balance,
+    /// Its nonce is not the one after the sender's committed nonce
+    Nonce,
+    Fee,
+    /// Not from a member of the current committee, or does not apply to the committed governance
+    Governance,
+}
+
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq)]
+pub struct SubmitResult {
+    pub id: String,
+    pub accepted: bool,
+    pub reason: Option<String>,
+}
+
+/// First message a stream client sends. Without `from_height` a stream starts at the next block,
+/// reconnecting clients pass the height after the last one they saw.
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq)]
+#[serde(tag = "subscribe", rename_all = "snake_case")]
+pub enum Subscription {
+    Blocks {
+        #[serde(default)]
+        from_height: Option<usize>
+    },
+    /// Ends after the confirmation is sent, which is immediate for committed transactions
+    Transaction {
+        id: String
+    },
+    Account {
+        account: String,
+        #[serde(default)]
+        from_height: Option<usize>
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Block { height: usize, block: Block },
+    Confirmation { receipt: TransactionReceipt },
+    Balance { height: usize, account_id: String, balance: f64, nonce: u64 },
+}
+
+pub trait RpcMethods {
+
+    fn new(node: &Node, submissions: UnboundedSender<Transaction>) -> Self;
+
+    fn publish(&mut self, node: &Node, pending: &[Transaction]);
+
+    fn handle(&self, request: RpcRequest) -> RpcResponse;
+
+    fn check_transaction(&self, transaction: &Transaction) -> Result<(), Rejection>;
+
+    fn submit(&mut self, transaction: Transaction) -> SubmitResult;
+
+    fn handle_submission(&mut self, request: RpcRequest) -> RpcResponse;
+
+    fn handle_governance(&mut self, request: RpcRequest, local: bool) -> RpcResponse;
+
+    fn events(&self, subscription: &Subscription, from: usize, to: usize) -> Vec<Event>;
+
+}
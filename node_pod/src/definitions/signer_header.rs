@@ -0,0 +1,162 @@
+use serde::{Serialize, Deserialize};
+use bls_signatures::{PrivateKey, PublicKey, Signature};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// Consensus step a signature is produced for
+#[derive(Serialize, Deserialize, Clone, Copy, std::fmt::Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Phase {
+    PrePrepare,
+    Prepare,
+    Commit,
+    Reply,
+    /// Governance transactions, which name no block and are only checked to start with `GOVERNANCE_DOMAIN`
+    Governance,
+    /// Proof that the node holds the key it registers, only ever over `possession_message` of its own key
+    Possession,
+    /// Stakes sent to the master, which name no block and are only checked to start with `STAKE_DOMAIN`
+    Stake,
+}
+
+/// Prefix of the message a proof of possession signs, which no block or reply can start with
+pub const POSSESSION_DOMAIN: &str = "node_pod proof of possession:";
+
+// Prefixes of the messages signed in the other phases. A signature only verifies for the
+// phase it was made in, whatever the message behind the prefix parses as.
+pub const PREPREPARE_DOMAIN: &str = "node_pod preprepare:";
+pub const PREPARE_DOMAIN: &str = "node_pod prepare:";
+pub const COMMIT_DOMAIN: &str = "node_pod commit:";
+pub const REPLY_DOMAIN: &str = "node_pod reply:";
+pub const GOVERNANCE_DOMAIN: &str = "node_pod governance:";
+
+/// A message to sign, which starts with the domain of its phase. The signer reads the height,
+/// view and block hash out of the message itself, a serialized block for the consensus phases
+/// and a serialized `ReplyBody` for replies, so a caller cannot vouch for one block while
+/// getting another signed.
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug)]
+pub struct SigningRequest {
+    pub phase: Phase,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq)]
+pub enum SignerError {
+    /// A different block was already signed for this height, view and phase
+    DoubleSign { height: u64, view: u64, phase: Phase },
+    /// The height is below what the signer still remembers, so it cannot rule out a double sign
+    Stale(u64),
+    Message(String),
+    Key(String),
+    Remote(String),
+    /// The guard's marks could not be read or written, so nothing is signed
+    Guard(String),
+}
+
+/// Highest height and view signed for a phase and the block signed there
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq)]
+pub struct GuardMark {
+    pub height: u64,
+    pub view: u64,
+    pub hash: String,
+}
+
+/// Block hashes signed per (height, view, phase). Heights more than `window` below the
+/// highest signed one are forgotten and refused from then on.
+pub struct SigningGuard {
+    pub signed: BTreeMap<(u64, u64, Phase), String>,
+    pub floor: u64,
+    pub window: u64,
+    /// Highest signed per phase, written to `path` before each signature that raises one
+    pub marks: BTreeMap<Phase, GuardMark>,
+    /// Marks read from `path` at start. Below them the signer can not tell what it signed
+    /// before the restart and refuses, at them it only signs the same block again.
+    pub restored: BTreeMap<Phase, GuardMark>,
+    pub path: Option<String>,
+}
+
+pub trait PhaseMethods {
+
+    fn domain(&self) -> &'static str;
+
+    /// `body` behind the phase's domain, which is what gets signed
+    fn message(&self, body: &str) -> String;
+
+    /// What follows the phase's domain, None when the message is not behind it
+    fn body<'a>(&self, message: &'a str) -> Option<&'a str>;
+
+}
+
+pub trait SigningGuardMethods {
+
+    fn new(window: u64) -> Self;
+
+    /// Guard whose marks are kept in `path`, picking up those of an earlier run
+    fn open(path: &str, window: u64) -> Result<Self, SignerError> where Self: Sized;
+
+    fn check(&mut self, request: &SigningRequest) -> Result<(), SignerError>;
+
+}
+
+pub trait Signer: Send + Sync {
+
+    fn public_key(&self) -> PublicKey;
+
+    fn sign(&self, request: &SigningRequest) -> Result<Signature, SignerError>;
+
+}
+
+/// Signs in process with a key held in memory
+pub struct LocalSigner {
+    pub private_key: PrivateKey,
+    pub guard: Mutex<SigningGuard>,
+}
+
+/// Signs with a key loaded from a key file, sealed or not, which is created if missing. The
+/// guard's marks are kept next to it in `<path>.guard`.
+pub struct FileSigner {
+    pub path: String,
+    pub inner: LocalSigner,
+}
+
+/// Forwards signing requests to a signer process listening on a Unix socket, which keeps
+/// the key and the double-sign guard out of the node
+pub struct RemoteSigner {
+    pub socket: String,
+    pub public_key: PublicKey,
+}
+
+/// One JSON line per request on the signer socket, answered by one `SignerResponse` line
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug)]
+#[serde(tag = "request", rename_all = "snake_case")]
+pub enum SignerRequest {
+    PublicKey,
+    Sign { phase: Phase, message: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug)]
+#[serde(tag = "response", rename_all = "snake_case")]
+pub enum SignerResponse {
+    PublicKey { public_key: String },
+    Signature { signature: String },
+    Refused { error: SignerError },
+}
+
+pub trait LocalSignerMethods {
+
+    fn new(private_key: PrivateKey) -> Self;
+
+}
+
+pub trait FileSignerMethods: Sized {
+
+    fn open(path: &str, passphrase: Option<&str>) -> Result<Self, SignerError>;
+
+}
+
+pub trait RemoteSignerMethods: Sized {
+
+    fn connect(socket: &str) -> Result<Self, SignerError>;
+
+    fn serve(signer: &dyn Signer, socket: &str) -> std::io::Result<()>;
+
+}
@@ -0,0 +1,88 @@
+use serde::{Serialize, Deserialize};
+use std::collections::{BTreeMap, HashMap};
+use crate::definitions::transaction_header::Transaction;
+use crate::definitions::governance_header::Governance;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Account {
+    pub balance: f64,
+    pub nonce: u64,
+}
+
+/// Account state committed as a sparse Merkle tree of depth 256 keyed by sha256(account id).
+/// Once governance holds anything the root also commits to it, before that it is the tree root.
+/// Accounts only change through `StateMethods`, which keep `nodes` in step with them.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(from = "StateRecord")]
+pub struct SparseMerkleTree {
+    pub accounts: BTreeMap<String, Account>,
+    #[serde(default)]
+    pub governance: Governance,
+    /// Hashes of the non-empty subtrees keyed by depth and key prefix, rebuilt on deserialization
+    #[serde(skip)]
+    pub nodes: HashMap<(usize, [u8; 32]), Vec<u8>>,
+    /// Changes of the block being applied, between `start_block` and `finish_block`
+    #[serde(skip)]
+    pub pending: Option<StateDiff>,
+}
+
+/// Serialized form of a `SparseMerkleTree`
+#[derive(Deserialize)]
+pub struct StateRecord {
+    pub accounts: BTreeMap<String, Account>,
+    #[serde(default)]
+    pub governance: Governance,
+}
+
+/// What a block changed, holding the values it replaced so the block can be undone
+#[derive(Debug, Clone, Default)]
+pub struct StateDiff {
+    /// Accounts the block touched with their values before it, None for the ones it created
+    pub accounts: BTreeMap<String, Option<Account>>,
+    /// Governance height before the block
+    pub height: u64,
+    /// Governance before the block, kept only when the block changed more than its height
+    pub governance: Option<Governance>,
+}
+
+/// Proof of an account's state, or of its absence when `account` is None. `siblings` climbs
+/// from the leaf to the root, an empty entry stands for an empty subtree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateProof {
+    pub account_id: String,
+    pub account: Option<Account>,
+    pub siblings: Vec<Vec<u8>>,
+    /// Digest of the governance state the root commits to, if any
+    #[serde(default)]
+    pub governance: Option<Vec<u8>>,
+}
+
+pub trait StateMethods {
+
+    fn new() -> Self;
+
+    fn from_balances(balances: &HashMap<String, f64>) -> Self;
+
+    fn apply_transaction(&mut self, transaction: &Transaction) -> bool;
+
+    fn apply_transactions(&self, transactions: &[Transaction]) -> Self;
+
+    fn start_block(&mut self, height: u64);
+
+    fn finish_block(&mut self) -> StateDiff;
+
+    fn apply_block(&mut self, height: u64, transactions: &[Transaction]) -> StateDiff;
+
+    /// Undoes a finished block, which must be the last one applied
+    fn revert(&mut self, diff: &StateDiff);
+
+    /// Undoes the changes of a block that was started and not finished
+    fn rollback(&mut self);
+
+    fn root(&self) -> Vec<u8>;
+
+    fn generate_proof(&self, account_id: &str) -> StateProof;
+
+    fn validate_proof(proof: &StateProof, state_root: &[u8]) -> bool;
+
+}
@@ -1,6 +1,7 @@
 
 use serde::{Serialize, Deserialize};
 use ring::signature::UnparsedPublicKey;
+use crate::definitions::governance_header::GovernanceAction;
 
 
 #[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq)]
@@ -12,6 +13,14 @@ pub struct Transaction {
     pub amount: f64,
     pub fee: f64,
     pub signature: String,
+    /// Set on governance transactions, which a validator signs with its BLS key. Left out of
+    /// the JSON when unset so transfers keep their ids and signatures.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub governance: Option<GovernanceAction>,
+    /// Sender's account nonce once this transaction applies, the transaction only applies right
+    /// after the sender's previous one. Left out of the JSON when unset like `governance`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<u64>,
 }
 
 pub trait TransactionMethods {
@@ -24,4 +33,8 @@ pub trait TransactionMethods {
     fn is_equal(self, tx: Transaction) -> bool;
 
     fn verify_transaction(&self, public_key: UnparsedPublicKey<Vec<u8>>) -> bool;
+
+    fn verify_sender(&self) -> bool;
+
+    fn compute_id(&self) -> String;
 }
@@ -0,0 +1,101 @@
+use serde::{Serialize, Deserialize};
+use std::io::Write;
+use crate::definitions::block_header::{BlockChain, BlockChainMethods, ChainProof};
+use crate::definitions::transaction_header::Transaction;
+
+// Commands behind the explorer binary. Lookups print pretty JSON, listings print one
+// line per block, failures come back as the message to show the user.
+
+pub const USAGE: &str = "Usage: explorer <chain.json> <command>
+
+Commands:
+    headers [from] [to]      List block headers
+    verify                   Verify the whole chain
+    tx <id>                  Show a transaction with its Merkle proof
+    account <id>             Show an account's balance and history
+    prove <id> [tip]         Prove a transaction is in the chain up to tip
+    check <proof.json>       Check a proof written by prove
+    export [from] [to]       Write blocks as JSON lines";
+
+/// What `prove` writes and `check` reads back
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug)]
+pub struct InclusionBundle {
+    pub tip: usize,
+    pub transaction: Transaction,
+    pub proof: ChainProof,
+}
+
+fn height(arg: Option<&String>, default: usize) -> Result<usize, String> {
+    match arg {
+        Some(arg) => arg.parse().map_err(|_| format!("Invalid height {}", arg)),
+        None => Ok(default)
+    }
+}
+
+fn write_json<T: Serialize>(out: &mut impl Write, value: &T) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value).expect("Failed to serialize output");
+    writeln!(out, "{}", json).map_err(|e| e.to_string())
+}
+
+pub fn run(chain: &BlockChain, command: &[String], out: &mut impl Write) -> Result<(), String> {
+    let tip = chain.headers.len() - 1;
+    let arg = |i: usize| command.get(i);
+    let required = |i: usize, name: &str| command.get(i).ok_or(format!("Missing {}\n\n{}", name, USAGE));
+
+    match command.first().map(|name| name.as_str()) {
+        Some("headers") => {
+            let (from, to) = (height(arg(1), 0)?, height(arg(2), tip + 1)?);
+            for (i, header) in chain.headers(from, to).iter().enumerate() {
+                writeln!(out, "{} {} txs={} time={} view={} proposer={}", from + i, chain.headers[from + i].hash,
+                    header.tx_count, header.timestamp, header.view, header.proposer).map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        }
+        Some("verify") => {
+            chain.verify_chain().map_err(|e| e.to_string())?;
+            writeln!(out, "Chain of {} blocks is valid", chain.headers.len()).map_err(|e| e.to_string())
+        }
+        Some("tx") => {
+            let receipt = chain.transaction(required(1, "transaction id")?).ok_or("Transaction not found")?;
+            write_json(out, &receipt)
+        }
+        Some("account") => {
+            let account_id = required(1, "account id")?;
+            let account = chain.state.accounts.get(account_id);
+            write_json(out, &serde_json::json!({
+                "account": account,
+                "history": chain.account_history(account_id)
+            }))
+        }
+        Some("prove") => {
+            let tx_id = required(1, "transaction id")?;
+            let tip = height(arg(2), tip)?;
+            let receipt = chain.transaction(tx_id).ok_or("Transaction not found")?;
+            let proof = chain.prove_inclusion(tx_id, receipt.height, tip)
+                .ok_or(format!("Block {} is not an ancestor of block {}", receipt.height, tip))?;
+            write_json(out, &InclusionBundle { tip, transaction: receipt.transaction, proof })
+        }
+        Some("check") => {
+            let path = required(1, "proof file")?;
+            let json = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+            let bundle: InclusionBundle = serde_json::from_str(&json).map_err(|e| format!("Invalid proof file: {}", e))?;
+            let header = chain.header(bundle.tip).ok_or(format!("Chain has no block {}", bundle.tip))?;
+
+            if !BlockChain::verify_inclusion(&bundle.proof, &bundle.transaction, header) {
+                return Err(format!("Proof for {} does not check against block {}", bundle.transaction.id, bundle.tip));
+            }
+            writeln!(out, "Transaction {} is in block {}", bundle.transaction.id, bundle.proof.header.index)
+                .map_err(|e| e.to_string())
+        }
+        Some("export") => {
+            let (from, to) = (height(arg(1), 0)?, height(arg(2), tip + 1)?);
+            for block in (from..to.min(tip + 1)).filter_map(|height| chain.block_by_height(height)) {
+                let line = serde_json::to_string(&block).expect("Failed to serialize block");
+                writeln!(out, "{}", line).map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        }
+        Some(other) => Err(format!("Unknown command {}\n\n{}", other, USAGE)),
+        None => Err(USAGE.to_string())
+    }
+}
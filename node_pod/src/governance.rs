@@ -0,0 +1,168 @@
+use bls_signatures::Serialize;
+use openssl::sha::Sha256;
+use std::collections::BTreeMap;
+use crate::definitions::consensus_header::Validator;
+use crate::definitions::governance_header::{Governance, GovernanceAction, GovernanceMethods, GovernanceTransactionMethods,
+    Ballot, Parameter, ParameterMethods, Parameters, Proposal, ProposalMethods, APPROVAL_THRESHOLD, GOVERNANCE_ACCOUNT};
+use crate::definitions::signer_header::{Phase, PhaseMethods, Signer, SignerError, SigningRequest};
+use crate::definitions::transaction_header::{Transaction, TransactionMethods};
+
+/// Stake of each member of a committee, which a proposal made under it is decided by
+pub fn electorate(validators: &[Validator]) -> BTreeMap<String, f64> {
+    validators.iter().map(|validator| (validator.node_id.clone(), validator.stake)).collect()
+}
+
+impl ParameterMethods for Parameters {
+    fn get(&self, parameter: Parameter) -> Option<f64> {
+        match parameter {
+            Parameter::BlockSize => self.block_size.map(|value| value as f64),
+            Parameter::TxTime => self.tx_time.map(|value| value as f64),
+            Parameter::Timeout => self.timeout.map(|value| value as f64),
+            Parameter::CommitteeSize => self.committee_size.map(|value| value as f64),
+            Parameter::MinFee => self.min_fee,
+        }
+    }
+
+    fn set(&mut self, parameter: Parameter, value: f64) {
+        match parameter {
+            Parameter::BlockSize => self.block_size = Some(value as usize),
+            Parameter::TxTime => self.tx_time = Some(value as u64),
+            Parameter::Timeout => self.timeout = Some(value as u64),
+            Parameter::CommitteeSize => self.committee_size = Some(value as usize),
+            Parameter::MinFee => self.min_fee = Some(value),
+        }
+    }
+
+    // Counts and durations are positive whole numbers, the fee any non negative amount
+    fn is_valid(parameter: Parameter, value: f64) -> bool {
+        match parameter {
+            Parameter::MinFee => value.is_finite() && value >= 0.0,
+            _ => value.is_finite() && value >= 1.0 && value.fract() == 0.0 && value <= u32::MAX as f64
+        }
+    }
+}
+
+impl ProposalMethods for Proposal {
+    fn approving_stake(&self) -> f64 {
+        self.votes.values().filter(|ballot| ballot.approve).map(|ballot| ballot.stake).sum()
+    }
+
+    // Summed in node id order so every node gets the same float
+    fn electorate_stake(&self) -> f64 {
+        self.electorate.values().sum()
+    }
+
+    fn passed(&self) -> bool {
+        self.approving_stake() > APPROVAL_THRESHOLD * self.electorate_stake()
+    }
+}
+
+impl GovernanceMethods for Governance {
+    // Returns false and leaves governance untouched for actions that can not apply at `height`
+    fn apply_action(&mut self, sender: &str, tx_id: &str, action: &GovernanceAction) -> bool {
+        match action {
+            GovernanceAction::Propose { parameter, value, activation_height, electorate } => {
+                let stake = match electorate.get(sender) {
+                    Some(stake) => *stake,
+                    None => { return false; }
+                };
+                if !Parameters::is_valid(*parameter, *value) || *activation_height <= self.height
+                    || !electorate.values().all(|stake| stake.is_finite() && *stake > 0.0)
+                    || self.proposals.contains_key(tx_id) {
+                    return false;
+                }
+
+                let mut proposal = Proposal { parameter: *parameter, value: *value, activation_height: *activation_height,
+                    electorate: electorate.clone(), votes: Default::default() };
+                proposal.votes.insert(sender.to_string(), Ballot { approve: true, stake });
+                self.proposals.insert(tx_id.to_string(), proposal);
+                true
+            }
+            // Weighed by the voter's stake in the proposal's electorate rather than in the committee it votes under
+            GovernanceAction::Vote { proposal, approve } => {
+                let Some(proposal) = self.proposals.get_mut(proposal) else {
+                    return false;
+                };
+                match proposal.electorate.get(sender) {
+                    Some(stake) => {
+                        proposal.votes.insert(sender.to_string(), Ballot { approve: *approve, stake: *stake });
+                        true
+                    }
+                    None => false
+                }
+            }
+        }
+    }
+
+    // Closes every proposal due by `height`, applying the ones that passed in proposal id order
+    fn activate(&mut self, height: u64) -> Vec<Parameter> {
+        let due: Vec<String> = self.proposals.iter()
+            .filter(|(_, proposal)| proposal.activation_height <= height)
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        let mut activated = vec![];
+        for id in due {
+            let proposal = self.proposals.remove(&id).expect("Due proposal is missing");
+            if proposal.passed() {
+                self.parameters.set(proposal.parameter, proposal.value);
+                activated.push(proposal.parameter);
+            }
+        }
+        activated
+    }
+
+    fn is_empty(&self) -> bool {
+        self.proposals.is_empty() && self.parameters == Parameters::default()
+    }
+
+    fn digest(&self) -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        hasher.update(&serde_json::to_vec(&self.parameters).expect("Failed to serialize parameters"));
+        hasher.update(&serde_json::to_vec(&self.proposals).expect("Failed to serialize proposals"));
+        hasher.finish().to_vec()
+    }
+}
+
+impl GovernanceTransactionMethods for Transaction {
+    fn governance(signer: &dyn Signer, action: GovernanceAction, timestamp: u64) -> Result<Self, SignerError> {
+        let mut transaction = Transaction {
+            id: String::new(),
+            from: hex::encode(signer.public_key().as_bytes()),
+            to: GOVERNANCE_ACCOUNT.to_string(),
+            timestamp,
+            amount: 0.0,
+            fee: 0.0,
+            signature: String::new(),
+            governance: Some(action),
+            nonce: None,
+        };
+        transaction.id = transaction.compute_id();
+
+        let message = Phase::Governance.message(&transaction.serialize_tx());
+        let signature = signer.sign(&SigningRequest { phase: Phase::Governance, message })?;
+        transaction.signature = hex::encode(signature.as_bytes());
+        Ok(transaction)
+    }
+
+    // A proposal's electorate is declared by the sender, so it must match the committee the transaction is pooled under
+    fn check_governance(&self, validators: &[Validator]) -> bool {
+        let action = match &self.governance {
+            Some(action) => action,
+            None => { return false; }
+        };
+        if self.to != GOVERNANCE_ACCOUNT || self.amount != 0.0 || self.fee != 0.0
+            || self.id != self.compute_id() || !self.verify_sender() {
+            return false;
+        }
+
+        if !validators.iter().any(|validator| validator.node_id == self.from) {
+            return false;
+        }
+
+        match action {
+            GovernanceAction::Propose { electorate: declared, .. } => *declared == electorate(validators),
+            GovernanceAction::Vote { .. } => true
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use bls_signatures::{PrivateKey, Serialize};
+use openssl::hash::MessageDigest;
+use openssl::pkcs5::pbkdf2_hmac;
+use openssl::rand::rand_bytes;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use std::path::Path;
+use crate::definitions::identity_header::{IdentityError, IdentityMethods, KeyFile, Sealing};
+
+const ITERATIONS: u32 = 100_000;
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; len];
+    rand_bytes(&mut bytes).expect("Failed to generate random bytes");
+    bytes
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> Vec<u8> {
+    let mut key = vec![0u8; 32];
+    pbkdf2_hmac(passphrase.as_bytes(), salt, iterations as usize, MessageDigest::sha256(), &mut key)
+        .expect("Failed to derive key");
+    key
+}
+
+fn decode(field: &str, value: &str) -> Result<Vec<u8>, IdentityError> {
+    hex::decode(value).map_err(|_| IdentityError::Format(format!("{} is not hex", field)))
+}
+
+impl IdentityMethods for KeyFile {
+    fn seal(private_key: &PrivateKey, passphrase: Option<&str>) -> Self {
+        let node_id = hex::encode(private_key.public_key().as_bytes());
+        let secret = private_key.as_bytes();
+
+        // The node id is bound as associated data so a sealed key cannot be moved under another id
+        match passphrase {
+            None => KeyFile { node_id, sealed: None, key: hex::encode(secret) },
+            Some(passphrase) => {
+                let (salt, iv) = (random_bytes(16), random_bytes(12));
+                let mut tag = [0u8; 16];
+                let key = encrypt_aead(Cipher::aes_256_gcm(), &derive_key(passphrase, &salt, ITERATIONS),
+                    Some(&iv), node_id.as_bytes(), &secret, &mut tag).expect("Failed to seal key");
+
+                let sealed = Sealing { iterations: ITERATIONS, salt: hex::encode(salt), iv: hex::encode(iv),
+                    tag: hex::encode(tag) };
+                KeyFile { node_id, sealed: Some(sealed), key: hex::encode(key) }
+            }
+        }
+    }
+
+    fn open(&self, passphrase: Option<&str>) -> Result<PrivateKey, IdentityError> {
+        let key = decode("key", &self.key)?;
+
+        let secret = match (&self.sealed, passphrase) {
+            (None, _) => key,
+            (Some(_), None) => return Err(IdentityError::Passphrase),
+            (Some(sealed), Some(passphrase)) => {
+                let salt = decode("salt", &sealed.salt)?;
+                decrypt_aead(Cipher::aes_256_gcm(), &derive_key(passphrase, &salt, sealed.iterations),
+                    Some(&decode("iv", &sealed.iv)?), self.node_id.as_bytes(), &key, &decode("tag", &sealed.tag)?)
+                    .map_err(|_| IdentityError::Passphrase)?
+            }
+        };
+
+        let private_key = PrivateKey::from_bytes(&secret)
+            .map_err(|e| IdentityError::Format(format!("Invalid BLS key: {}", e)))?;
+
+        if hex::encode(private_key.public_key().as_bytes()) != self.node_id {
+            return Err(IdentityError::Mismatch);
+        }
+        Ok(private_key)
+    }
+
+    fn load(path: &str, passphrase: Option<&str>) -> Result<PrivateKey, IdentityError> {
+        let json = std::fs::read_to_string(path).map_err(|e| IdentityError::Io(format!("{}: {}", path, e)))?;
+        let key_file: KeyFile = serde_json::from_str(&json).map_err(|e| IdentityError::Format(e.to_string()))?;
+        key_file.open(passphrase)
+    }
+
+    // Written to a temporary file first and renamed, so a crash never leaves a half written key
+
+    fn store(&self, path: &str) -> Result<(), IdentityError> {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let io_error = |e: std::io::Error| IdentityError::Io(format!("{}: {}", path, e));
+        if let Some(dir) = Path::new(path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(io_error)?;
+        }
+
+        let tmp = format!("{}.tmp", path);
+        let mut file = std::fs::OpenOptions::new().write(true).create(true).truncate(true).mode(0o600)
+            .open(&tmp).map_err(io_error)?;
+        file.write_all(serde_json::to_string_pretty(self).expect("Failed to serialize key file").as_bytes())
+            .and_then(|_| file.sync_all()).map_err(io_error)?;
+        std::fs::rename(&tmp, path).map_err(io_error)
+    }
+
+    fn load_or_create(path: &str, passphrase: Option<&str>) -> Result<PrivateKey, IdentityError> {
+        if Path::new(path).exists() {
+            return Self::load(path, passphrase);
+        }
+
+        let private_key = PrivateKey::generate(&mut rand::thread_rng());
+        Self::seal(&private_key, passphrase).store(path)?;
+        Ok(private_key)
+    }
+}
+
+impl std::fmt::Display for IdentityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            IdentityError::Io(e) => write!(f, "Failed to access key file {}", e),
+            IdentityError::Format(e) => write!(f, "Malformed key file: {}", e),
+            IdentityError::Passphrase => write!(f, "Key file is sealed and the passphrase is missing or wrong"),
+            IdentityError::Mismatch => write!(f, "Key file does not match its node id"),
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+use crate::definitions::block_header::Block;
+use crate::definitions::index_header::{ChainIndex, IndexMethods, TxLocation};
+
+impl IndexMethods for ChainIndex {
+
+    fn new() -> Self {
+        ChainIndex { by_hash: HashMap::new(), by_transaction: HashMap::new(), sent: HashMap::new(), received: HashMap::new() }
+    }
+
+    fn insert(&mut self, height: usize, block: &Block) {
+        self.by_hash.insert(block.hash.clone(), height);
+
+        for (position, transaction) in block.transactions.iter().enumerate() {
+            let location = (height, position);
+
+            // A replayed id keeps pointing at the block that first included it
+            self.by_transaction.entry(transaction.id.clone()).or_insert(location);
+            self.sent.entry(transaction.from.clone()).or_default().push(location);
+            self.received.entry(transaction.to.clone()).or_default().push(location);
+        }
+    }
+
+    fn height_of(&self, block_hash: &str) -> Option<usize> {
+        self.by_hash.get(block_hash).copied()
+    }
+
+    fn locate(&self, tx_id: &str) -> Option<TxLocation> {
+        self.by_transaction.get(tx_id).copied()
+    }
+
+    fn sent_by(&self, account_id: &str) -> &[TxLocation] {
+        self.sent.get(account_id).map(|locations| locations.as_slice()).unwrap_or(&[])
+    }
+
+    fn received_by(&self, account_id: &str) -> &[TxLocation] {
+        self.received.get(account_id).map(|locations| locations.as_slice()).unwrap_or(&[])
+    }
+}
@@ -0,0 +1,18 @@
+pub mod consensus;
+pub mod network;
+mod tests;
+pub mod definitions;
+pub mod transaction;
+pub mod simulate;
+pub mod merkle_tree;
+pub mod mmr;
+pub mod state;
+pub mod index;
+pub use pod_config as config;
+pub mod identity;
+pub mod signer;
+pub mod governance;
+pub mod rpc;
+pub mod explorer;
+pub mod node;
+pub mod block;
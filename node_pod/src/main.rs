@@ -1,33 +1,30 @@
 use rdkafka::consumer::{Consumer, StreamConsumer};
 use rdkafka::producer::BaseProducer;
 use rdkafka::ClientConfig;
-use simulate::User;
+use node_pod::simulate::User;
 use std::time::Duration;
 use log::{error, info};
 use rdkafka::Message;
 use futures_util::stream::StreamExt;
 use tokio::time::{timeout, Instant};
-use crate::definitions::node_header::{ConcensusMetrics, PoolingMetrics, Node, NodeMethods};
+use node_pod::definitions::node_header::{BlockPolicy, ConcensusMetrics, PoolingMetrics, Node, NodeMethods};
 use std::sync::Arc;
-use tokio::fs;
+use node_pod::config;
 use serde::{Serialize as SerdeSerialize, Deserialize};
-use crate::definitions::transaction_header::Transaction;
+use node_pod::definitions::transaction_header::Transaction;
 use bls_signatures::{PublicKey, Serialize};
 use std::collections::HashMap;
-use crate::definitions::consensus_header::{PoS, Validator};
+use node_pod::definitions::consensus_header::{PoS, Validator, ValidatorMethods, ValidatorSet, ValidatorSetMethods};
+use node_pod::consensus::epoch_of;
+use node_pod::definitions::block_header::{BlockChain, BlockChainMethods};
+use node_pod::definitions::state_header::{Account, SparseMerkleTree, StateMethods};
+use node_pod::definitions::signer_header::{FileSigner, FileSignerMethods, RemoteSigner, RemoteSignerMethods, Signer, SignerError};
+use node_pod::definitions::rpc_header::{RpcMethods, RpcState, SharedRpcState};
+use tokio::sync::{mpsc, RwLock};
 use csv::Writer;
 ///     WORK ON THE STEPS THAT WILL BE DONE BY THE NODES
 ///     IN PARTICULAR HOW THEY WILL LISTEN FOR VALIDATORS TO UPDATE THE VALIDATORS LIST AND PRIMARIES
 ///     ALSO HOW THEY WILL LISTEN FOR USERS TO GET USER PUB KEYS
-mod consensus;
-mod network;
-mod tests;
-mod definitions;
-pub mod transaction;
-pub mod simulate;
-pub mod merkle_tree;
-pub mod node;
-pub mod block;
 
 #[derive(SerdeSerialize, Deserialize, Clone)]
 
@@ -52,14 +49,53 @@ pub struct ProducerConfig {
 pub struct PerformanceConfig {
     pub tx_time: u64,
     pub timeout: u64,
-    pub block_size: usize
+    pub block_size: usize,
+    pub block_bytes: usize,
+    pub block_wait: u64,
+    /// Blocks agreed by each validator set before the next one is selected
+    #[serde(default = "default_epoch_length")]
+    pub epoch_length: u64
+}
+
+fn default_epoch_length() -> u64 {
+    1
+}
+
+#[derive(SerdeSerialize, Deserialize, Clone)]
+pub struct RpcConfig {
+    pub address: String
+}
+
+#[derive(SerdeSerialize, Deserialize, Clone)]
+pub struct IdentityConfig {
+    pub key_file: String,
+    pub passphrase_env: String,
+    #[serde(default)]
+    pub remote_signer: Option<String>
+}
+
+impl Default for IdentityConfig {
+    fn default() -> Self {
+        IdentityConfig { key_file: String::from("identity/node.key"), passphrase_env: String::from("NODE_KEY_PASSPHRASE"),
+            remote_signer: None }
+    }
+}
+
+impl IdentityConfig {
+    // An unset or empty variable leaves the key file unsealed
+    pub fn passphrase(&self) -> Option<String> {
+        std::env::var(&self.passphrase_env).ok().filter(|passphrase| !passphrase.is_empty())
+    }
 }
 
 #[derive(SerdeSerialize, Deserialize, Clone)]
 pub struct Config{
     pub consumer: ConsumerConfig,
     pub producer: ProducerConfig,
-    pub performance: PerformanceConfig
+    pub performance: PerformanceConfig,
+    pub rpc: RpcConfig,
+    #[serde(default)]
+    pub identity: IdentityConfig
 }
 
 pub async fn listen_user(consumer: &StreamConsumer, time_out: &u64) -> Vec<User> {
@@ -151,14 +187,80 @@ pub async fn listen_validators(consumer: &StreamConsumer, time_out: &u64) -> Vec
     validators
 }
 
-pub async fn load_config() -> Option<Config> {
-    let path = "src/config.yaml";
+/// Waits for the validator set of `epoch`, skipping sets of other epochs and sets whose hash does not match
+pub async fn listen_validator_set(consumer: &StreamConsumer, time_out: &u64, epoch: u64) -> ValidatorSet {
+    info!("Listening for the validator set of epoch {}", epoch);
 
-    let config_content = fs::read_to_string(&path).await.expect("Failed to read file");
+    let mut msg_stream = consumer.stream();
 
-    let config =serde_yaml::from_str(&config_content).expect("Failed to parse yaml file");
+    loop {
+        match timeout(Duration::from_millis(*time_out), msg_stream.next()).await {
+        Ok(Some(message_result)) => {
+            match message_result {
+                Err(e) => eprintln!("Error while receiving message: {}", e),
+                Ok(message) => {
+                    if let Err(e) = consumer.commit_message(&message, rdkafka::consumer::CommitMode::Sync) {
+                        eprintln!("Failed to commit message: {}", e);
+                    }
+                    let set = message.payload()
+                        .and_then(|payload| std::str::from_utf8(payload).ok())
+                        .and_then(<ValidatorSet as ValidatorSetMethods>::deserialize);
+                    match set {
+                        Some(set) if set.epoch == epoch => { return set; }
+                        Some(set) => info!("Skipped the validator set of epoch {}", set.epoch),
+                        None => eprintln!("Failed to deserialize the validator set")
+                    }
+                }
+            }
+        }
+        Ok(_) => {
+            panic!["stream ended"];
+        }
+        Err(_) => {
+            continue;
+        }
+        }
+    }
+}
+
+impl Config {
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
+
+        if self.consumer.server.is_empty() || self.producer.server.is_empty() {
+            problems.push(String::from("consumer.server and producer.server must name the Kafka bootstrap servers"));
+        }
+        if self.performance.tx_time == 0 || self.performance.timeout == 0 {
+            problems.push(String::from("performance.tx_time and performance.timeout must be at least 1 millisecond"));
+        }
+        if self.performance.block_size == 0 {
+            problems.push(String::from("performance.block_size must allow at least 1 transaction per block"));
+        }
+        if self.performance.block_bytes == 0 {
+            problems.push(String::from("performance.block_bytes must be at least 1"));
+        }
+        if self.performance.block_wait == 0 {
+            problems.push(String::from("performance.block_wait must be at least 1 millisecond"));
+        }
+        if self.performance.epoch_length == 0 {
+            problems.push(String::from("performance.epoch_length must be at least 1 block"));
+        }
+        if self.rpc.address.parse::<std::net::SocketAddr>().is_err() {
+            problems.push(format!("rpc.address {} is not an ip:port address", self.rpc.address));
+        }
+        match &self.identity.remote_signer {
+            Some(socket) if socket.is_empty() => problems.push(String::from("identity.remote_signer must be a socket path when set")),
+            None if self.identity.key_file.is_empty() => problems.push(String::from("identity.key_file must be set without a remote signer")),
+            _ => {}
+        }
+        problems
+    }
+}
 
-    config
+pub fn load_config(path: &str) -> Result<Config, String> {
+    let config: Config = config::load(path, "NODE", &[("BOOTSTRAP_SERVERS", &["consumer.server", "producer.server"])])?;
+    let problems = config.validate();
+    config::check(config, path, problems)
 }
 
 #[derive(SerdeSerialize)]
@@ -181,9 +283,40 @@ pub struct Record {
 
 #[tokio::main]
 async fn main() {
-    let mut node = Node::new();
+    let (config_flag, args) = config::parse_args(std::env::args().skip(1));
+    let config_path = config::resolve_path(config_flag, "NODE_CONFIG");
+    let config = match load_config(&config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let signer: Result<Arc<dyn Signer>, SignerError> = match &config.identity.remote_signer {
+        Some(socket) => RemoteSigner::connect(socket).map(|signer| Arc::new(signer) as Arc<dyn Signer>),
+        None => FileSigner::open(&config.identity.key_file, config.identity.passphrase().as_deref())
+            .map(|signer| Arc::new(signer) as Arc<dyn Signer>)
+    };
+    let mut node = match signer {
+        Ok(signer) => Node::from_signer(signer),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if args.first().map(String::as_str) == Some("identity") {
+        println!("Node id: {}", node.id);
+        println!("Public key: {}", hex::encode(node.signer.public_key().as_bytes()));
+        match &config.identity.remote_signer {
+            Some(socket) => println!("Remote signer: {}", socket),
+            None => println!("Key file: {}", config.identity.key_file)
+        }
+        return;
+    }
 
-    let config = load_config().await.unwrap();
+    println!("Effective config from {}:\n{}", config_path, config::effective(&config));
 
     let mut wtr: Writer<_> = Writer::from_path("data.csv").expect("can not find file path");
     // wtr.write_record(&["pool_tps", "pool_process_time", "failed_transactions", "ttf", "preprepare_time",
@@ -215,16 +348,22 @@ async fn main() {
 
     let users: Vec<User> = listen_user(&user_consumer, &config.performance.timeout).await;
 
-    let mut user_base: HashMap<String, f64> = HashMap::new();
-
-    for val in users.clone() {
-        user_base.insert(val.user_id, val.balance);
-    }
+    let balances: HashMap<String, f64> = users.iter().map(|user| (user.user_id.clone(), user.balance)).collect();
+    let mut user_base: HashMap<String, Account> = balances.iter()
+        .map(|(id, balance)| (id.clone(), Account { balance: *balance, nonce: 0 }))
+        .collect();
 
     if users.len() < 100 {
         panic!["Not all users fetched"];
     }
 
+    *node.block_chain.write().expect("Chain lock poisoned") = BlockChain::from_genesis_state(SparseMerkleTree::from_balances(&balances));
+
+    let (submissions, submission_queue) = mpsc::unbounded_channel();
+    let rpc_state: SharedRpcState = Arc::new(RwLock::new(RpcState::new(&node, submissions)));
+
+    tokio::spawn(node_pod::rpc::serve(Arc::clone(&rpc_state), config.rpc.address.clone()));
+
     let val_consumer: StreamConsumer = ClientConfig::new()
     .set("bootstrap.servers", &config.consumer.server)
     .set("group.id", &(node.id.clone()+"validator"))
@@ -340,6 +479,19 @@ async fn main() {
             .create()
             .expect("Failed to create producer"));
 
+    let submit_producer: Arc<BaseProducer> = Arc::new(ClientConfig::new()
+            .set("bootstrap.servers", &config.producer.server)
+            .set("group.id", &node.id)
+            .set("enable.auto.commit",&config.producer.autocommit)
+            .set("linger.ms", &config.producer.lingerms)
+            .set("batch.size", &config.producer.batchsize)
+            .set("compression.type", &config.producer.compressiontype)
+            .set("acks", &config.producer.acks)
+            .create()
+            .expect("Failed to create producer"));
+
+    tokio::spawn(node_pod::rpc::forward_submissions(submission_queue, submit_producer));
+
     tokio::time::sleep(Duration::from_secs(1)).await;
 
     // POOL LOGIC GOES HERE
@@ -359,58 +511,82 @@ async fn main() {
     // CONCENSUS LOGIC GOES HERE
 
     let mut resid: Vec<Transaction> = vec![];
-    let mut pool: Option<Vec<Transaction>>; 
-    let mut pool_metrics: Option<PoolingMetrics>;
+    let mut pool: Vec<Transaction>;
+    let mut pool_metrics: PoolingMetrics;
     let mut concensus_metrics: Option<ConcensusMetrics>;
     loop {
         info!("Entering block creation round");
 
+        // Parameters set by governance on the committed chain take over from the config
+        let (parameters, height) = {
+            let chain = node.block_chain.read().expect("Chain lock poisoned");
+            (chain.governance().parameters.clone(), chain.headers.len() as u64)
+        };
+        let policy = BlockPolicy {
+            max_size: parameters.block_size.unwrap_or(config.performance.block_size),
+            max_bytes: config.performance.block_bytes,
+            max_wait: config.performance.block_wait,
+            min_fee: parameters.min_fee.unwrap_or(0.0)
+        };
+        let tx_time = parameters.tx_time.unwrap_or(config.performance.tx_time);
+        let time_out = parameters.timeout.unwrap_or(config.performance.timeout);
+
         let start1 = Instant::now();
 
-        let (_, validators, primary) = tokio::join!(
-            node.propose_stake(&stake_producer),
-            listen_validators(&val_consumer, &config.performance.timeout),
-            listen_validators(&primary_consumer, &config.performance.timeout)
-        );
+        // Stakes are only sent when an epoch starts, its committee and primary then agree every block of it
+        let epoch = epoch_of(height, config.performance.epoch_length);
+        if node.validator_set.validators.is_empty() || node.validator_set.epoch != epoch {
+            let (_, validator_set, primary) = tokio::join!(
+                node.propose_stake(&stake_producer, epoch),
+                listen_validator_set(&val_consumer, &time_out, epoch),
+                listen_validators(&primary_consumer, &time_out)
+            );
 
-        node.validators = validators;
+            node.validator_set = validator_set;
 
-        match node.validators.is_empty() {
-            true => error!("Did not receive validators"),
-            false => info!("Received validators")
-        };
+            info!("Received the {} validators of epoch {}", node.validator_set.validators.len(), epoch);
 
-        node.primary = primary;
+            node.primary = primary;
 
-        match node.primary.is_empty() {
-            true => error!("Did not select primary"),
-            false => info!("Selected primary")
-        };
+            match node.primary.is_empty() {
+                true => error!("Did not select primary"),
+                false => info!("Selected primary")
+            };
+        }
 
         let mut pkey_store: HashMap<String, PublicKey> = HashMap::new();
 
-        for val in node.validators.clone() {
+        // A key only enters the store with a proof that its holder staked it, keeping out rogue keys
+        for val in node.validator_set.validators.clone() {
+            if !val.verify_possession() {
+                error!("Left {} out of the key store, its proof of possession does not verify", val.node_id);
+                continue;
+            }
             pkey_store.insert(val.node_id, PublicKey::from_bytes(hex::decode(val.public_key).unwrap().as_slice()).unwrap());
         }
 
         info!("Created validator public key store");
 
+        rpc_state.write().await.publish(&node, &resid);
+
         let end1 = start1.elapsed().as_millis() as f64;
 
         info!("Started pooling the transactions");
 
-        (pool, resid, pool_metrics) = node.pool_transactions(&tx_consumer, &mut user_base, 
-        &mut resid,config.performance.timeout, config.performance.tx_time,
-        &config.performance.block_size).await;
+        (pool, resid, pool_metrics) = node.pool_transactions(&tx_consumer, &mut user_base,
+        &mut resid, tx_time, &policy).await;
 
-        match pool.is_none() {
-            true => error!("Did not receive transactions"),
-            false => info!("Received transactions")
+        match pool.is_empty() {
+            true => info!("No transactions received, proposing a heartbeat block"),
+            false => info!("Received {} transactions", pool.len())
         };
 
         info!("Finished pooling the transactions");
 
-        let pool_perf = pool_metrics.unwrap();
+        rpc_state.write().await.publish(&node, &resid);
+
+        let pool_perf = pool_metrics;
+        let block_len = pool.len();
 
         let prepre_con_clone= Arc::clone(&prepre_consumer);
         let pre_con_clone= Arc::clone(&pre_consumer);
@@ -422,23 +598,25 @@ async fn main() {
 
         info!("Started concensus protocol");
 
-        match pool {
-            Option::None => {panic!["No pool continuing"]},
-            Some(tx_pool) =>  {concensus_metrics = node.concensus(tx_pool, pkey_store,
+        concensus_metrics = node.concensus(pool, pkey_store,
             prepre_con_clone, pre_con_clone, &prepre_ready, &pre_ready, &comm_ready,
-        prepre_prod_clone, pre_prod_clone, comm_prod_clone,config.performance.timeout).await}
-        };
+        prepre_prod_clone, pre_prod_clone, comm_prod_clone, time_out).await;
 
         info!("Finished concensus protocol");
 
-        let concensus_perf = concensus_metrics.unwrap();
+        rpc_state.write().await.publish(&node, &resid);
+
+        let concensus_perf = match concensus_metrics {
+            Some(concensus_perf) => concensus_perf,
+            None => { continue; }
+        };
         let end = start.elapsed().as_millis() as f64;
         // println!("Block {} is out!", node.msg_idx[0]);
         let end_total = start1.elapsed().as_millis() as f64;
         let concensus_total = end1 + end;
         let ttf = end + pool_perf.ttf;
 
-        let block_tps = 1000.0 * ((config.performance.block_size as f64) / (end + end1));
+        let block_tps = 1000.0 * ((block_len as f64) / (end + end1));
 
         let record = Record {
             pool_tps: pool_perf.tps,
@@ -1,8 +1,77 @@
 use openssl::sha::Sha256;
-use std::ops::Deref;
-use crate::definitions::merkle_header::{MerkleTree, Proof, MerkleMethods};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use crate::definitions::merkle_header::{MerkleTree, MerkleScheme, MultiProof, Proof, ProofError, MerkleMethods};
 use crate::definitions::transaction_header::Transaction;
 
+// The tree is stored level by level in `nodes`, leaves first and the root last.
+// How an odd node at the end of a level is handled depends on the scheme, see MerkleScheme.
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+impl std::fmt::Display for ProofError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ProofError::InvalidId(id) => write!(f, "Transaction id {} is not hex encoded", id),
+            ProofError::NotInTree(id) => write!(f, "Transaction {} is not in the tree", id),
+            ProofError::InvalidRoot => write!(f, "Merkle root is not hex encoded"),
+        }
+    }
+}
+
+// A transaction listed twice is proven and checked once
+fn distinct(transactions: &[Transaction]) -> Vec<&Transaction> {
+    let mut ids = HashSet::new();
+    transactions.iter().filter(|transaction| ids.insert(&transaction.id)).collect()
+}
+
+fn hash_pair(left: &[u8], right: &[u8], scheme: MerkleScheme) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    if scheme == MerkleScheme::Tagged {
+        hasher.update(&[NODE_PREFIX]);
+    }
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finish().to_vec()
+}
+
+fn hash_leaf(id: &[u8], scheme: MerkleScheme) -> Vec<u8> {
+    match scheme {
+        MerkleScheme::Legacy => id.to_vec(),
+        MerkleScheme::Tagged => {
+            let mut hasher = Sha256::new();
+            hasher.update(&[LEAF_PREFIX]);
+            hasher.update(id);
+            hasher.finish().to_vec()
+        }
+    }
+}
+
+fn next_level(level: &[Vec<u8>], scheme: MerkleScheme) -> Vec<Vec<u8>> {
+    level.chunks(2).map(|pair| match (pair.get(1), scheme) {
+        (Some(right), _) => hash_pair(&pair[0], right, scheme),
+        (None, MerkleScheme::Legacy) => hash_pair(&pair[0], &pair[0], scheme),
+        (None, MerkleScheme::Tagged) => pair[0].clone()
+    }).collect()
+}
+
+fn level_sizes(leaf_count: usize) -> Vec<usize> {
+    let mut sizes = vec![leaf_count];
+    let mut size = leaf_count;
+
+    while size > 1 {
+        size = size.div_ceil(2);
+        sizes.push(size);
+    }
+    sizes
+}
+
+fn leaf_hashes(data: &[Transaction], scheme: MerkleScheme) -> Vec<Vec<u8>> {
+    data.iter().map(|x| -> Vec<u8> {
+        hash_leaf(&hex::decode(&x.id).expect("Failed to decode transaction id"), scheme)
+    }).collect()
+}
+
 impl MerkleMethods for MerkleTree {
 
     fn new_genesis() -> Self {
@@ -10,153 +79,191 @@ impl MerkleMethods for MerkleTree {
             merkle_root: Vec::new(),
             leaves: Vec::new(),
             nodes: Vec::new(),
-            depth: 0
+            depth: 0,
+            scheme: MerkleScheme::Tagged
         }
     }
 
-    fn new(data: &[Transaction]) -> Self {
-        let leaves: Vec<Vec<u8>> = data.iter().map(|x| -> Vec<u8> { hex::decode(x.id.clone()).unwrap() }).collect();
+    fn new(data: &[Transaction], scheme: MerkleScheme) -> Self {
+        if data.is_empty() {
+            return MerkleTree { scheme, ..MerkleTree::new_genesis() };
+        }
 
-        let mut nodes = Vec::new();
+        let leaves = leaf_hashes(data, scheme);
 
+        let mut nodes = leaves.clone();
         let mut current_level = leaves.clone();
-        let mut next_level = Vec::new();
-        let mut levels = 1;
-        let current_level_it = current_level.clone();
 
-        for leaf in current_level_it {
-            nodes.push(leaf);
-        }
-        
         while current_level.len() > 1 {
-            next_level.clear();
-            for i in (0..current_level.len()).step_by(2) {
-                let left = &current_level[i];
-                let right = if i + 1 < current_level.len() {
-                    &current_level[i + 1]
-                } else {
-                    left
-                };
+            current_level = next_level(&current_level, scheme);
+            nodes.extend(current_level.iter().cloned());
+        }
 
-                let mut hasher = Sha256::new();
-                hasher.update(left);
-                hasher.update(right);
-                let hash = hasher.finish();
+        let merkle_root = current_level[0].clone();
+        let depth = level_sizes(leaves.len()).len() as u32;
 
+        MerkleTree { merkle_root, leaves, nodes, depth, scheme }
+    }
 
-                next_level.push(hash.clone().to_vec());
-                nodes.push(hash.to_vec());
+    fn generate_proof(&self, target: &Transaction) -> Proof {
+        let target_id = hex::decode(&target.id).expect("Failed to decode transaction id");
+        let target_hash = hash_leaf(&target_id, self.scheme);
+        let leaf_index = self.leaves.iter().position(|x| *x == target_hash)
+                                        .expect("Failed to retrieve current_index");
+
+        let mut path: Vec<Vec<u8>> = Vec::new();
+        let mut right_sibling: Vec<bool> = Vec::new();
+
+        let mut index = leaf_index;
+        let mut offset = 0;
+
+        for size in level_sizes(self.leaves.len()) {
+            if size == 1 { break; }
+
+            let is_last_odd = index % 2 == 0 && index + 1 == size;
+
+            match (is_last_odd, self.scheme) {
+                (true, MerkleScheme::Tagged) => {},
+                (true, MerkleScheme::Legacy) => {
+                    path.push(self.nodes[offset + index].clone());
+                    right_sibling.push(true);
+                }
+                (false, _) => {
+                    let sibling = index ^ 1;
+                    path.push(self.nodes[offset + sibling].clone());
+                    right_sibling.push(index % 2 == 0);
+                }
             }
 
-            current_level = next_level.clone();
-            levels += 1;
+            offset += size;
+            index /= 2;
         }
 
-        let merkle_root = current_level.first().cloned().expect("Failed to extract Merkle Root");
-        MerkleTree { merkle_root, leaves, nodes, depth: levels }
+        Proof { path, right_sibling, leaf_index }
     }
 
-    fn generate_proof(&self, target: &Transaction) -> Proof {
-        let mut path: Vec<Vec<u8>> = Vec::new();
-        let mut current_index = self.leaves.iter().position(|x| { x.deref() == hex::decode(target.id.clone()).unwrap() })
-                                        .expect("Failed to retrieve current_index");
-        let mut level_size = self.leaves.len();
-        let leaf_index = current_index.clone();
+    fn generate_root(data: &[Transaction], scheme: MerkleScheme) -> Vec<u8> {
+        // An empty block (heartbeat) commits to an empty root like the genesis block
+        MerkleTree::new(data, scheme).merkle_root
+    }
 
-        match current_index % 2 {
-            0 => { path.push(self.nodes[current_index].clone()); path.push(self.nodes[current_index + 1].clone()) },
-            _ => { path.push(self.nodes[current_index - 1].clone()); path.push(self.nodes[current_index].clone()) }
+    fn validate_proof(proof: &Proof, leaf: Transaction, merkle_root: &[u8], scheme: MerkleScheme) -> bool {
+        if proof.path.len() != proof.right_sibling.len() {
+            return false;
+        }
+
+        let mut hash = match hex::decode(leaf.id) {
+            Ok(id) => hash_leaf(&id, scheme),
+            Err(_) => { return false; }
         };
 
-        while level_size > 2 {
-            let sibling_index = match current_index % 2 {
-                0 => current_index + 1,
-                _ => current_index - 1
-            };
+        for (sibling, is_right) in proof.path.iter().zip(&proof.right_sibling) {
+            if scheme == MerkleScheme::Tagged && sibling.len() != 32 {
+                return false;
+            }
 
-            let hash: Vec<u8>;
-            match current_index % 2 {
-                0 => {
-                    let mut hasher = Sha256::new();
-                    hasher.update([self.nodes[current_index].clone(), self.nodes[sibling_index].clone()].concat().as_slice());
-                    hash = hasher.finish().to_vec();
-                }
-                _ => {
-                    let mut hasher = Sha256::new();
-                    hasher.update([self.nodes[sibling_index].clone(), self.nodes[current_index].clone()].concat().as_slice());
-                    hash = hasher.finish().to_vec();
-                }
+            hash = match is_right {
+                true => hash_pair(&hash, sibling, scheme),
+                false => hash_pair(sibling, &hash, scheme)
             };
-            current_index += level_size;
-            match current_index % 2 {
-                0 => { path.push(hash); path.push(self.nodes[current_index + 1].clone()); },
-                _ => { path.push(self.nodes[current_index - 1].clone()); path.push(hash); }
-            }
-            level_size /= 2;
         }
 
-        Proof { path, leaf_index }
+        hash == *merkle_root
     }
 
-    fn generate_root(data: &[Transaction]) -> Vec<u8> {
-        let transactions: Vec<Vec<u8>> = data.iter().map(|x| -> Vec<u8> {hex::decode(x.id.clone()).unwrap()}).collect();
+    fn generate_multi_proof(&self, targets: &[Transaction]) -> Result<MultiProof, ProofError> {
+        let leaf_indices = distinct(targets).into_iter().map(|target| {
+            let target_id = hex::decode(&target.id).map_err(|_| ProofError::InvalidId(target.id.clone()))?;
+            let target_hash = hash_leaf(&target_id, self.scheme);
+            self.leaves.iter().position(|x| *x == target_hash).ok_or_else(|| ProofError::NotInTree(target.id.clone()))
+        }).collect::<Result<Vec<usize>, ProofError>>()?;
 
-        let mut current_level = transactions.clone();
-        let mut next_level = Vec::new();
+        let mut hashes: Vec<Vec<u8>> = Vec::new();
+        let mut known: BTreeSet<usize> = leaf_indices.iter().cloned().collect();
+        let mut offset = 0;
 
-        while current_level.len() > 1 {
-            next_level.clear();
-            for i in (0..current_level.len()).step_by(2) {
-                let current_node = &current_level[i];
-                let sibling_node = if i + 1 < current_level.len() {
-                    &current_level[i + 1]
-                } else {
-                    current_node
-                };
-                let mut combined = current_node.clone();
-                combined.append(sibling_node.clone().as_mut());
-                let mut hasher = Sha256::new();
-                hasher.update(&combined);
-                let hash = hasher.finish().to_vec();
+        for size in level_sizes(self.leaves.len()) {
+            if size == 1 { break; }
 
-                next_level.push(hash)
+            let mut parents = BTreeSet::new();
+
+            for &index in &known {
+                // The left sibling already accounted for this pair
+                if !parents.insert(index / 2) { continue; }
+
+                let is_last_odd = index % 2 == 0 && index + 1 == size;
+                if !is_last_odd && !known.contains(&(index ^ 1)) {
+                    hashes.push(self.nodes[offset + (index ^ 1)].clone());
+                }
             }
-            current_level = next_level.clone();
+
+            offset += size;
+            known = parents;
         }
-        current_level.first().expect("Failed to extract the Merkle Root").deref().to_vec()
+
+        Ok(MultiProof { leaf_indices, leaf_count: self.leaves.len(), hashes })
     }
 
-    fn validate_proof(proof: &Proof, leaf: Transaction, merkle_root: &[u8]) -> bool {
-        let mut sibling;
-        let mut hash = hex::decode(leaf.id).unwrap();
+    fn validate_multi_proof(proof: &MultiProof, leaves: &[Transaction], merkle_root: &[u8], scheme: MerkleScheme) -> bool {
+        let leaves = distinct(leaves);
+        if leaves.is_empty() || proof.leaf_indices.len() != leaves.len() {
+            return false;
+        }
+
+        let mut known: BTreeMap<usize, Vec<u8>> = BTreeMap::new();
 
-        for _ in 0..proof.path.len() / 2 {
-            let idx = proof.path.iter().position(|x| *x == hash);
-            let idx = match idx {
-                Some(val) => val,
-                None => { return false; }
+        for (index, leaf) in proof.leaf_indices.iter().zip(leaves) {
+            let id = match hex::decode(&leaf.id) {
+                Ok(id) => id,
+                Err(_) => { return false; }
             };
 
-            let mut hasher = Sha256::new();
-            
-            if idx % 2 == 0 {
-                sibling = proof.path[&idx + 1].clone();
+            if *index >= proof.leaf_count || known.insert(*index, hash_leaf(&id, scheme)).is_some() {
+                return false;
+            }
+        }
+
+        let mut hashes = proof.hashes.iter();
+
+        for size in level_sizes(proof.leaf_count) {
+            if size == 1 { break; }
+
+            let mut parents: BTreeMap<usize, Vec<u8>> = BTreeMap::new();
+
+            for (&index, hash) in &known {
+                if parents.contains_key(&(index / 2)) { continue; }
 
-                hasher.update(&hash);
-                hasher.update(&sibling);
-            } else {
-                sibling = proof.path[&idx - 1].clone();
+                let is_last_odd = index % 2 == 0 && index + 1 == size;
 
-                hasher.update(&sibling);
-                hasher.update(&hash);
+                let parent = match (is_last_odd, scheme) {
+                    (true, MerkleScheme::Tagged) => hash.clone(),
+                    (true, MerkleScheme::Legacy) => hash_pair(hash, hash, scheme),
+                    (false, _) => {
+                        let sibling = match known.get(&(index ^ 1)) {
+                            Some(sibling) => sibling,
+                            None => match hashes.next() {
+                                Some(sibling) => sibling,
+                                None => { return false; }
+                            }
+                        };
+
+                        if scheme == MerkleScheme::Tagged && sibling.len() != 32 {
+                            return false;
+                        }
+
+                        match index % 2 {
+                            0 => hash_pair(hash, sibling, scheme),
+                            _ => hash_pair(sibling, hash, scheme)
+                        }
+                    }
+                };
+
+                parents.insert(index / 2, parent);
             }
 
-            hash = hasher.finish().to_vec();
+            known = parents;
         }
-        
-        hash == *merkle_root
+
+        hashes.next().is_none() && known.get(&0).is_some_and(|root| root == merkle_root)
     }
 }
-
-
-
@@ -0,0 +1,149 @@
+use openssl::sha::Sha256;
+use crate::definitions::mmr_header::{MerkleMountainRange, MmrProof, MmrMethods};
+
+// Leaves and internal nodes are domain separated the same way as the tagged Merkle tree.
+// Peaks are bagged from right to left into a single root.
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+fn hash_leaf(leaf: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(&[LEAF_PREFIX]);
+    hasher.update(leaf);
+    hasher.finish().to_vec()
+}
+
+fn hash_pair(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(&[NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finish().to_vec()
+}
+
+fn bag_peaks(peaks: &[Vec<u8>]) -> Vec<u8> {
+    match peaks.split_last() {
+        Some((last, rest)) => rest.iter().rev().fold(last.clone(), |acc, peak| hash_pair(peak, &acc)),
+        None => Vec::new()
+    }
+}
+
+/// Heights and node offsets of the perfect subtrees making up a range of `leaf_count` leaves
+fn peak_layout(leaf_count: usize) -> Vec<(u32, usize)> {
+    let mut layout = Vec::new();
+    let mut offset: usize = 0;
+
+    for height in (0..usize::BITS).rev() {
+        if leaf_count & (1 << height) != 0 {
+            layout.push((height, offset));
+            // A peak of height h spans 2^(h+1) - 1 nodes, written so the top height does not overflow.
+            // Offsets only saturate for counts no range holds, as a proof may claim.
+            offset = offset.saturating_add(usize::MAX >> (usize::BITS - 1 - height));
+        }
+    }
+    layout
+}
+
+/// Finds the peak holding `leaf_index` and the leaf's index inside it
+fn locate(leaf_index: usize, leaf_count: usize) -> Option<(usize, u32, usize, usize)> {
+    let mut first_leaf = 0;
+
+    for (peak, (height, offset)) in peak_layout(leaf_count).into_iter().enumerate() {
+        let leaves = 1 << height;
+        if leaf_index < first_leaf + leaves {
+            return Some((peak, height, offset, leaf_index - first_leaf));
+        }
+        first_leaf += leaves;
+    }
+    None
+}
+
+impl MmrMethods for MerkleMountainRange {
+
+    fn new() -> Self {
+        MerkleMountainRange { leaf_count: 0, nodes: Vec::new() }
+    }
+
+    fn append(&mut self, leaf: &[u8]) {
+        self.nodes.push(hash_leaf(leaf));
+
+        // Every trailing one bit of the old count is a peak of equal height to merge with
+        for height in 0..self.leaf_count.trailing_ones() {
+            let right = self.nodes.len() - 1;
+            let left = right - ((1 << (height + 1)) - 1);
+            let parent = hash_pair(&self.nodes[left], &self.nodes[right]);
+            self.nodes.push(parent);
+        }
+
+        self.leaf_count += 1;
+    }
+
+    fn root(&self) -> Vec<u8> {
+        self.root_at(self.leaf_count)
+    }
+
+    fn root_at(&self, leaf_count: usize) -> Vec<u8> {
+        if leaf_count > self.leaf_count {
+            return Vec::new();
+        }
+
+        let peaks: Vec<Vec<u8>> = peak_layout(leaf_count).iter()
+            .map(|(height, offset)| self.nodes[offset + (1 << (height + 1)) - 2].clone())
+            .collect();
+
+        bag_peaks(&peaks)
+    }
+
+    fn generate_proof(&self, leaf_index: usize, leaf_count: usize) -> Option<MmrProof> {
+        if leaf_count > self.leaf_count {
+            return None;
+        }
+
+        let (_, height, offset, local_index) = locate(leaf_index, leaf_count)?;
+
+        let mut path = Vec::new();
+        let mut offset = offset;
+
+        // Walk down from the peak, the left subtree of a height h node spans 2^h - 1 nodes
+        for level in (1..=height).rev() {
+            let subtree = (1 << level) - 1;
+            match local_index & (1 << (level - 1)) {
+                0 => path.push(self.nodes[offset + 2 * subtree - 1].clone()),
+                _ => {
+                    path.push(self.nodes[offset + subtree - 1].clone());
+                    offset += subtree;
+                }
+            }
+        }
+        path.reverse();
+
+        let peaks = peak_layout(leaf_count).iter()
+            .map(|(height, offset)| self.nodes[offset + (1 << (height + 1)) - 2].clone())
+            .collect();
+
+        Some(MmrProof { leaf_index, leaf_count, path, peaks })
+    }
+
+    fn validate_proof(proof: &MmrProof, leaf: &[u8], root: &[u8]) -> bool {
+        let (peak, height, _, local_index) = match locate(proof.leaf_index, proof.leaf_count) {
+            Some(location) => location,
+            None => { return false; }
+        };
+
+        if proof.path.len() != height as usize || proof.peaks.len() != proof.leaf_count.count_ones() as usize {
+            return false;
+        }
+
+        let mut hash = hash_leaf(leaf);
+
+        for (level, sibling) in proof.path.iter().enumerate() {
+            hash = match local_index & (1 << level) {
+                0 => hash_pair(&hash, sibling),
+                _ => hash_pair(sibling, &hash)
+            };
+        }
+
+        hash == proof.peaks[peak] && bag_peaks(&proof.peaks) == root
+    }
+}
@@ -5,8 +5,9 @@ use rdkafka::message::Message;
 use tokio::time::timeout;
 use futures_util::StreamExt;
 use std::time::{Duration, Instant};
-use crate::definitions::network_header::{MessageTypeMethods, NodeMessage, NodeMessageMethods, MessageType, Network};
+use crate::definitions::network_header::{ClientReply, ClientReplyMethods, MessageTypeMethods, NodeMessage, NodeMessageMethods, MessageType, Network, ReplyBody};
 
+use crate::definitions::signer_header::{Phase, PhaseMethods, SignerError};
 use crate::definitions::{block_header::{Block, BlockMethods}, node_header::{Node, NodeMethods}};
 
 impl MessageTypeMethods for MessageType {
@@ -20,19 +21,33 @@ impl MessageTypeMethods for MessageType {
         
         val
     }
+
+    fn phase(&self) -> Phase {
+        match self {
+            MessageType::PrePrepare(_) => Phase::PrePrepare,
+            MessageType::Prepare(_) => Phase::Prepare,
+            MessageType::Commit(_) => Phase::Commit,
+            MessageType::Reply(_) => Phase::Reply,
+        }
+    }
+
+    fn block(&self) -> Option<Block> {
+        let signed = self.unwrap();
+        serde_json::from_str(self.phase().body(&signed)?).ok()
+    }
 }
 
 impl NodeMessageMethods for NodeMessage {
-    fn new(node: &Node, block: &Block, msg_type: String, idx: usize) -> Self {
-       let sign = node.sign_message(block);
-       let msg = match msg_type.to_uppercase().as_str() {
-                        "PREPREPARE" => {MessageType::PrePrepare(block.serialize_block())},
-                        "PREPARE" => {MessageType::Prepare(block.serialize_block())},
-                        "COMMIT" => {MessageType::Commit(block.serialize_block())},
-                        "REPLY" => {MessageType::Reply(block.serialize_block())},
+    fn new(node: &Node, block: &Block, msg_type: String, idx: usize) -> Result<Self, SignerError> {
+       let (msg, phase) = match msg_type.to_uppercase().as_str() {
+                        "PREPREPARE" => {(MessageType::PrePrepare(Phase::PrePrepare.message(&block.serialize_block())), Phase::PrePrepare)},
+                        "PREPARE" => {(MessageType::Prepare(Phase::Prepare.message(&block.serialize_block())), Phase::Prepare)},
+                        "COMMIT" => {(MessageType::Commit(Phase::Commit.message(&block.serialize_block())), Phase::Commit)},
+                        "REPLY" => {(MessageType::Reply(Phase::Reply.message(&block.serialize_block())), Phase::Reply)},
                         _ => panic!("Invalid message type")
                 };
-        NodeMessage { msg_type: msg, block: block.clone(), signature: sign, sender_id: node.id.clone(), seq_num: idx }
+       let sign = node.sign_message(&msg.unwrap(), phase)?;
+        Ok(NodeMessage { msg_type: msg, block: block.clone(), signature: sign, sender_id: node.id.clone(), seq_num: idx })
     }
 
     fn deserialize_message(msg_json: String) -> NodeMessage {
@@ -40,14 +55,61 @@ impl NodeMessageMethods for NodeMessage {
         node_msg
     }
 
+    // The signature covers `msg_type`, the unsigned copy in `block` is not checked
     fn verify_message(self, pub_key: PublicKey) -> bool {
-        pub_key.verify(Signature::from_bytes(hex::decode(&self.signature).unwrap().as_slice())
-            .expect("Failed to parse the signature"),
-             self.block.serialize_block())
+        match hex::decode(&self.signature).ok().and_then(|bytes| Signature::from_bytes(&bytes).ok()) {
+            Some(signature) => self.msg_type.block().is_some() && pub_key.verify(signature, self.msg_type.unwrap()),
+            None => false
+        }
+    }
+}
+
+impl ClientReplyMethods for ClientReply {
+    fn new(node: &Node, block: &Block) -> Result<Self, SignerError> {
+        let body = ReplyBody {
+            view: block.header.view,
+            height: block.header.index,
+            block_hash: block.hash.clone(),
+            tx_ids: block.transactions.iter().map(|tx| tx.id.clone()).collect()
+        };
+        let message = Phase::Reply.message(&serde_json::to_string(&body).expect("Failed to serialize reply"));
+        let signature = node.sign_message(&message, Phase::Reply)?;
+
+        Ok(ClientReply { msg_type: MessageType::Reply(message), signature, sender_id: node.id.clone() })
+    }
+
+    fn body(&self) -> Option<ReplyBody> {
+        match &self.msg_type {
+            MessageType::Reply(message) => serde_json::from_str(Phase::Reply.body(message)?).ok(),
+            _ => None
+        }
+    }
+
+    fn verify(&self, pub_key: &PublicKey) -> bool {
+        let signature = match hex::decode(&self.signature).ok().and_then(|bytes| Signature::from_bytes(&bytes).ok()) {
+            Some(signature) => signature,
+            None => { return false; }
+        };
+
+        self.body().is_some() && pub_key.verify(signature, self.msg_type.unwrap())
     }
 }
 
 impl Network for Node {
+    async fn broadcast_reply(&self, reply: ClientReply, producer: &BaseProducer) {
+        let message = serde_json::to_string(&reply).expect("Failed to serialize reply");
+
+        producer.send(
+            BaseRecord::to("Reply")
+            .payload(&message)
+            .key(&self.id)
+        ).expect("Failed to send reply");
+
+        producer.flush(Duration::from_secs(2)).expect("Failed to flush");
+        info!("Reply broadcasted");
+    }
+
+
     async fn broadcast_kafka(&self, topic: &str, message: NodeMessage, producer: &BaseProducer){
         info!("Brodcasting message to topic: {}", topic);
 
@@ -1,111 +1,180 @@
-use crate::definitions::{block_header::{Block, BlockChain, BlockMethods, BlockChainMethods}, transaction_header::{Transaction, TransactionMethods}};
+use crate::definitions::{block_header::{BlockChain, BlockChainMethods}, transaction_header::{Transaction, TransactionMethods}};
 use bls_signatures::{PrivateKey, PublicKey, Serialize};
 use ring::signature::{UnparsedPublicKey, ED25519};
 use futures_util::StreamExt;
 use std::collections::HashMap;
-use log::info;
+use crate::definitions::state_header::Account;
+use log::{error, info};
 use crate::definitions::consensus_header::Pbft;
 use rdkafka::{consumer::{Consumer, StreamConsumer}, producer::BaseProducer};
 use tokio::time::{timeout, Instant};
 use crate::definitions::node_header::NodeMethods;
+use crate::definitions::consensus_header::Validator;
+use crate::definitions::governance_header::{Governance, GovernanceMethods, GovernanceTransactionMethods};
+use crate::definitions::signer_header::{LocalSigner, LocalSignerMethods, Phase, Signer, SignerError, SigningRequest};
 use crate::definitions::network_header::Network;
 use rdkafka::Message;
 use std::time::Duration;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use tokio;
-use crate::definitions::node_header::{Node, NodeState, PoolingMetrics, ConcensusMetrics};
+use crate::definitions::node_header::{Node, NodeState, BlockPolicy, PoolingMetrics, ConcensusMetrics};
 
 // use serde_json::to_string;transaction_header
 
+/// Moves transactions from the front of `residual` into `pool` until the block policy
+/// is met. Invalid transactions are dropped, a transaction that does not fit in the
+/// remaining bytes stays in `residual` for the next block. Governance transactions must
+/// come from a member of `validators` and apply to `governance`, which tracks the pool.
+/// Returns whether the pool is full and how many transactions were checked.
+pub fn fill_pool(pool: &mut Vec<Transaction>, pool_bytes: &mut usize, residual: &mut Vec<Transaction>,
+    user_base: &mut HashMap<String, Account>, governance: &mut Governance, validators: &[Validator],
+    policy: &BlockPolicy) -> (bool, f64) {
+
+    let mut full = pool.len() >= policy.max_size || *pool_bytes >= policy.max_bytes;
+    let mut consumed: usize = 0;
+
+    for transaction in residual.iter() {
+        if full { break; }
+
+        let tx_bytes = serde_json::to_vec(transaction).expect("Failed to serialize transaction").len();
+
+        if !pool.is_empty() && *pool_bytes + tx_bytes > policy.max_bytes {
+            full = true;
+            break;
+        }
+
+        consumed += 1;
+
+        if tx_bytes > policy.max_bytes { continue; }
+
+        if let Some(action) = &transaction.governance {
+            if transaction.check_governance(validators) && governance.apply_action(&transaction.from, &transaction.id, action) {
+                pool.push(transaction.to_owned());
+                *pool_bytes += tx_bytes;
+                full = pool.len() >= policy.max_size || *pool_bytes >= policy.max_bytes;
+            }
+            continue;
+        }
+
+        if transaction.fee < policy.min_fee { continue; }
+
+        let account = match user_base.get(&transaction.from) {
+            Some(account) => account.clone(),
+            None => continue
+        };
+
+        if account.balance < transaction.amount + transaction.fee { continue; }
+        if transaction.nonce.is_some_and(|nonce| nonce != account.nonce + 1) { continue; }
+
+        let pub_key_bytes = match hex::decode(&transaction.from) {
+            Ok(bytes) => bytes,
+            Err(_) => continue
+        };
+        let public_key = UnparsedPublicKey::new(&ED25519, pub_key_bytes);
+
+        if transaction.verify_transaction(public_key) {
+            user_base.insert(transaction.from.clone(), Account { balance: account.balance - transaction.amount - transaction.fee,
+                nonce: account.nonce + 1 });
+            pool.push(transaction.to_owned());
+            *pool_bytes += tx_bytes;
+            full = pool.len() >= policy.max_size || *pool_bytes >= policy.max_bytes;
+        }
+    }
+
+    residual.drain(..consumed);
+
+    (full, consumed as f64)
+}
+
 
 ///         WORK ON CORDINATING THE CONCENSUS STEPS AND PROCESS
 
 impl NodeMethods for Node {
     fn new() -> Self {
         let mut rng = rand::thread_rng();
-        let pvt_key = PrivateKey::generate(&mut rng);
-        let pub_key = pvt_key.public_key();
+        Self::from_key(PrivateKey::generate(&mut rng))
+    }
 
-        let id = hex::encode(pub_key.as_bytes());
+    fn from_key(pvt_key: PrivateKey) -> Self {
+        Self::from_signer(Arc::new(LocalSigner::new(pvt_key)))
+    }
+
+    fn from_signer(signer: Arc<dyn Signer>) -> Self {
+        let id = hex::encode(signer.public_key().as_bytes());
 
         let indexes: Vec<usize> = vec![0,0,0];
 
-        let node = Node { id, block_chain: BlockChain::new(), stake: 0.0, state: NodeState::Idle,
-             staging: vec![], block_staging: vec![], validators: vec![],
-            primary: vec![], msg_idx: indexes, private_key: pvt_key };
+        let node = Node { id, block_chain: Arc::new(RwLock::new(BlockChain::new())), stake: 0.0, state: NodeState::Idle,
+             staging: vec![], block_staging: vec![], validator_set: Default::default(),
+            primary: vec![], msg_idx: indexes, signer };
         node
     }
 
-    fn sign_message(&self, block: &Block) -> String {
-        let signature = self.private_key.sign(block.serialize_block());
-        hex::encode(signature.as_bytes())
+    fn sign_message(&self, message: &str, phase: Phase) -> Result<String, SignerError> {
+        let signature = self.signer.sign(&SigningRequest { phase, message: message.to_string() })?;
+        Ok(hex::encode(signature.as_bytes()))
     }
 
-    // Will have to change the pooling logic to pool only then wait
+    // Pools until the block policy is met by count or bytes, or until max_wait elapses.
+    // An empty pool on timeout is returned as is and becomes a heartbeat block.
+
+    async fn pool_transactions(&mut self, consumer: &StreamConsumer, user_base: &mut HashMap<String, Account>,
+         residual: &mut Vec<Transaction>, tx_time: u64, policy: &BlockPolicy) ->
+         (Vec<Transaction>, Vec<Transaction>, PoolingMetrics){
 
-    async fn pool_transactions(&mut self, consumer: &StreamConsumer, user_base: &mut HashMap<String, f64>,
-         residual: &mut Vec<Transaction>, time_out: u64, tx_time: u64, block_size: &usize) -> 
-         (Option<Vec<Transaction>>,Vec<Transaction>, Option<PoolingMetrics>){
+        let mut pool: Vec<Transaction> = Vec::with_capacity(policy.max_size);
+        let mut pool_bytes: usize = 0;
 
-        let mut pool: Vec<Transaction> = Vec::with_capacity(*block_size);
+        let mut governance = {
+            let chain = self.block_chain.read().expect("Chain lock poisoned");
+            let mut governance = chain.governance().clone();
+            governance.height = chain.headers.len() as u64;
+            governance
+        };
+        let validators = self.validator_set.validators.clone();
 
         let mut message_stream = consumer.stream();
 
         let start = Instant::now();
-        // let mut transactions: f64 = 0.0;
+        let deadline = start + Duration::from_millis(policy.max_wait);
+        let mut ttf_start = Instant::now();
+        let mut checked: f64 = 0.0;
+        let mut process_time: f64 = 0.0;
 
         loop {
-            let ttf_start = Instant::now();
-            match timeout(Duration::from_millis(time_out), message_stream.next()).await {
+            let s1 = Instant::now();
+            let (full, a) = fill_pool(&mut pool, &mut pool_bytes, residual, user_base, &mut governance, &validators, policy);
+            checked += a;
+            process_time += s1.elapsed().as_millis() as f64;
+
+            if full { break; }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            match timeout(remaining, message_stream.next()).await {
             Ok(Some(message_result)) => {
                 match message_result {
                     Err(_) => panic!["Error while receiving message"],
                     Ok(message) => {
+                            ttf_start = Instant::now();
                             if let Some(payload) = message.payload() {
                                 let transaction_result: Result<Vec<Transaction>, _> = serde_json::from_slice(payload);
                                 match transaction_result{
                                     Ok(mut transaction_vec) => {
                                         tokio::time::sleep(Duration::from_millis(tx_time)).await;
 
-                                        // transactions += 64.0;
                                         if let Err(e) = consumer.commit_message(&message, rdkafka::consumer::CommitMode::Sync) {
                                             eprintln!("Failed to commit message: {}", e);
                                         }
 
                                         residual.append(&mut transaction_vec);
-                                        if residual.len() > *block_size {
-                                            let s1 = Instant::now();
-                                            let mut a: f64 = 0.0;
-                                            for transaction in residual.clone() {
-                                                a = a+1.0;    
-                                                let balance = user_base.get(&transaction.from).unwrap().to_owned();
-                                                
-                                                if balance < transaction.amount + transaction.fee {
-                                                    continue;
-                                                }
-
-                                                let pub_key_bytes = hex::decode(&transaction.from).unwrap();
-                                                let public_key = UnparsedPublicKey::new(&ED25519, pub_key_bytes);
-                                                if transaction.verify_transaction(public_key) == true {
-                                                    let new_balance = balance - transaction.amount - transaction.fee;
-                                                    user_base.insert(transaction.from.clone(), new_balance);
-                                                    pool.push(transaction.to_owned());
-                                                } else { continue; }
-                                                if pool.len() == *block_size { 
-                                                    let end = s1.elapsed().as_millis() as f64;
-                                                    let tps = 1000.0 * (a / (start.elapsed().as_millis() as f64));
-                                                    let ttf = ttf_start.elapsed().as_millis() as f64;
-                                                    let bad_tx = a - (*block_size as f64);
-                                                    let metrics = PoolingMetrics {
-                                                        tps, processtime: end, bad_tx, ttf
-                                                    };
-                                                    return (Some(pool),residual.to_owned(), Some(metrics));
-                                                } else { continue; }
-                                            }
-                                        }
                                     }
-                                    Err(_) => {
-                                        panic!["Failed to deserialize message"];
+                                    Err(e) => {
+                                        error!("Dropping malformed transaction payload: {}", e);
+
+                                        if let Err(e) = consumer.commit_message(&message, rdkafka::consumer::CommitMode::Sync) {
+                                            eprintln!("Failed to commit message: {}", e);
+                                        }
                                     }
                                 }
                             }
@@ -117,20 +186,23 @@ impl NodeMethods for Node {
                 break;
             }
             Err(_) => {
-                if !pool.is_empty(){
-                    break;
-                } else {
-                    continue;
-                }
+                info!("Block wait time elapsed with {} transactions", pool.len());
+                break;
             }
             }
         }
-        if pool.is_empty() {
-            return (None,residual.to_owned(), None);
-        }
-        else {
-            return (Some(pool),residual.to_owned(), None);
-        }
+
+        let elapsed = start.elapsed().as_millis() as f64;
+        let tps = match elapsed > 0.0 {
+            true => 1000.0 * (checked / elapsed),
+            false => 0.0
+        };
+        let metrics = PoolingMetrics {
+            tps, processtime: process_time, bad_tx: checked - (pool.len() as f64),
+            ttf: ttf_start.elapsed().as_millis() as f64
+        };
+
+        (pool, residual.to_owned(), metrics)
     }
 
     async fn concensus(&mut self, pool: Vec<Transaction>, pkey_store: HashMap<String, PublicKey>,
@@ -161,7 +233,9 @@ impl NodeMethods for Node {
 
         info!("Sarted listening for other nodes to sync in with primary handle");
 
-        let (a, preprepare_wait) = Node::ready_state(self.validators.len(), String::from("Preprepare"), prepre_ready, time_out).await;
+        let (a, preprepare_wait) = Node::ready_state(self.validator_set.validators.len(), String::from("Preprepare"), prepre_ready, time_out).await;
+
+        self.state = NodeState::PrePreparing;
 
         if a {self.preprepare_phase(pool, &prepre_prod).await;}
         else {panic!["returned false..."]}
@@ -182,7 +256,9 @@ impl NodeMethods for Node {
 
         // tokio::time::sleep(Duration::from_millis(time_out)).await;
 
-        let (b, prepare_wait) = Node::ready_state(self.validators.len(), String::from("Prepare"), pre_ready, time_out).await;
+        let (b, prepare_wait) = Node::ready_state(self.validator_set.validators.len(), String::from("Prepare"), pre_ready, time_out).await;
+
+        self.state = NodeState::Preparing;
 
         if b {self.prepare_phase(&pkey_store, primary_msg, &pre_prod).await;} 
         else {panic!["returned false"];}
@@ -201,9 +277,19 @@ impl NodeMethods for Node {
 
         // tokio::time::sleep(Duration::from_millis(time_out)).await;
 
-        let (c, commit_wait) = Node::ready_state(self.validators.len(), String::from("Commit"), commit_ready, time_out).await;
+        let (c, commit_wait) = Node::ready_state(self.validator_set.validators.len(), String::from("Commit"), commit_ready, time_out).await;
+
+        self.state = NodeState::Committing;
+
+        let committed = c && self.commit_phase(&pkey_store,prepare_msg, &comm_prod).await;
 
-        if c {self.commit_phase(&pkey_store,prepare_msg, &comm_prod).await;}
+        self.state = NodeState::Done;
+
+        // A round that committed nothing has no timings to report
+        if !committed {
+            info!("No block was committed this round");
+            return None;
+        }
 
         let end3 = start3.elapsed().as_millis() as f64;
 
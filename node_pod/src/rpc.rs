@@ -0,0 +1,413 @@
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::response::Response;
+use axum::{extract::{ConnectInfo, State}, routing::{get, post}, Json, Router};
+use log::{error, info};
+use rdkafka::producer::{BaseProducer, BaseRecord};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use crate::definitions::block_header::{BlockChainMethods, BlockHeader};
+use crate::definitions::index_header::IndexMethods;
+use crate::definitions::node_header::Node;
+use crate::definitions::rpc_header::{Event, Rejection, RpcError, RpcMethods, RpcRequest, RpcResponse, RpcState, SharedRpcState, SubmitResult, Subscription};
+use crate::definitions::transaction_header::{Transaction, TransactionMethods};
+use crate::definitions::governance_header::{GovernanceAction, GovernanceMethods, GovernanceTransactionMethods};
+use crate::definitions::consensus_header::ValidatorSetMethods;
+use crate::governance::electorate;
+
+// JSON-RPC 2.0 over a single POST route. Lookups that find nothing answer with a null
+// result, errors are kept for requests that can not be answered at all.
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const FORBIDDEN: i64 = -32000;
+const NOT_A_VALIDATOR: i64 = -32001;
+
+fn param<T: DeserializeOwned>(params: &Value, name: &str) -> Result<T, RpcError> {
+    params.get(name)
+        .and_then(|value| serde_json::from_value(value.clone()).ok())
+        .ok_or(RpcError { code: INVALID_PARAMS, message: format!("Missing or invalid param {}", name) })
+}
+
+fn to_value<T: serde::Serialize>(value: Option<T>) -> Value {
+    serde_json::to_value(value).expect("Failed to serialize RPC result")
+}
+
+fn error_response(code: i64, message: &str, id: Value) -> RpcResponse {
+    RpcResponse { jsonrpc: String::from("2.0"), result: None,
+        error: Some(RpcError { code, message: message.to_string() }), id }
+}
+
+fn respond(request: &RpcRequest, outcome: Result<Value, RpcError>) -> RpcResponse {
+    match outcome {
+        Ok(result) => RpcResponse { jsonrpc: String::from("2.0"), result: Some(result), error: None, id: request.id.clone() },
+        Err(error) => RpcResponse { jsonrpc: String::from("2.0"), result: None, error: Some(error), id: request.id.clone() }
+    }
+}
+
+fn is_hex(value: &str, bytes: usize) -> bool {
+    value.len() == 2 * bytes && hex::decode(value).is_ok()
+}
+
+fn check_format(transaction: &Transaction) -> Result<(), Rejection> {
+    let reason = if !is_hex(&transaction.id, 32) {
+        "id is not a 32 byte hex digest"
+    } else if !is_hex(&transaction.from, 32) {
+        "sender is not a 32 byte hex public key"
+    } else if !is_hex(&transaction.signature, 64) {
+        "signature is not a 64 byte hex signature"
+    } else if transaction.to.is_empty() || transaction.to == transaction.from {
+        "receiver is missing or is the sender"
+    } else if !transaction.amount.is_finite() || transaction.amount <= 0.0 {
+        "amount is not positive"
+    } else if !transaction.fee.is_finite() || transaction.fee < 0.0 {
+        "fee is negative"
+    } else {
+        return Ok(());
+    };
+
+    Err(Rejection::Format(reason.to_string()))
+}
+
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rejection::Format(reason) => write!(f, "malformed transaction: {}", reason),
+            Rejection::Id => write!(f, "id does not match the transaction"),
+            Rejection::Signature => write!(f, "signature does not verify against the sender"),
+            Rejection::Replay => write!(f, "transaction was already submitted"),
+            Rejection::UnknownSender => write!(f, "sender has no account"),
+            Rejection::Balance => write!(f, "sender can not pay the amount and fee"),
+            Rejection::Nonce => write!(f, "nonce does not follow the sender's account nonce"),
+            Rejection::Fee => write!(f, "fee is below the minimum set by governance"),
+            Rejection::Governance => write!(f, "governance action is not valid for the current committee and proposals"),
+        }
+    }
+}
+
+impl Subscription {
+    fn start(&self, chain_len: usize) -> usize {
+        match self {
+            Subscription::Blocks { from_height } | Subscription::Account { from_height, .. } => from_height.unwrap_or(chain_len),
+            Subscription::Transaction { .. } => 0
+        }
+    }
+}
+
+impl RpcState {
+    fn dispatch(&self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        let chain = self.block_chain.read().expect("Chain lock poisoned");
+
+        match method {
+            "chain_tip" => {
+                let tip = chain.headers.last().expect("Chain has no genesis block");
+                Ok(json!({ "height": chain.headers.len() - 1, "hash": tip.hash, "header": tip.header }))
+            }
+            "block_by_height" => Ok(to_value(chain.block_by_height(param(params, "height")?))),
+            "block_by_hash" => Ok(to_value(chain.block_by_hash(&param::<String>(params, "hash")?))),
+            "header_by_height" => Ok(to_value::<&BlockHeader>(chain.header(param(params, "height")?))),
+            "transaction" => Ok(to_value(chain.transaction(&param::<String>(params, "id")?))),
+            "account_balance" => {
+                let account_id: String = param(params, "account")?;
+                Ok(to_value(chain.state.accounts.get(&account_id).map(|account| json!({
+                    "account_id": account_id,
+                    "balance": account.balance,
+                    "nonce": account.nonce,
+                    "height": chain.headers.len() - 1
+                }))))
+            }
+            "mempool_size" => Ok(json!(self.pending.union(&self.submitted).count())),
+            "validators" => Ok(json!({ "validators": self.validator_set.validators, "epoch": self.validator_set.epoch,
+                "validator_set_hash": self.validator_set.hash, "primary": self.primary })),
+            "governance" => Ok(json!(chain.governance())),
+            "node_state" => Ok(json!({
+                "node_id": self.node_id,
+                "state": self.node_state,
+                "height": chain.headers.len() - 1
+            })),
+            _ => Err(RpcError { code: METHOD_NOT_FOUND, message: format!("Unknown method {}", method) })
+        }
+    }
+}
+
+impl RpcMethods for RpcState {
+
+    fn new(node: &Node, submissions: UnboundedSender<Transaction>) -> Self {
+        RpcState {
+            node_id: node.id.clone(),
+            block_chain: Arc::clone(&node.block_chain),
+            height: node.block_chain.read().expect("Chain lock poisoned").headers.len(),
+            pending: HashSet::new(),
+            submitted: HashSet::new(),
+            submissions,
+            tips: broadcast::channel(64).0,
+            validator_set: node.validator_set.clone(),
+            primary: node.primary.clone(),
+            node_state: node.state.clone(),
+            signer: Arc::clone(&node.signer),
+        }
+    }
+
+    fn publish(&mut self, node: &Node, pending: &[Transaction]) {
+        let chain = self.block_chain.read().expect("Chain lock poisoned");
+
+        // Sending only fails when nobody is subscribed
+        if chain.headers.len() != self.height {
+            self.height = chain.headers.len();
+            let _ = self.tips.send(self.height);
+        }
+
+        self.pending = pending.iter().map(|transaction| transaction.id.clone()).collect();
+
+        let (pending, index) = (&self.pending, &chain.index);
+        self.submitted.retain(|id| !pending.contains(id) && index.locate(id).is_none());
+        drop(chain);
+
+        self.validator_set = node.validator_set.clone();
+        self.primary = node.primary.clone();
+        self.node_state = node.state.clone();
+    }
+
+    fn handle(&self, request: RpcRequest) -> RpcResponse {
+        if request.jsonrpc != "2.0" {
+            return error_response(INVALID_REQUEST, "Only JSON-RPC 2.0 is supported", request.id);
+        }
+
+        respond(&request, self.dispatch(&request.method, &request.params))
+    }
+
+    // Governance actions are checked against committed governance only, so a vote on a
+    // proposal that is still pending is turned away until the proposal commits
+    fn check_transaction(&self, transaction: &Transaction) -> Result<(), Rejection> {
+        let id = &transaction.id;
+        let chain = self.block_chain.read().expect("Chain lock poisoned");
+        let replayed = self.pending.contains(id) || self.submitted.contains(id) || chain.index.locate(id).is_some();
+
+        if let Some(action) = &transaction.governance {
+            if replayed {
+                return Err(Rejection::Replay);
+            }
+            let mut governance = chain.governance().clone();
+            governance.height = chain.headers.len() as u64;
+            return match transaction.check_governance(&self.validator_set.validators) && governance.apply_action(&transaction.from, id, action) {
+                true => Ok(()),
+                false => Err(Rejection::Governance)
+            };
+        }
+
+        check_format(transaction)?;
+
+        if transaction.compute_id() != transaction.id {
+            return Err(Rejection::Id);
+        }
+        if !transaction.verify_sender() {
+            return Err(Rejection::Signature);
+        }
+        if replayed {
+            return Err(Rejection::Replay);
+        }
+        if transaction.fee < chain.governance().parameters.min_fee.unwrap_or(0.0) {
+            return Err(Rejection::Fee);
+        }
+
+        match chain.state.accounts.get(&transaction.from) {
+            None => Err(Rejection::UnknownSender),
+            Some(account) if account.balance < transaction.amount + transaction.fee => Err(Rejection::Balance),
+            Some(account) if transaction.nonce.is_some_and(|nonce| nonce != account.nonce + 1) => Err(Rejection::Nonce),
+            Some(_) => Ok(())
+        }
+    }
+
+    fn submit(&mut self, transaction: Transaction) -> SubmitResult {
+        let id = transaction.id.clone();
+
+        if let Err(rejection) = self.check_transaction(&transaction) {
+            return SubmitResult { id, accepted: false, reason: Some(rejection.to_string()) };
+        }
+
+        if self.submissions.send(transaction).is_err() {
+            return SubmitResult { id, accepted: false, reason: Some(String::from("node is not accepting transactions")) };
+        }
+
+        self.submitted.insert(id.clone());
+
+        SubmitResult { id, accepted: true, reason: None }
+    }
+
+    fn handle_submission(&mut self, request: RpcRequest) -> RpcResponse {
+        if request.jsonrpc != "2.0" {
+            return error_response(INVALID_REQUEST, "Only JSON-RPC 2.0 is supported", request.id);
+        }
+
+        let outcome = param::<Transaction>(&request.params, "transaction")
+            .map(|transaction| json!(self.submit(transaction)));
+
+        respond(&request, outcome)
+    }
+
+    // Signs with this node's key, so only clients on the node's own host may ask for it
+    fn handle_governance(&mut self, request: RpcRequest, local: bool) -> RpcResponse {
+        if request.jsonrpc != "2.0" {
+            return error_response(INVALID_REQUEST, "Only JSON-RPC 2.0 is supported", request.id);
+        }
+        if !local {
+            return error_response(FORBIDDEN, "Governance requests are only accepted from localhost", request.id);
+        }
+
+        if !self.validator_set.contains(&self.node_id) {
+            return error_response(NOT_A_VALIDATOR, "Node is not in the current committee", request.id);
+        }
+
+        let params = &request.params;
+        let action = match request.method.as_str() {
+            "propose_parameter" => (|| Ok(GovernanceAction::Propose {
+                parameter: param(params, "parameter")?,
+                value: param(params, "value")?,
+                activation_height: param(params, "activation_height")?,
+                electorate: electorate(&self.validator_set.validators)
+            }))(),
+            _ => (|| Ok(GovernanceAction::Vote {
+                proposal: param(params, "proposal")?,
+                approve: param(params, "approve")?
+            }))()
+        };
+
+        let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+            .expect("Clock is before the epoch").as_millis() as u64;
+        let outcome = action.and_then(|action| Transaction::governance(self.signer.as_ref(), action, timestamp)
+            .map_err(|e| RpcError { code: NOT_A_VALIDATOR, message: e.to_string() }))
+            .map(|transaction| json!(self.submit(transaction)));
+
+        respond(&request, outcome)
+    }
+
+    fn events(&self, subscription: &Subscription, from: usize, to: usize) -> Vec<Event> {
+        let chain = self.block_chain.read().expect("Chain lock poisoned");
+        let heights = from..to.min(chain.headers.len());
+
+        match subscription {
+            Subscription::Blocks { .. } => heights
+                .filter_map(|height| Some(Event::Block { height, block: chain.block_by_height(height)? }))
+                .collect(),
+            Subscription::Transaction { id } => match chain.index.locate(id) {
+                Some((height, _)) if heights.contains(&height) => chain.transaction(id)
+                    .map(|receipt| Event::Confirmation { receipt })
+                    .into_iter()
+                    .collect(),
+                _ => vec![]
+            },
+            Subscription::Account { account, .. } => {
+                let mut changed: Vec<usize> = chain.index.sent_by(account).iter()
+                    .chain(chain.index.received_by(account))
+                    .map(|(height, _)| *height)
+                    .filter(|height| heights.contains(height))
+                    .collect();
+                changed.sort();
+                changed.dedup();
+
+                changed.into_iter().filter_map(|height| {
+                    let state = chain.account(account, height)?;
+                    Some(Event::Balance { height, account_id: account.clone(), balance: state.balance, nonce: state.nonce })
+                }).collect()
+            }
+        }
+    }
+}
+
+/// Produces transactions accepted over RPC to the Transactions topic in the batch format pools consume
+pub async fn forward_submissions(mut submissions: UnboundedReceiver<Transaction>, producer: Arc<BaseProducer>) {
+    while let Some(transaction) = submissions.recv().await {
+        let payload = serde_json::to_string(&vec![transaction]).expect("Failed to serialize transaction");
+
+        if let Err((e, _)) = producer.send(BaseRecord::to("Transactions").payload(&payload).key("RPC Transaction")) {
+            error!("Failed to forward submitted transaction: {}", e);
+        }
+    }
+}
+
+async fn rpc_handler(State(state): State<SharedRpcState>, ConnectInfo(client): ConnectInfo<SocketAddr>, body: String) -> Json<RpcResponse> {
+    match serde_json::from_str::<RpcRequest>(&body) {
+        Ok(request) if request.method == "submit_transaction" => Json(state.write().await.handle_submission(request)),
+        Ok(request) if request.method == "propose_parameter" || request.method == "vote_proposal" =>
+            Json(state.write().await.handle_governance(request, client.ip().is_loopback())),
+        Ok(request) => Json(state.read().await.handle(request)),
+        Err(_) => Json(error_response(PARSE_ERROR, "Request is not a valid JSON-RPC request", Value::Null))
+    }
+}
+
+async fn ws_handler(upgrade: WebSocketUpgrade, State(state): State<SharedRpcState>) -> Response {
+    upgrade.on_upgrade(move |socket| stream_events(socket, state))
+}
+
+// Events are read from the published chain between a cursor and the tip, so a subscriber that
+// lags behind the broadcast or reconnects with `from_height` catches up without gaps
+async fn stream_events(mut socket: WebSocket, state: SharedRpcState) {
+    let subscription = match socket.recv().await {
+        Some(Ok(Message::Text(text))) => match serde_json::from_str::<Subscription>(&text) {
+            Ok(subscription) => subscription,
+            Err(e) => {
+                let _ = socket.send(Message::Text(json!({ "error": e.to_string() }).to_string())).await;
+                return;
+            }
+        },
+        _ => return
+    };
+
+    let (mut tips, mut cursor) = {
+        let state = state.read().await;
+        (state.tips.subscribe(), subscription.start(state.height))
+    };
+
+    loop {
+        let events = {
+            let state = state.read().await;
+            let tip = state.height;
+            let events = state.events(&subscription, cursor, tip);
+            cursor = cursor.max(tip);
+            events
+        };
+
+        for event in &events {
+            let message = serde_json::to_string(event).expect("Failed to serialize event");
+            if socket.send(Message::Text(message)).await.is_err() {
+                return;
+            }
+        }
+
+        if matches!(subscription, Subscription::Transaction { .. }) && !events.is_empty() {
+            let _ = socket.send(Message::Close(None)).await;
+            return;
+        }
+
+        tokio::select! {
+            tip = tips.recv() => {
+                if let Err(RecvError::Closed) = tip { return; }
+            }
+            message = socket.recv() => {
+                match message {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+pub async fn serve(state: SharedRpcState, address: String) {
+    let app = Router::new()
+        .route("/", post(rpc_handler))
+        .route("/ws", get(ws_handler))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(&address).await.expect("Failed to bind RPC address");
+
+    info!("Serving RPC on {}", address);
+
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.expect("RPC server stopped");
+}
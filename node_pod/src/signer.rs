@@ -0,0 +1,289 @@
+use bls_signatures::{PrivateKey, PublicKey, Serialize, Signature};
+use log::{error, info};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::Mutex;
+use std::time::Duration;
+use crate::definitions::block_header::{Block, BlockMethods};
+use crate::definitions::consensus_header::STAKE_DOMAIN;
+use crate::definitions::identity_header::{IdentityMethods, KeyFile};
+use crate::definitions::network_header::ReplyBody;
+use crate::definitions::signer_header::{FileSigner, FileSignerMethods, LocalSigner, LocalSignerMethods, Phase,
+    PhaseMethods, RemoteSigner, RemoteSignerMethods, Signer, SignerError, SignerRequest, SignerResponse, SigningGuard,
+    SigningGuardMethods, SigningRequest, GuardMark, COMMIT_DOMAIN, GOVERNANCE_DOMAIN, POSSESSION_DOMAIN,
+    PREPARE_DOMAIN, PREPREPARE_DOMAIN, REPLY_DOMAIN};
+use openssl::sha::sha256;
+use std::collections::BTreeMap;
+
+// Heights kept by the guard below the highest one signed
+const GUARD_WINDOW: u64 = 1024;
+
+/// What a validator signs to prove it holds the secret key of `public_key`
+pub fn possession_message(public_key: &PublicKey) -> String {
+    format!("{}{}", POSSESSION_DOMAIN, hex::encode(public_key.as_bytes()))
+}
+
+impl PhaseMethods for Phase {
+    fn domain(&self) -> &'static str {
+        match self {
+            Phase::PrePrepare => PREPREPARE_DOMAIN,
+            Phase::Prepare => PREPARE_DOMAIN,
+            Phase::Commit => COMMIT_DOMAIN,
+            Phase::Reply => REPLY_DOMAIN,
+            Phase::Governance => GOVERNANCE_DOMAIN,
+            Phase::Possession => POSSESSION_DOMAIN,
+            Phase::Stake => STAKE_DOMAIN,
+        }
+    }
+
+    fn message(&self, body: &str) -> String {
+        format!("{}{}", self.domain(), body)
+    }
+
+    fn body<'a>(&self, message: &'a str) -> Option<&'a str> {
+        message.strip_prefix(self.domain())
+    }
+}
+
+// A block is told apart by the hash of its header, recomputed rather than taken from the
+// message, and a reply by a digest of its whole body
+
+fn target(request: &SigningRequest, body: &str) -> Result<(u64, u64, String), SignerError> {
+    let invalid = |e: serde_json::Error| SignerError::Message(e.to_string());
+
+    match request.phase {
+        Phase::Reply => {
+            let body: ReplyBody = serde_json::from_str(body).map_err(invalid)?;
+            Ok((body.height, body.view, hex::encode(sha256(request.message.as_bytes()))))
+        }
+        _ => {
+            let block: Block = serde_json::from_str(body).map_err(invalid)?;
+            let hash = hex::encode(block.hash().map_err(|e| SignerError::Message(e.to_string()))?);
+            if hash != block.hash {
+                return Err(SignerError::Message(String::from("the block hash does not match its header")));
+            }
+            Ok((block.header.index, block.header.view, hash))
+        }
+    }
+}
+
+fn save_marks(path: &str, marks: &BTreeMap<Phase, GuardMark>) -> Result<(), SignerError> {
+    let failed = |e: std::io::Error| SignerError::Guard(format!("{}: {}", path, e));
+    let temp = format!("{}.tmp", path);
+    std::fs::write(&temp, serde_json::to_vec(marks).expect("Failed to serialize guard marks")).map_err(failed)?;
+    std::fs::rename(&temp, path).map_err(failed)
+}
+
+impl SigningGuardMethods for SigningGuard {
+    fn new(window: u64) -> Self {
+        SigningGuard { signed: Default::default(), floor: 0, window, marks: Default::default(),
+            restored: Default::default(), path: None }
+    }
+
+    fn open(path: &str, window: u64) -> Result<Self, SignerError> {
+        let restored: BTreeMap<Phase, GuardMark> = match std::fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| SignerError::Guard(format!("{}: {}", path, e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => { return Err(SignerError::Guard(format!("{}: {}", path, e))); }
+        };
+        let floor = restored.values().map(|mark| mark.height.saturating_sub(window)).max().unwrap_or(0);
+
+        Ok(SigningGuard { signed: Default::default(), floor, window, marks: restored.clone(), restored,
+            path: Some(path.to_string()) })
+    }
+
+    // Signing the same block again is allowed so a node can resend its own message
+
+    // Every message starts with the domain of its phase, so a signature made for one phase never
+    // verifies for another. Phases that name no block are not guarded beyond that.
+
+    fn check(&mut self, request: &SigningRequest) -> Result<(), SignerError> {
+        let body = match request.phase.body(&request.message) {
+            Some(body) => body,
+            None => {
+                return Err(SignerError::Message(format!("a {:?} signature can only cover a message behind its domain",
+                    request.phase)));
+            }
+        };
+        if matches!(request.phase, Phase::Governance | Phase::Possession | Phase::Stake) {
+            return Ok(());
+        }
+
+        let (height, view, hash) = target(request, body)?;
+
+        if height < self.floor {
+            return Err(SignerError::Stale(height));
+        }
+        if let Some(mark) = self.restored.get(&request.phase) {
+            match (height, view).cmp(&(mark.height, mark.view)) {
+                std::cmp::Ordering::Less => { return Err(SignerError::Stale(height)); }
+                std::cmp::Ordering::Equal if mark.hash != hash => {
+                    return Err(SignerError::DoubleSign { height, view, phase: request.phase });
+                }
+                _ => {}
+            }
+        }
+
+        let key = (height, view, request.phase);
+        match self.signed.get(&key) {
+            Some(signed) if *signed != hash => {
+                return Err(SignerError::DoubleSign { height, view, phase: request.phase });
+            }
+            Some(_) => {}
+            None => { self.signed.insert(key, hash.clone()); }
+        }
+
+        // The mark is on disk before the signature exists
+        let raised = self.marks.get(&request.phase).is_none_or(|mark| (height, view) > (mark.height, mark.view));
+        if raised {
+            self.marks.insert(request.phase, GuardMark { height, view, hash });
+            if let Some(path) = &self.path {
+                save_marks(path, &self.marks)?;
+            }
+        }
+
+        let floor = height.saturating_sub(self.window);
+        if floor > self.floor {
+            self.floor = floor;
+            self.signed = self.signed.split_off(&(floor, 0, Phase::PrePrepare));
+        }
+        Ok(())
+    }
+}
+
+impl LocalSignerMethods for LocalSigner {
+    fn new(private_key: PrivateKey) -> Self {
+        LocalSigner { private_key, guard: Mutex::new(SigningGuard::new(GUARD_WINDOW)) }
+    }
+}
+
+impl Signer for LocalSigner {
+    fn public_key(&self) -> PublicKey {
+        self.private_key.public_key()
+    }
+
+    fn sign(&self, request: &SigningRequest) -> Result<Signature, SignerError> {
+        if request.phase == Phase::Possession && request.message != possession_message(&self.public_key()) {
+            return Err(SignerError::Message(String::from("a proof of possession can only cover the signer's own key")));
+        }
+        self.guard.lock().expect("Signing guard poisoned").check(request)?;
+        Ok(self.private_key.sign(&request.message))
+    }
+}
+
+impl FileSignerMethods for FileSigner {
+    fn open(path: &str, passphrase: Option<&str>) -> Result<Self, SignerError> {
+        let private_key = KeyFile::load_or_create(path, passphrase).map_err(|e| SignerError::Key(e.to_string()))?;
+        let guard = SigningGuard::open(&format!("{}.guard", path), GUARD_WINDOW)?;
+        Ok(FileSigner { path: path.to_string(), inner: LocalSigner { private_key, guard: Mutex::new(guard) } })
+    }
+}
+
+impl Signer for FileSigner {
+    fn public_key(&self) -> PublicKey {
+        self.inner.public_key()
+    }
+
+    fn sign(&self, request: &SigningRequest) -> Result<Signature, SignerError> {
+        self.inner.sign(request)
+    }
+}
+
+fn exchange(socket: &str, request: &SignerRequest) -> Result<SignerResponse, SignerError> {
+    let remote = |e: std::io::Error| SignerError::Remote(format!("{}: {}", socket, e));
+
+    let mut stream = UnixStream::connect(socket).map_err(remote)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5))).map_err(remote)?;
+
+    let line = serde_json::to_string(request).expect("Failed to serialize signer request");
+    writeln!(stream, "{}", line).map_err(remote)?;
+
+    let mut response = String::new();
+    BufReader::new(&stream).read_line(&mut response).map_err(remote)?;
+    serde_json::from_str(&response).map_err(|e| SignerError::Remote(format!("Invalid response: {}", e)))
+}
+
+fn decode<T>(value: &str, parse: impl Fn(&[u8]) -> Result<T, bls_signatures::Error>) -> Result<T, SignerError> {
+    hex::decode(value).ok().and_then(|bytes| parse(&bytes).ok())
+        .ok_or(SignerError::Remote(format!("Invalid key material {}", value)))
+}
+
+fn respond(signer: &dyn Signer, line: &str) -> SignerResponse {
+    match serde_json::from_str::<SignerRequest>(line) {
+        Ok(SignerRequest::PublicKey) => SignerResponse::PublicKey { public_key: hex::encode(signer.public_key().as_bytes()) },
+        Ok(SignerRequest::Sign { phase, message }) => match signer.sign(&SigningRequest { phase, message }) {
+            Ok(signature) => SignerResponse::Signature { signature: hex::encode(signature.as_bytes()) },
+            Err(error) => SignerResponse::Refused { error }
+        },
+        Err(e) => SignerResponse::Refused { error: SignerError::Message(e.to_string()) }
+    }
+}
+
+impl RemoteSignerMethods for RemoteSigner {
+    fn connect(socket: &str) -> Result<Self, SignerError> {
+        match exchange(socket, &SignerRequest::PublicKey)? {
+            SignerResponse::PublicKey { public_key } => {
+                Ok(RemoteSigner { socket: socket.to_string(), public_key: decode(&public_key, PublicKey::from_bytes)? })
+            }
+            other => Err(SignerError::Remote(format!("Unexpected response {:?}", other)))
+        }
+    }
+
+    // Connections are served one at a time, so requests reach the guard in the order they arrive
+
+    fn serve(signer: &dyn Signer, socket: &str) -> std::io::Result<()> {
+        let _ = std::fs::remove_file(socket);
+        let listener = UnixListener::bind(socket)?;
+        info!("Signer listening on {}", socket);
+
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => { error!("Failed to accept signer connection: {}", e); continue; }
+            };
+
+            let mut writer = stream.try_clone()?;
+            for line in BufReader::new(stream).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(e) => { error!("Failed to read signer request: {}", e); break; }
+                };
+                let response = serde_json::to_string(&respond(signer, &line)).expect("Failed to serialize signer response");
+                if let Err(e) = writeln!(writer, "{}", response) {
+                    error!("Failed to answer signer request: {}", e);
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Signer for RemoteSigner {
+    fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+
+    fn sign(&self, request: &SigningRequest) -> Result<Signature, SignerError> {
+        let sign = SignerRequest::Sign { phase: request.phase, message: request.message.clone() };
+        match exchange(&self.socket, &sign)? {
+            SignerResponse::Signature { signature } => decode(&signature, Signature::from_bytes),
+            SignerResponse::Refused { error } => Err(error),
+            other => Err(SignerError::Remote(format!("Unexpected response {:?}", other)))
+        }
+    }
+}
+
+impl std::fmt::Display for SignerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SignerError::DoubleSign { height, view, phase } =>
+                write!(f, "Refused to sign a second block for height {} view {} phase {:?}", height, view, phase),
+            SignerError::Stale(height) => write!(f, "Refused to sign for height {} below the guard window", height),
+            SignerError::Message(e) => write!(f, "Malformed signing request: {}", e),
+            SignerError::Key(e) => write!(f, "{}", e),
+            SignerError::Remote(e) => write!(f, "Remote signer: {}", e),
+            SignerError::Guard(e) => write!(f, "Signing guard: {}", e),
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use openssl::sha::Sha256;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+use crate::definitions::state_header::{Account, SparseMerkleTree, StateDiff, StateProof, StateRecord, StateMethods};
+use crate::definitions::transaction_header::Transaction;
+use crate::definitions::governance_header::{Governance, GovernanceMethods};
+
+// Leaves hash the key with the account, internal nodes use the same 0x01 prefix as the
+// Merkle tree. Empty subtrees take precomputed default hashes so only occupied paths are hashed,
+// and the hashes of occupied subtrees are kept so a write only rehashes the path above its leaf.
+
+const DEPTH: usize = 256;
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+const GOVERNANCE_PREFIX: u8 = 0x02;
+
+fn hash_pair(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(&[NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finish().to_vec()
+}
+
+fn hash_leaf(key: &[u8], account: &Account) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(&[LEAF_PREFIX]);
+    hasher.update(key);
+    hasher.update(&account.balance.to_be_bytes());
+    hasher.update(&account.nonce.to_be_bytes());
+    hasher.finish().to_vec()
+}
+
+fn with_governance(tree_root: Vec<u8>, governance: Option<&Vec<u8>>) -> Vec<u8> {
+    match governance {
+        None => tree_root,
+        Some(digest) => {
+            let mut hasher = Sha256::new();
+            hasher.update(&[GOVERNANCE_PREFIX]);
+            hasher.update(&tree_root);
+            hasher.update(digest);
+            hasher.finish().to_vec()
+        }
+    }
+}
+
+fn account_key(account_id: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(account_id.as_bytes());
+    hasher.finish()
+}
+
+fn bit(key: &[u8; 32], depth: usize) -> u8 {
+    (key[depth / 8] >> (7 - depth % 8)) & 1
+}
+
+/// Root of an empty subtree of every height, index 0 being an empty leaf
+fn defaults() -> &'static Vec<Vec<u8>> {
+    static DEFAULTS: OnceLock<Vec<Vec<u8>>> = OnceLock::new();
+    DEFAULTS.get_or_init(|| {
+        let mut defaults = vec![vec![0u8; 32]];
+        for height in 0..DEPTH {
+            let empty = hash_pair(&defaults[height], &defaults[height]);
+            defaults.push(empty);
+        }
+        defaults
+    })
+}
+
+/// Key prefix of the subtree at `depth` holding `key`, the bits below it cleared
+fn prefix(key: &[u8; 32], depth: usize) -> [u8; 32] {
+    let mut prefix = [0u8; 32];
+    prefix[..depth / 8].copy_from_slice(&key[..depth / 8]);
+    if !depth.is_multiple_of(8) {
+        prefix[depth / 8] = key[depth / 8] & (0xff << (8 - depth % 8));
+    }
+    prefix
+}
+
+/// Prefix of the other child of the subtree at `depth` on the path to `key`
+fn sibling(key: &[u8; 32], depth: usize) -> [u8; 32] {
+    let mut sibling = prefix(key, depth + 1);
+    sibling[depth / 8] ^= 1 << (7 - depth % 8);
+    sibling
+}
+
+impl SparseMerkleTree {
+    fn build(accounts: BTreeMap<String, Account>, governance: Governance) -> Self {
+        let mut state = SparseMerkleTree { governance, ..Default::default() };
+        for (id, account) in accounts {
+            state.set_account(&id, Some(account));
+        }
+        state
+    }
+
+    /// Writes an account and rehashes the path above its leaf, recording the value it replaces
+    /// when a block is being applied
+    fn set_account(&mut self, account_id: &str, account: Option<Account>) {
+        let key = account_key(account_id);
+        let leaf = account.as_ref().map(|account| hash_leaf(&key, account));
+        let previous = match account {
+            Some(account) => self.accounts.insert(account_id.to_string(), account),
+            None => self.accounts.remove(account_id)
+        };
+        if let Some(diff) = &mut self.pending {
+            diff.accounts.entry(account_id.to_string()).or_insert(previous);
+        }
+
+        match leaf {
+            Some(leaf) => self.nodes.insert((DEPTH, key), leaf),
+            None => self.nodes.remove(&(DEPTH, key))
+        };
+        for depth in (0..DEPTH).rev() {
+            let node = prefix(&key, depth);
+            let (left, right) = match bit(&key, depth) {
+                0 => (prefix(&key, depth + 1), sibling(&key, depth)),
+                _ => (sibling(&key, depth), prefix(&key, depth + 1))
+            };
+            match (self.nodes.get(&(depth + 1, left)), self.nodes.get(&(depth + 1, right))) {
+                (None, None) => { self.nodes.remove(&(depth, node)); }
+                (left, right) => {
+                    let empty = &defaults()[DEPTH - depth - 1];
+                    let hash = hash_pair(left.unwrap_or(empty), right.unwrap_or(empty));
+                    self.nodes.insert((depth, node), hash);
+                }
+            }
+        }
+    }
+
+    fn governance_digest(&self) -> Option<Vec<u8>> {
+        match self.governance.is_empty() {
+            true => None,
+            false => Some(self.governance.digest())
+        }
+    }
+}
+
+impl From<StateRecord> for SparseMerkleTree {
+    fn from(record: StateRecord) -> Self {
+        SparseMerkleTree::build(record.accounts, record.governance)
+    }
+}
+
+impl StateMethods for SparseMerkleTree {
+
+    fn new() -> Self {
+        SparseMerkleTree::default()
+    }
+
+    fn from_balances(balances: &HashMap<String, f64>) -> Self {
+        let accounts = balances.iter()
+            .map(|(id, balance)| (id.clone(), Account { balance: *balance, nonce: 0 }))
+            .collect();
+
+        SparseMerkleTree::build(accounts, Default::default())
+    }
+
+    // Returns false and leaves the state untouched when the sender can not pay or its nonce does
+    // not follow, or when a governance action does not apply. Governance transactions move no funds.
+    fn apply_transaction(&mut self, transaction: &Transaction) -> bool {
+        if let Some(action) = &transaction.governance {
+            return self.governance.apply_action(&transaction.from, &transaction.id, action);
+        }
+
+        let mut sender = match self.accounts.get(&transaction.from) {
+            Some(sender) if sender.balance >= transaction.amount + transaction.fee
+                && transaction.nonce.is_none_or(|nonce| nonce == sender.nonce + 1) => sender.clone(),
+            _ => { return false; }
+        };
+
+        sender.balance -= transaction.amount + transaction.fee;
+        sender.nonce += 1;
+        self.set_account(&transaction.from, Some(sender));
+
+        let mut receiver = self.accounts.get(&transaction.to).cloned()
+            .unwrap_or(Account { balance: 0.0, nonce: 0 });
+        receiver.balance += transaction.amount;
+        self.set_account(&transaction.to, Some(receiver));
+
+        true
+    }
+
+    // Transactions that can not be paid for are skipped so every node reaches the same state
+    fn apply_transactions(&self, transactions: &[Transaction]) -> Self {
+        let mut state = self.clone();
+
+        for transaction in transactions {
+            state.apply_transaction(transaction);
+        }
+
+        state
+    }
+
+    fn start_block(&mut self, height: u64) {
+        self.pending = Some(StateDiff { accounts: BTreeMap::new(), height: self.governance.height,
+            governance: Some(self.governance.clone()) });
+        self.governance.height = height;
+    }
+
+    // Proposals due at the next height are closed, so the parameters read after a block are
+    // the ones the next block is built under
+    fn finish_block(&mut self) -> StateDiff {
+        let next = self.governance.height + 1;
+        self.governance.activate(next);
+
+        let mut diff = self.pending.take().unwrap_or_default();
+        let unchanged = diff.governance.as_ref().is_some_and(|previous| previous.parameters == self.governance.parameters
+            && previous.proposals == self.governance.proposals);
+        if unchanged {
+            diff.governance = None;
+        }
+        diff
+    }
+
+    fn apply_block(&mut self, height: u64, transactions: &[Transaction]) -> StateDiff {
+        self.start_block(height);
+        for transaction in transactions {
+            self.apply_transaction(transaction);
+        }
+        self.finish_block()
+    }
+
+    fn revert(&mut self, diff: &StateDiff) {
+        self.pending = None;
+        for (id, account) in &diff.accounts {
+            self.set_account(id, account.clone());
+        }
+        match &diff.governance {
+            Some(governance) => self.governance = governance.clone(),
+            None => self.governance.height = diff.height
+        }
+    }
+
+    fn rollback(&mut self) {
+        if let Some(diff) = self.pending.take() {
+            self.revert(&diff);
+        }
+    }
+
+    fn root(&self) -> Vec<u8> {
+        let tree_root = self.nodes.get(&(0, [0u8; 32])).cloned().unwrap_or_else(|| defaults()[DEPTH].clone());
+        with_governance(tree_root, self.governance_digest().as_ref())
+    }
+
+    fn generate_proof(&self, account_id: &str) -> StateProof {
+        let key = account_key(account_id);
+
+        let mut siblings: Vec<Vec<u8>> = (0..DEPTH)
+            .map(|depth| self.nodes.get(&(depth + 1, sibling(&key, depth))).cloned().unwrap_or_default())
+            .collect();
+        siblings.reverse();
+
+        StateProof { account_id: account_id.to_string(), account: self.accounts.get(account_id).cloned(), siblings,
+            governance: self.governance_digest() }
+    }
+
+    fn validate_proof(proof: &StateProof, state_root: &[u8]) -> bool {
+        if proof.siblings.len() != DEPTH {
+            return false;
+        }
+
+        let key = account_key(&proof.account_id);
+        let mut hash = match &proof.account {
+            Some(account) => hash_leaf(&key, account),
+            None => defaults()[0].clone()
+        };
+
+        for (height, sibling) in proof.siblings.iter().enumerate() {
+            let sibling = match sibling.len() {
+                0 => &defaults()[height],
+                32 => sibling,
+                _ => { return false; }
+            };
+
+            hash = match bit(&key, DEPTH - 1 - height) {
+                0 => hash_pair(&hash, sibling),
+                _ => hash_pair(sibling, &hash)
+            };
+        }
+
+        with_governance(hash, proof.governance.as_ref()) == state_root
+    }
+}
@@ -1,2 +1,11 @@
 mod test_merkle;
-mod test_block;
\ No newline at end of file
+mod test_block;
+mod test_node;
+mod test_mmr;
+mod test_state;
+mod test_rpc;
+mod test_network;
+mod test_explorer;
+mod test_identity;
+mod test_signer;
+pub mod test_governance;
\ No newline at end of file
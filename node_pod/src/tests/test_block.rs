@@ -2,9 +2,16 @@
 mod tests {
     use crate::definitions::transaction_header::Transaction;
     use rand::{distributions::DistString, thread_rng, Rng};
-    use crate::definitions::block_header::{Block, BlockMethods, BlockChain, BlockChainMethods};
+    use crate::definitions::block_header::{Block, BlockMethods, BlockChain, BlockChainMethods, ChainError, ChainFault, HeaderMethods, PROTOCOL_VERSION};
+    use crate::definitions::merkle_header::{MerkleMethods, MerkleScheme, MerkleTree, ProofError};
+    use crate::definitions::mmr_header::MmrMethods;
+    use crate::definitions::state_header::{SparseMerkleTree, StateMethods};
+    use std::collections::HashMap;
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
     use rand::distributions::Alphanumeric;
     use std::time::UNIX_EPOCH;
+    use crate::definitions::consensus_header::{Validator, ValidatorSet, ValidatorSetMethods};
 
     fn generate_random_transactions(n: usize) -> Vec<Transaction> {
         let mut rng = thread_rng();
@@ -29,12 +36,35 @@ mod tests {
                 amount,
                 fee: 0.01 * amount,
                 signature: sig,
+                governance: None,
+                nonce: None,
             };
             transactions.push(tx);
         }
         transactions
     }
 
+    fn generate_key_pair() -> Ed25519KeyPair {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap()
+    }
+
+    fn sign_transaction(key_pair: &Ed25519KeyPair, transaction: &mut Transaction) {
+        transaction.from = hex::encode(key_pair.public_key().as_ref());
+        transaction.signature = String::new();
+        let msg = serde_json::to_string_pretty(&transaction).unwrap();
+        transaction.signature = hex::encode(key_pair.sign(msg.as_bytes()).as_ref());
+    }
+
+    fn generate_signed_transactions(n: usize) -> Vec<Transaction> {
+        let key_pair = generate_key_pair();
+        let mut transactions = generate_random_transactions(n);
+        for transaction in transactions.iter_mut() {
+            sign_transaction(&key_pair, transaction);
+        }
+        transactions
+    }
+
     #[test]
     fn test_block_hash() {
         let transactions = generate_random_transactions(128);
@@ -45,8 +75,8 @@ mod tests {
         let block = Block::new(transactions, dummy_hash, 0);
         let block_clone = block.clone();
 
-        let block_hash = block.hash();
-        let block_clone_hash = block_clone.hash();
+        let block_hash = block.hash().unwrap();
+        let block_clone_hash = block_clone.hash().unwrap();
 
         assert!(!block_hash.is_empty());
         assert!(block_hash == block_clone_hash);
@@ -66,11 +96,11 @@ mod tests {
 
         let block_clone = Block::deserialize_block(&block_json);
 
-        assert!(block.index == block_clone.index);
+        assert!(block.header.index == block_clone.header.index);
         assert!(block.hash == block_clone.hash);
-        assert!(block.timestamp == block_clone.timestamp);
-        assert!(block.merkle_root == block_clone.merkle_root);
-        assert!(block.prev_hash == block_clone.prev_hash);
+        assert!(block.header.timestamp == block_clone.header.timestamp);
+        assert!(block.header.merkle_root == block_clone.header.merkle_root);
+        assert!(block.header.prev_hash == block_clone.header.prev_hash);
         assert!(block.transactions.len() == block_clone.transactions.len());
         assert!(block.transactions == block_clone.transactions);
     }
@@ -107,9 +137,458 @@ mod tests {
     fn test_new_blockchain() {
         let blockchain = BlockChain::new();
 
-        assert!(blockchain.chain.len() == 1);
-        assert !(blockchain.chain[0].index == 0);
-        assert!(blockchain.chain[0].prev_hash == hex::encode(vec![]));
-        assert!(blockchain.chain[0].transactions.is_empty());
+        assert!(blockchain.headers.len() == 1);
+        assert !(blockchain.headers[0].header.index == 0);
+        assert!(blockchain.headers[0].header.prev_hash == hex::encode(vec![]));
+        assert!(blockchain.bodies[0].is_empty());
+    }
+
+    #[test]
+    fn test_empty_block() {
+        let blockchain = BlockChain::new();
+        let block = Block::new(vec![], blockchain.headers[0].hash.clone(), 1);
+
+        assert!(block.header.merkle_root.is_empty());
+        assert!(block.validate(vec![]));
+    }
+
+    #[test]
+    fn test_duplicate_transactions() {
+        let mut transactions = generate_random_transactions(3);
+        transactions.push(transactions[2].clone());
+
+        let block = Block::new(transactions.clone(), hex::encode(vec![]), 1);
+
+        assert!(!block.validate(transactions));
+    }
+
+    #[test]
+    fn test_legacy_block() {
+        let transactions = generate_random_transactions(5);
+        let block = Block::new(transactions.clone(), hex::encode(vec![]), 1);
+
+        let mut json: serde_json::Value = serde_json::from_str(&block.serialize_block()).unwrap();
+        json.as_object_mut().unwrap().remove("merkle_scheme");
+        let legacy = Block::deserialize_block(&json.to_string());
+
+        assert_eq!(legacy.header.merkle_scheme, MerkleScheme::Legacy);
+        assert!(!legacy.validate(transactions.clone()));
+        assert_ne!(legacy.hash().unwrap(), block.hash().unwrap());
+    }
+
+    #[test]
+    fn test_verify_transactions() {
+        let transactions = generate_random_transactions(37);
+        let block = Block::new(transactions.clone(), hex::encode(vec![]), 1);
+
+        let mine = vec![transactions[3].clone(), transactions[20].clone(), transactions[36].clone()];
+        let proof = block.prove_transactions(&mine).unwrap();
+
+        assert_eq!(block.verify_transactions(&proof, &mine), Ok(true));
+        assert_eq!(block.verify_transactions(&proof, &transactions[..3]), Ok(false));
+        assert!(block.prove_transactions(&generate_random_transactions(1)).is_err());
+
+        let mut unreadable = block.clone();
+        unreadable.header.merkle_root = String::from("not hex");
+        assert_eq!(unreadable.verify_transactions(&proof, &mine), Err(ProofError::InvalidRoot));
+    }
+
+    #[test]
+    fn test_chain_inclusion() {
+        let mut blockchain = BlockChain::new();
+        let mut blocks = vec![];
+
+        for _ in 0..6 {
+            let block = blockchain.new_block(generate_signed_transactions(5), String::new(), String::new(), 0);
+            blockchain.add_block(block.clone());
+            blocks.push(block);
+        }
+
+        assert!(blockchain.verify_chain().is_ok());
+
+        let tx = blocks[1].transactions[3].clone();
+        let tip = blockchain.headers[5].header.clone();
+        let proof = blockchain.prove_inclusion(&tx.id, 2, 5).unwrap();
+
+        assert_eq!(proof.header, blocks[1].header);
+        assert!(BlockChain::verify_inclusion(&proof, &tx, &tip));
+        assert!(!BlockChain::verify_inclusion(&proof, &blocks[1].transactions[2], &tip));
+        assert!(!BlockChain::verify_inclusion(&proof, &tx, &blockchain.headers[6].header));
+
+        assert!(blockchain.prove_inclusion(&tx.id, 2, 2).is_none());
+        assert!(blockchain.prove_inclusion(&tx.id, 3, 5).is_none());
+    }
+
+    #[test]
+    fn test_mmr_rebuilt_on_deserialize() {
+        let mut blockchain = BlockChain::new();
+        for _ in 0..3 {
+            let block = blockchain.new_block(generate_signed_transactions(2), String::new(), String::new(), 0);
+            blockchain.add_block(block);
+        }
+
+        let restored = BlockChain::deserialize(&blockchain.serialize());
+
+        assert_eq!(restored.mmr.root(), blockchain.mmr.root());
+        assert!(restored.verify_chain().is_ok());
+    }
+
+    #[test]
+    fn test_headers_stored_apart_from_bodies() {
+        let mut blockchain = BlockChain::new();
+        for _ in 0..3 {
+            let block = blockchain.new_block(generate_signed_transactions(2), String::new(), String::new(), 0);
+            blockchain.add_block(block);
+        }
+        assert_eq!(blockchain.headers.len(), blockchain.bodies.len());
+        assert_eq!(blockchain.body(2), Some(&blockchain.bodies[2]));
+
+        // Saved chains keep the whole blocks, so files written before the split still load
+        let saved: serde_json::Value = serde_json::from_str(&blockchain.serialize()).unwrap();
+        assert_eq!(saved["chain"][2]["hash"], blockchain.headers[2].hash);
+        assert_eq!(saved["chain"][2]["transactions"].as_array().unwrap().len(), 2);
+
+        let restored = BlockChain::deserialize(&blockchain.serialize());
+        assert_eq!(restored.headers, blockchain.headers);
+        assert_eq!(restored.bodies, blockchain.bodies);
+        assert_eq!(restored.block_by_height(2), blockchain.block_by_height(2));
+    }
+
+    #[test]
+    fn test_wrong_mmr_root() {
+        let mut blockchain = BlockChain::new();
+        let mut block = blockchain.new_block(generate_signed_transactions(2), String::new(), String::new(), 0);
+        block.header.mmr_root = blockchain.headers[0].hash.clone();
+        block.hash = hex::encode(block.hash().unwrap());
+        blockchain.add_block(block);
+
+        assert_eq!(blockchain.verify_chain(), Err(ChainError { height: 1, fault: ChainFault::MmrRoot }));
+    }
+
+    #[test]
+    fn test_account_at_height() {
+        let alice = generate_key_pair();
+        let alice_id = hex::encode(alice.public_key().as_ref());
+        let balances: HashMap<String, f64> = [(alice_id.as_str(), 100.0), ("bob", 5.0)].iter()
+            .map(|(id, balance)| (id.to_string(), *balance))
+            .collect();
+        let mut blockchain = BlockChain::from_genesis_state(SparseMerkleTree::from_balances(&balances));
+
+        let mut transactions = generate_random_transactions(1);
+        transactions[0].to = String::from("bob");
+        transactions[0].amount = 40.0;
+        transactions[0].fee = 0.4;
+        sign_transaction(&alice, &mut transactions[0]);
+        let block = blockchain.new_block(transactions, String::new(), String::new(), 0);
+        blockchain.add_block(block);
+
+        assert!(blockchain.verify_chain().is_ok());
+
+        let before = blockchain.prove_account("bob", 0).unwrap();
+        let after = blockchain.prove_account("bob", 1).unwrap();
+
+        assert_eq!(before.account.as_ref().unwrap().balance, 5.0);
+        assert_eq!(after.account.as_ref().unwrap().balance, 45.0);
+        assert!(BlockChain::verify_account(&before, &blockchain.headers[0].header));
+        assert!(BlockChain::verify_account(&after, &blockchain.headers[1].header));
+        assert!(!BlockChain::verify_account(&before, &blockchain.headers[1].header));
+        assert!(blockchain.prove_account("bob", 2).is_none());
+
+        let restored = BlockChain::deserialize(&blockchain.serialize());
+        assert_eq!(restored.diffs.len(), 2);
+        assert_eq!(restored.account("bob", 0).unwrap().balance, 5.0);
+        assert_eq!(restored.account("bob", 1).unwrap().balance, 45.0);
+        assert!(restored.verify_chain().is_ok());
+    }
+
+    #[test]
+    fn test_header_hash_covers_fields() {
+        let mut blockchain = BlockChain::new();
+        let block = blockchain.new_block(generate_signed_transactions(3), String::from("node-a"), String::from("ab"), 4);
+
+        assert_eq!(block.header.version, PROTOCOL_VERSION);
+        assert_eq!(block.header.tx_count, 3);
+        assert_eq!(block.hash, hex::encode(block.header.hash().unwrap()));
+
+        let mut proposer = block.header.clone();
+        proposer.proposer = String::from("node-b");
+        assert_ne!(proposer.hash().unwrap(), block.header.hash().unwrap());
+
+        let mut view = block.header.clone();
+        view.view = 5;
+        assert_ne!(view.hash().unwrap(), block.header.hash().unwrap());
+
+        let mut validators = block.header.clone();
+        validators.validator_set_hash = String::from("cd");
+        assert_ne!(validators.hash().unwrap(), block.header.hash().unwrap());
+    }
+
+    #[test]
+    fn test_header_hash_rejects_non_hex() {
+        let mut blockchain = BlockChain::new();
+        let block = blockchain.new_block(generate_signed_transactions(3), String::from("node-a"), String::from("ab"), 4);
+
+        let mut mmr = block.header.clone();
+        mmr.mmr_root = String::from("not hex");
+        assert!(mmr.hash().is_err());
+
+        let mut state = block.header.clone();
+        state.state_root = String::from("not hex");
+        assert!(state.hash().is_err());
+
+        let mut validators = block.header.clone();
+        validators.validator_set_hash = String::from("not hex");
+        assert!(validators.hash().is_err());
+        assert!(Block::from_parts(validators, block.transactions.clone()).is_none());
+
+        let mut legacy = block.header.clone();
+        legacy.version = 0;
+        legacy.prev_hash = String::from("not hex");
+        assert!(legacy.hash().is_err());
+    }
+
+    #[test]
+    fn test_headers_and_bodies() {
+        let mut blockchain = BlockChain::new();
+        let mut blocks = vec![];
+        for _ in 0..4 {
+            let block = blockchain.new_block(generate_signed_transactions(3), String::new(), String::new(), 0);
+            blockchain.add_block(block.clone());
+            blocks.push(block);
+        }
+
+        let headers = blockchain.headers(1, 4);
+        assert_eq!(headers.len(), 3);
+        assert_eq!(blockchain.headers(3, 10).len(), 2);
+        assert!(blockchain.header(5).is_none());
+
+        let rebuilt = Block::from_parts(headers[1].clone(), blockchain.body(2).unwrap().clone()).unwrap();
+        assert_eq!(rebuilt, blocks[1]);
+
+        assert!(Block::from_parts(headers[1].clone(), blocks[2].transactions.clone()).is_none());
+        assert!(Block::from_parts(headers[1].clone(), blocks[1].transactions[..2].to_vec()).is_none());
+    }
+
+    #[test]
+    fn test_version_zero_block() {
+        let mut block = Block::new(generate_random_transactions(3), hex::encode(vec![]), 1);
+        block.header.version = 0;
+        block.header.tx_count = 0;
+        block.hash = hex::encode(block.hash().unwrap());
+
+        let mut json: serde_json::Value = serde_json::from_str(&block.serialize_block()).unwrap();
+        for field in ["version", "proposer", "validator_set_hash", "view", "tx_count"] {
+            json.as_object_mut().unwrap().remove(field);
+        }
+        let old = Block::deserialize_block(&json.to_string());
+
+        assert_eq!(old.header, block.header);
+        assert_eq!(hex::encode(old.hash().unwrap()), old.hash);
+        assert!(old.validate(old.transactions.clone()));
+    }
+
+    fn funded_chain(balance: f64) -> (BlockChain, Ed25519KeyPair) {
+        let key_pair = generate_key_pair();
+        let balances: HashMap<String, f64> = [(hex::encode(key_pair.public_key().as_ref()), balance)].into_iter().collect();
+        (BlockChain::from_genesis_state(SparseMerkleTree::from_balances(&balances)), key_pair)
+    }
+
+    #[test]
+    fn test_verify_chain_reports_height() {
+        let (mut blockchain, key_pair) = funded_chain(10000.0);
+        for _ in 0..4 {
+            let mut transactions = generate_random_transactions(3);
+            for transaction in transactions.iter_mut() {
+                sign_transaction(&key_pair, transaction);
+            }
+            let block = blockchain.new_block(transactions, String::new(), String::new(), 0);
+            blockchain.add_block(block);
+        }
+        assert!(blockchain.verify_chain().is_ok());
+
+        let mut forged = blockchain.clone();
+        forged.bodies[3][1].amount = 1.0;
+        let id = forged.bodies[3][1].id.clone();
+        assert_eq!(forged.verify_chain(), Err(ChainError { height: 3, fault: ChainFault::Signature(id) }));
+
+        let mut dropped = blockchain.clone();
+        dropped.bodies[3].pop();
+        assert_eq!(dropped.verify_chain(), Err(ChainError { height: 3, fault: ChainFault::Body }));
+
+        let mut rehashed = blockchain.clone();
+        rehashed.headers[2].header.timestamp = rehashed.headers[1].header.timestamp - 1;
+        rehashed.headers[2].hash = hex::encode(rehashed.headers[2].header.hash().unwrap());
+        assert_eq!(rehashed.verify_chain(), Err(ChainError { height: 2, fault: ChainFault::Timestamp }));
+
+        let mut relinked = blockchain.clone();
+        relinked.headers[2].header.index = 7;
+        assert_eq!(relinked.verify_chain(), Err(ChainError { height: 2, fault: ChainFault::Hash }));
+        relinked.headers[2].hash = hex::encode(relinked.headers[2].header.hash().unwrap());
+        assert_eq!(relinked.verify_chain(), Err(ChainError { height: 2, fault: ChainFault::Index }));
+
+        let mut reordered = blockchain.clone();
+        reordered.headers.swap(2, 3);
+        reordered.bodies.swap(2, 3);
+        assert_eq!(reordered.verify_chain(), Err(ChainError { height: 2, fault: ChainFault::PrevHash }));
+
+        let mut unhashable = blockchain.clone();
+        unhashable.headers[3].header.prev_hash = String::from("not hex");
+        assert_eq!(unhashable.verify_chain(), Err(ChainError { height: 3, fault: ChainFault::Hash }));
+        let restored = BlockChain::deserialize(&unhashable.serialize());
+        assert_eq!(restored.verify_chain(), Err(ChainError { height: 3, fault: ChainFault::Hash }));
+    }
+
+    #[test]
+    fn test_verify_next() {
+        let (mut blockchain, key_pair) = funded_chain(10000.0);
+        let committee = ValidatorSet::new(1, vec![Validator { node_id: String::from("a"), public_key: String::from("ab"),
+            stake: 1.0, proof_of_possession: String::new() }]);
+        let block = blockchain.new_epoch_block(vec![], String::new(), &committee, 0);
+        assert!(blockchain.verify_next(&block).is_ok());
+        blockchain.add_block(block);
+
+        let mut transactions = generate_random_transactions(2);
+        for transaction in transactions.iter_mut() {
+            sign_transaction(&key_pair, transaction);
+        }
+        let block = blockchain.new_block(transactions, String::new(), committee.hash.clone(), 0);
+        assert!(blockchain.verify_next(&block).is_ok());
+
+        let mut stale = blockchain.new_block(vec![], String::new(), committee.hash.clone(), 0);
+        stale.header.prev_hash = blockchain.headers[0].hash.clone();
+        stale.hash = hex::encode(stale.hash().unwrap());
+        assert_eq!(blockchain.verify_next(&stale), Err(ChainError { height: 2, fault: ChainFault::PrevHash }));
+
+        let mut wrong_state = block.clone();
+        wrong_state.header.state_root = blockchain.headers[1].header.state_root.clone();
+        wrong_state.hash = hex::encode(wrong_state.hash().unwrap());
+        assert_eq!(blockchain.verify_next(&wrong_state), Err(ChainError { height: 2, fault: ChainFault::StateRoot }));
+
+        let outside = blockchain.new_block(vec![], String::new(), String::new(), 0);
+        assert_eq!(blockchain.verify_next(&outside), Err(ChainError { height: 2, fault: ChainFault::ValidatorSet }));
+
+        let mut unhashable = block.clone();
+        unhashable.header.state_root = String::from("not hex");
+        assert_eq!(blockchain.verify_next(&unhashable), Err(ChainError { height: 2, fault: ChainFault::Hash }));
+
+        blockchain.add_block(block);
+        assert!(blockchain.verify_chain().is_ok());
+    }
+
+    #[test]
+    fn test_verify_chain_validator_sets() {
+        let committee = |epoch: u64, node_id: &str| ValidatorSet::new(epoch, vec![Validator {
+            node_id: node_id.to_string(), public_key: String::from("ab"), stake: 1.0, proof_of_possession: String::new() }]);
+        let (first, second) = (committee(0, "a"), committee(1, "b"));
+
+        let mut blockchain = BlockChain::new();
+        let block = blockchain.new_block(vec![], String::new(), String::new(), 0);
+        blockchain.add_block(block);
+        assert!(blockchain.validator_set().is_none());
+
+        for set in [&first, &first, &second, &second] {
+            let block = match blockchain.validator_set() == Some(set) {
+                true => blockchain.new_block(vec![], String::new(), set.hash.clone(), 0),
+                false => blockchain.new_epoch_block(vec![], String::new(), set, 0)
+            };
+            blockchain.add_block(block);
+        }
+        assert_eq!(blockchain.validator_set(), Some(&second));
+        assert!(blockchain.headers[2].header.validator_set.is_some() && blockchain.headers[3].header.validator_set.is_none());
+        assert!(blockchain.verify_chain().is_ok());
+        assert_eq!(BlockChain::deserialize(&blockchain.serialize()).validator_set(), Some(&second));
+
+        let mut wrong_set = blockchain.clone();
+        wrong_set.headers[5].header.validator_set_hash = first.hash.clone();
+        wrong_set.headers[5].hash = hex::encode(wrong_set.headers[5].header.hash().unwrap());
+        assert_eq!(wrong_set.verify_chain(), Err(ChainError { height: 5, fault: ChainFault::ValidatorSet }));
+
+        let mut swapped = blockchain.clone();
+        swapped.headers[4].header.validator_set = Some(committee(1, "c"));
+        assert_eq!(swapped.verify_chain(), Err(ChainError { height: 4, fault: ChainFault::ValidatorSet }));
+
+        let mut rewound = blockchain.clone();
+        let old = committee(0, "b");
+        rewound.headers[4].header.validator_set_hash = old.hash.clone();
+        rewound.headers[4].header.validator_set = Some(old);
+        rewound.headers[4].hash = hex::encode(rewound.headers[4].header.hash().unwrap());
+        assert_eq!(rewound.verify_chain(), Err(ChainError { height: 4, fault: ChainFault::ValidatorSet }));
+    }
+
+    #[test]
+    fn test_verify_chain_signatures_and_balances() {
+        let (mut blockchain, key_pair) = funded_chain(100.0);
+
+        let mut unsigned = generate_random_transactions(1);
+        unsigned[0].from = hex::encode(key_pair.public_key().as_ref());
+        let id = unsigned[0].id.clone();
+        let mut bad_signature = blockchain.clone();
+        let block = bad_signature.new_block(unsigned, String::new(), String::new(), 0);
+        bad_signature.add_block(block);
+        assert_eq!(bad_signature.verify_chain(), Err(ChainError { height: 1, fault: ChainFault::Signature(id) }));
+
+        let mut overdraft = generate_random_transactions(1);
+        sign_transaction(&key_pair, &mut overdraft[0]);
+        let id = overdraft[0].id.clone();
+        let block = blockchain.new_block(overdraft, String::new(), String::new(), 0);
+        blockchain.add_block(block);
+        let err = blockchain.verify_chain().unwrap_err();
+        assert_eq!(err, ChainError { height: 1, fault: ChainFault::Balance(id.clone()) });
+        assert_eq!(err.to_string(), format!("block 1: transaction {} overdraws its sender", id));
+    }
+
+    #[test]
+    fn test_verify_chain_nonces() {
+        let (mut blockchain, key_pair) = funded_chain(10000.0);
+
+        let mut transactions = generate_random_transactions(2);
+        transactions[0].nonce = Some(1);
+        transactions[1].nonce = Some(3);
+        for transaction in transactions.iter_mut() {
+            sign_transaction(&key_pair, transaction);
+        }
+        let id = transactions[1].id.clone();
+        let block = blockchain.new_block(transactions, String::new(), String::new(), 0);
+        blockchain.add_block(block);
+
+        assert_eq!(blockchain.verify_chain(), Err(ChainError { height: 1, fault: ChainFault::Nonce(id) }));
+    }
+
+    #[test]
+    fn test_chain_index() {
+        let (mut blockchain, key_pair) = funded_chain(10000.0);
+        let sender = hex::encode(key_pair.public_key().as_ref());
+
+        for _ in 0..3 {
+            let mut transactions = generate_random_transactions(4);
+            transactions[2].to = String::from("bob");
+            for transaction in transactions.iter_mut() {
+                sign_transaction(&key_pair, transaction);
+            }
+            let block = blockchain.new_block(transactions, String::new(), String::new(), 0);
+            blockchain.add_block(block);
+        }
+
+        let block = blockchain.block_by_height(2).unwrap();
+        assert_eq!(blockchain.block_by_hash(&block.hash), Some(block.clone()));
+        assert!(blockchain.block_by_hash("missing").is_none());
+
+        let tx = block.transactions[3].clone();
+        let receipt = blockchain.transaction(&tx.id).unwrap();
+        let root = hex::decode(&block.header.merkle_root).unwrap();
+        assert_eq!(receipt.height, 2);
+        assert_eq!(receipt.block_hash, block.hash);
+        assert_eq!(receipt.transaction, tx);
+        assert!(MerkleTree::validate_proof(&receipt.proof, tx, &root, block.header.merkle_scheme));
+        assert!(blockchain.transaction("missing").is_none());
+
+        let history = blockchain.account_history("bob");
+        assert!(history.sent.is_empty());
+        assert_eq!(history.received.iter().map(|(height, _)| *height).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(blockchain.account_history(&sender).sent.len(), 12);
+
+        let restored = BlockChain::deserialize(&blockchain.serialize());
+        assert_eq!(restored.index.by_transaction, blockchain.index.by_transaction);
+        assert_eq!(restored.account_history("bob").received.len(), 3);
     }
-}
\ No newline at end of file
+}
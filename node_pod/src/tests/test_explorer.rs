@@ -0,0 +1,93 @@
+#[cfg(test)]
+mod tests {
+    use crate::definitions::transaction_header::Transaction;
+    use crate::definitions::block_header::{BlockChain, BlockChainMethods};
+    use crate::definitions::state_header::{SparseMerkleTree, StateMethods};
+    use crate::explorer::{run, InclusionBundle};
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+    use std::collections::HashMap;
+
+    fn signed_chain() -> BlockChain {
+        let key_pair = Ed25519KeyPair::from_pkcs8(Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap().as_ref()).unwrap();
+        let sender = hex::encode(key_pair.public_key().as_ref());
+        let balances: HashMap<String, f64> = [(sender.clone(), 1000.0)].into_iter().collect();
+        let mut chain = BlockChain::from_genesis_state(SparseMerkleTree::from_balances(&balances));
+
+        for height in 1..4 {
+            let mut transactions = vec![];
+            for i in 0..3 {
+                let mut tx = Transaction {
+                    id: format!("{:064x}", 10 * height + i),
+                    from: sender.clone(),
+                    to: String::from("bob"),
+                    timestamp: 0,
+                    amount: 10.0,
+                    fee: 0.1,
+                    signature: String::new(),
+                    governance: None,
+                    nonce: None,
+                };
+                let msg = serde_json::to_string_pretty(&tx).unwrap();
+                tx.signature = hex::encode(key_pair.sign(msg.as_bytes()).as_ref());
+                transactions.push(tx);
+            }
+            let block = chain.new_block(transactions, String::new(), String::new(), 0);
+            chain.add_block(block);
+        }
+        chain
+    }
+
+    fn output(chain: &BlockChain, command: &[&str]) -> Result<String, String> {
+        let command: Vec<String> = command.iter().map(|arg| arg.to_string()).collect();
+        let mut out = Vec::new();
+        run(chain, &command, &mut out).map(|_| String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_listing_and_lookups() {
+        let chain = signed_chain();
+
+        assert_eq!(output(&chain, &["headers"]).unwrap().lines().count(), 4);
+        assert_eq!(output(&chain, &["headers", "1", "3"]).unwrap().lines().next().unwrap().split(' ').nth(1),
+            Some(chain.headers[1].hash.as_str()));
+        assert_eq!(output(&chain, &["verify"]).unwrap(), "Chain of 4 blocks is valid\n");
+
+        let receipt: serde_json::Value = serde_json::from_str(&output(&chain, &["tx", &format!("{:064x}", 21)]).unwrap()).unwrap();
+        assert_eq!(receipt["height"], 2);
+        assert!(output(&chain, &["tx", "missing"]).is_err());
+
+        let account: serde_json::Value = serde_json::from_str(&output(&chain, &["account", "bob"]).unwrap()).unwrap();
+        assert_eq!(account["account"]["balance"], 90.0);
+        assert_eq!(account["history"]["received"].as_array().unwrap().len(), 9);
+
+        let lines: Vec<String> = output(&chain, &["export", "2"]).unwrap().lines().map(String::from).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&lines[0]).unwrap()["hash"], chain.headers[2].hash);
+
+        assert!(output(&chain, &["headers", "x"]).is_err());
+        assert!(output(&chain, &["unknown"]).is_err());
+    }
+
+    #[test]
+    fn test_prove_and_check() {
+        let chain = signed_chain();
+        let tx_id = format!("{:064x}", 12);
+
+        let json = output(&chain, &["prove", &tx_id]).unwrap();
+        let bundle: InclusionBundle = serde_json::from_str(&json).unwrap();
+        assert_eq!(bundle.tip, 3);
+        assert!(output(&chain, &["prove", &tx_id, "1"]).is_err());
+
+        let path = std::env::temp_dir().join(format!("explorer-proof-{}.json", std::process::id()));
+        std::fs::write(&path, &json).unwrap();
+        assert_eq!(output(&chain, &["check", path.to_str().unwrap()]).unwrap(), format!("Transaction {} is in block 1\n", tx_id));
+
+        let mut forged = bundle.clone();
+        forged.transaction.id = format!("{:064x}", 13);
+        std::fs::write(&path, serde_json::to_string(&forged).unwrap()).unwrap();
+        assert!(output(&chain, &["check", path.to_str().unwrap()]).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
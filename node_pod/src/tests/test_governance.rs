@@ -0,0 +1,180 @@
+#[cfg(test)]
+mod tests {
+    use crate::definitions::transaction_header::{Transaction, TransactionMethods};
+    use crate::definitions::block_header::{BlockChain, BlockChainMethods, ChainError, ChainFault};
+    use crate::definitions::consensus_header::{Validator, ValidatorSet, ValidatorSetMethods};
+    use crate::definitions::governance_header::{Governance, GovernanceAction, GovernanceMethods, GovernanceTransactionMethods,
+        Parameter, Parameters, ProposalMethods};
+    use crate::definitions::signer_header::{LocalSigner, LocalSignerMethods, Signer};
+    use crate::definitions::state_header::{SparseMerkleTree, StateMethods};
+    use crate::governance::electorate;
+    use bls_signatures::{PrivateKey, Serialize};
+    use std::collections::BTreeMap;
+
+    fn committee(stakes: &[f64]) -> (Vec<LocalSigner>, Vec<Validator>) {
+        let signers: Vec<LocalSigner> = stakes.iter().map(|_| LocalSigner::new(PrivateKey::generate(&mut rand::thread_rng()))).collect();
+        let validators = signers.iter().zip(stakes).map(|(signer, stake)| {
+            let public_key = hex::encode(signer.public_key().as_bytes());
+            Validator { node_id: public_key.clone(), public_key, stake: *stake, proof_of_possession: String::new() }
+        }).collect();
+        (signers, validators)
+    }
+
+    fn propose(signer: &LocalSigner, validators: &[Validator], activation_height: u64) -> Transaction {
+        let action = GovernanceAction::Propose { parameter: Parameter::BlockSize, value: 128.0, activation_height,
+            electorate: electorate(validators) };
+        Transaction::governance(signer, action, 1).unwrap()
+    }
+
+    fn vote(signer: &LocalSigner, proposal: &str, approve: bool, timestamp: u64) -> Transaction {
+        let action = GovernanceAction::Vote { proposal: proposal.to_string(), approve };
+        Transaction::governance(signer, action, timestamp).unwrap()
+    }
+
+    fn stakes(members: &[(&str, f64)]) -> BTreeMap<String, f64> {
+        members.iter().map(|(id, stake)| (id.to_string(), *stake)).collect()
+    }
+
+    #[test]
+    fn test_proposal_activates() {
+        let (signers, validators) = committee(&[3.0, 3.0, 2.0, 2.0]);
+        let mut chain = BlockChain::new();
+
+        let proposal = propose(&signers[0], &validators, 3);
+        assert!(proposal.check_governance(&validators));
+        let set = ValidatorSet::new(1, validators);
+        let block = chain.new_epoch_block(vec![proposal.clone()], String::new(), &set, 0);
+        chain.add_block(block);
+        assert_eq!(chain.governance().proposals.len(), 1);
+        assert_eq!(chain.governance().parameters, Parameters::default());
+
+        // 3 + 3 is not more than two thirds of 10, the third vote carries it
+        let votes = vec![vote(&signers[1], &proposal.id, true, 2), vote(&signers[2], &proposal.id, true, 3)];
+        let block = chain.new_block(votes, String::new(), set.hash.clone(), 0);
+        chain.add_block(block);
+
+        assert!(chain.governance().proposals.is_empty());
+        assert_eq!(chain.governance().parameters.block_size, Some(128));
+        assert!(chain.verify_chain().is_ok());
+    }
+
+    #[test]
+    fn test_chain_rejects_ballots() {
+        let (signers, validators) = committee(&[3.0, 3.0, 2.0, 2.0]);
+        let (outsider, outsider_set) = committee(&[10.0]);
+        let set = ValidatorSet::new(1, validators);
+
+        // A lone outsider declaring itself the electorate, or a member inflating its own stake
+        let forged = propose(&outsider[0], &outsider_set, 3);
+        let mut inflated = set.validators.clone();
+        inflated[0].stake = 10.0;
+        for ballot in [forged, propose(&signers[0], &inflated, 3)] {
+            let mut chain = BlockChain::new();
+            let block = chain.new_epoch_block(vec![ballot.clone()], String::new(), &set, 0);
+            chain.add_block(block);
+            assert_eq!(chain.verify_chain(), Err(ChainError { height: 1, fault: ChainFault::Governance(ballot.id) }));
+        }
+
+        // Without a committee on chain there is nothing to check ballots against
+        let mut chain = BlockChain::new();
+        let block = chain.new_block(vec![propose(&signers[0], &set.validators, 3)], String::new(), String::new(), 0);
+        chain.add_block(block);
+        assert!(matches!(chain.verify_chain(), Err(ChainError { fault: ChainFault::Governance(_), .. })));
+    }
+
+    #[test]
+    fn test_proposal_lapses() {
+        let (signers, validators) = committee(&[3.0, 3.0, 2.0, 2.0]);
+        let mut chain = BlockChain::new();
+
+        let proposal = propose(&signers[0], &validators, 2);
+        let block = chain.new_block(vec![proposal.clone(), vote(&signers[1], &proposal.id, true, 2)],
+            String::new(), String::new(), 0);
+        chain.add_block(block);
+
+        assert!(chain.governance().proposals.is_empty());
+        assert_eq!(chain.governance().parameters, Parameters::default());
+
+        // A vote after the activation height has nothing to count towards
+        let late = vote(&signers[2], &proposal.id, true, 3);
+        let mut governance = chain.governance().clone();
+        assert!(!governance.apply_action(&late.from, &late.id, late.governance.as_ref().unwrap()));
+    }
+
+    #[test]
+    fn test_vote_replaced() {
+        let mut governance = Governance::default();
+        let action = GovernanceAction::Propose { parameter: Parameter::MinFee, value: 0.5, activation_height: 1,
+            electorate: stakes(&[("a", 4.0), ("b", 2.0)]) };
+        assert!(governance.apply_action("a", "p", &action));
+        assert!(governance.apply_action("b", "p", &GovernanceAction::Vote { proposal: String::from("p"), approve: true }));
+        assert!(governance.apply_action("b", "p", &GovernanceAction::Vote { proposal: String::from("p"), approve: false }));
+
+        assert_eq!(governance.activate(1), vec![]);
+        assert_eq!(governance.parameters.min_fee, None);
+
+        let invalid = GovernanceAction::Propose { parameter: Parameter::BlockSize, value: 0.5, activation_height: 1,
+            electorate: stakes(&[("a", 4.0), ("b", 2.0)]) };
+        assert!(!governance.apply_action("a", "q", &invalid));
+        let unstaked = GovernanceAction::Propose { parameter: Parameter::BlockSize, value: 64.0, activation_height: 1,
+            electorate: stakes(&[("a", 4.0), ("b", 0.0)]) };
+        assert!(!governance.apply_action("a", "q", &unstaked));
+        let outside = GovernanceAction::Propose { parameter: Parameter::BlockSize, value: 64.0, activation_height: 1,
+            electorate: stakes(&[("b", 2.0)]) };
+        assert!(!governance.apply_action("a", "q", &outside));
+    }
+
+    #[test]
+    fn test_votes_weighed_by_electorate() {
+        let mut governance = Governance::default();
+        let action = GovernanceAction::Propose { parameter: Parameter::MinFee, value: 0.5, activation_height: 5,
+            electorate: stakes(&[("a", 1.0), ("b", 1.0), ("c", 10.0)]) };
+        assert!(governance.apply_action("a", "p", &action));
+
+        // Later committees may stake differently, their members count with their stake in the electorate
+        let approve = GovernanceAction::Vote { proposal: String::from("p"), approve: true };
+        assert!(governance.apply_action("b", "p", &approve));
+        assert!(!governance.apply_action("d", "p", &approve));
+        assert_eq!(governance.proposals["p"].approving_stake(), 2.0);
+        assert_eq!(governance.proposals["p"].electorate_stake(), 12.0);
+        assert!(!governance.proposals["p"].passed());
+
+        assert!(governance.apply_action("c", "p", &approve));
+        assert!(governance.proposals["p"].passed());
+    }
+
+    #[test]
+    fn test_check_governance() {
+        let (signers, validators) = committee(&[3.0, 3.0, 2.0, 2.0]);
+        let (outsider, _) = committee(&[1.0]);
+
+        assert!(propose(&signers[0], &validators, 3).check_governance(&validators));
+        assert!(!propose(&signers[0], &validators[..3], 3).check_governance(&validators));
+        assert!(!propose(&outsider[0], &validators, 3).check_governance(&validators));
+        assert!(vote(&signers[1], "p", true, 2).check_governance(&validators));
+        assert!(!vote(&outsider[0], "p", true, 2).check_governance(&validators));
+
+        let mut tampered = vote(&signers[1], "p", true, 2);
+        tampered.governance = Some(GovernanceAction::Vote { proposal: String::from("p"), approve: false });
+        tampered.id = tampered.compute_id();
+        assert!(!tampered.check_governance(&validators));
+
+        let mut paying = vote(&signers[1], "p", true, 2);
+        paying.amount = 1.0;
+        assert!(!paying.check_governance(&validators));
+    }
+
+    #[test]
+    fn test_state_proof_with_governance() {
+        let balances = [(String::from("alice"), 10.0)].into_iter().collect();
+        let mut state = SparseMerkleTree::from_balances(&balances);
+        let before = state.root();
+
+        state.governance.parameters.min_fee = Some(0.1);
+        assert_ne!(state.root(), before);
+
+        let proof = state.generate_proof("alice");
+        assert!(SparseMerkleTree::validate_proof(&proof, &state.root()));
+        assert!(!SparseMerkleTree::validate_proof(&proof, &before));
+    }
+}
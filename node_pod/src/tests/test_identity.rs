@@ -0,0 +1,56 @@
+#[cfg(test)]
+mod tests {
+    use crate::definitions::identity_header::{IdentityError, IdentityMethods, KeyFile};
+    use crate::definitions::node_header::{Node, NodeMethods};
+    use bls_signatures::{PrivateKey, Serialize};
+
+    fn key_path(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("identity-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.join("node.key").to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_identity_survives_restart() {
+        let path = key_path("restart");
+
+        let first = Node::from_key(KeyFile::load_or_create(&path, None).unwrap());
+        let second = Node::from_key(KeyFile::load_or_create(&path, None).unwrap());
+        assert_eq!(first.id, second.id);
+
+        std::fs::remove_file(&path).unwrap();
+        let third = Node::from_key(KeyFile::load_or_create(&path, None).unwrap());
+        assert_ne!(first.id, third.id);
+    }
+
+    #[test]
+    fn test_sealed_key() {
+        let path = key_path("sealed");
+
+        let key = KeyFile::load_or_create(&path, Some("secret")).unwrap();
+        let json = std::fs::read_to_string(&path).unwrap();
+        assert!(!json.contains(&hex::encode(key.as_bytes())));
+
+        assert_eq!(KeyFile::load(&path, Some("secret")).unwrap().as_bytes(), key.as_bytes());
+        assert_eq!(KeyFile::load(&path, Some("wrong")).unwrap_err(), IdentityError::Passphrase);
+        assert_eq!(KeyFile::load(&path, None).unwrap_err(), IdentityError::Passphrase);
+    }
+
+    #[test]
+    fn test_tampered_key_file() {
+        let key = PrivateKey::generate(&mut rand::thread_rng());
+        let other = PrivateKey::generate(&mut rand::thread_rng());
+
+        let mut plain = KeyFile::seal(&key, None);
+        plain.node_id = hex::encode(other.public_key().as_bytes());
+        assert_eq!(plain.open(None).unwrap_err(), IdentityError::Mismatch);
+
+        // The id is bound to the ciphertext, so moving a sealed key under another id fails to open
+        let mut sealed = KeyFile::seal(&key, Some("secret"));
+        sealed.node_id = hex::encode(other.public_key().as_bytes());
+        assert_eq!(sealed.open(Some("secret")).unwrap_err(), IdentityError::Passphrase);
+
+        plain.key = String::from("zz");
+        assert!(matches!(plain.open(None), Err(IdentityError::Format(_))));
+    }
+}
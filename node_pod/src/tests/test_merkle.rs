@@ -1,7 +1,7 @@
 #[cfg(test)]
 mod tests {
     use crate::definitions::transaction_header::Transaction;
-    use crate::definitions::merkle_header::{MerkleTree, MerkleMethods};
+    use crate::definitions::merkle_header::{MerkleTree, MerkleScheme, Proof, ProofError, MerkleMethods};
     use rand::{distributions::DistString, thread_rng, Rng};
     use rand::distributions::{Uniform, Alphanumeric};
     use std::time::UNIX_EPOCH;
@@ -30,6 +30,8 @@ mod tests {
                 amount,
                 fee: 0.01 * amount,
                 signature: sig,
+                governance: None,
+                nonce: None,
             };
             transactions.push(tx);
         }
@@ -40,7 +42,7 @@ mod tests {
     fn test_root() {
         let transactions = generate_random_transactions(128);
 
-        let root = MerkleTree::generate_root(&transactions);
+        let root = MerkleTree::generate_root(&transactions, MerkleScheme::Tagged);
         assert!(!root.is_empty());
     }
 
@@ -55,7 +57,7 @@ mod tests {
     #[test]
     fn test_new() {
         let transactions = generate_random_transactions(128);
-        let tree = MerkleTree::new(&transactions);
+        let tree = MerkleTree::new(&transactions, MerkleScheme::Tagged);
 
         assert_eq!(tree.leaves.len(), transactions.len());
 
@@ -65,13 +67,13 @@ mod tests {
     #[test]
     fn test_generate_proof_and_validate() {
         let transactions = generate_random_transactions(128);
-        let tree = MerkleTree::new(&transactions);
+        let tree = MerkleTree::new(&transactions, MerkleScheme::Tagged);
 
         let proof = tree.generate_proof(&transactions[0]);
 
-        let generated_root = MerkleTree::generate_root(&transactions);
+        let generated_root = MerkleTree::generate_root(&transactions, MerkleScheme::Tagged);
 
-        let is_valid = MerkleTree::validate_proof(&proof, transactions[0].clone(), &generated_root);
+        let is_valid = MerkleTree::validate_proof(&proof, transactions[0].clone(), &generated_root, MerkleScheme::Tagged);
         assert!(is_valid);
     }
 
@@ -79,8 +81,163 @@ mod tests {
     fn test_generate_root_consistency() {
         let transactions1 = generate_random_transactions(128);
         let transactions2 = transactions1.clone();
-        let root1 = MerkleTree::generate_root(&transactions1);
-        let root2 = MerkleTree::generate_root(&transactions2);
+        let root1 = MerkleTree::generate_root(&transactions1, MerkleScheme::Tagged);
+        let root2 = MerkleTree::generate_root(&transactions2, MerkleScheme::Tagged);
         assert_eq!(root1, root2);
     }
+
+    #[test]
+    fn test_root_arbitrary_sizes() {
+        for n in [1, 3, 5, 100, 129] {
+            let transactions = generate_random_transactions(n);
+            let tree = MerkleTree::new(&transactions, MerkleScheme::Tagged);
+
+            assert_eq!(tree.merkle_root, MerkleTree::generate_root(&transactions, MerkleScheme::Tagged));
+        }
+    }
+
+    #[test]
+    fn test_empty_root() {
+        let tree = MerkleTree::new(&[], MerkleScheme::Tagged);
+
+        assert!(tree.merkle_root.is_empty());
+        assert!(MerkleTree::generate_root(&[], MerkleScheme::Tagged).is_empty());
+    }
+
+    #[test]
+    fn test_proofs_arbitrary_sizes() {
+        for n in [1, 2, 3, 5, 7, 100, 129] {
+            let transactions = generate_random_transactions(n);
+            let tree = MerkleTree::new(&transactions, MerkleScheme::Tagged);
+
+            for tx in &transactions {
+                let proof = tree.generate_proof(tx);
+                assert!(MerkleTree::validate_proof(&proof, tx.clone(), &tree.merkle_root, tree.scheme));
+            }
+        }
+    }
+
+    #[test]
+    fn test_tampered_proof() {
+        let transactions = generate_random_transactions(6);
+        let tree = MerkleTree::new(&transactions, MerkleScheme::Tagged);
+
+        let mut proof = tree.generate_proof(&transactions[4]);
+        proof.right_sibling[0] = !proof.right_sibling[0];
+        assert!(!MerkleTree::validate_proof(&proof, transactions[4].clone(), &tree.merkle_root, tree.scheme));
+
+        let proof = tree.generate_proof(&transactions[4]);
+        assert!(!MerkleTree::validate_proof(&proof, transactions[3].clone(), &tree.merkle_root, tree.scheme));
+    }
+
+    #[test]
+    fn test_legacy_proofs() {
+        for n in [1, 3, 6, 129] {
+            let transactions = generate_random_transactions(n);
+            let tree = MerkleTree::new(&transactions, MerkleScheme::Legacy);
+
+            for tx in &transactions {
+                let proof = tree.generate_proof(tx);
+                assert!(MerkleTree::validate_proof(&proof, tx.clone(), &tree.merkle_root, MerkleScheme::Legacy));
+                assert!(!MerkleTree::validate_proof(&proof, tx.clone(), &tree.merkle_root, MerkleScheme::Tagged));
+            }
+        }
+    }
+
+    #[test]
+    fn test_duplicated_last_leaf() {
+        let transactions = generate_random_transactions(3);
+        let mut padded = transactions.clone();
+        padded.push(transactions[2].clone());
+
+        let legacy = MerkleTree::generate_root(&transactions, MerkleScheme::Legacy);
+        assert_eq!(legacy, MerkleTree::generate_root(&padded, MerkleScheme::Legacy));
+
+        let tagged = MerkleTree::generate_root(&transactions, MerkleScheme::Tagged);
+        assert_ne!(tagged, MerkleTree::generate_root(&padded, MerkleScheme::Tagged));
+    }
+
+    #[test]
+    fn test_internal_node_as_leaf() {
+        let transactions = generate_random_transactions(4);
+        let tree = MerkleTree::new(&transactions, MerkleScheme::Tagged);
+
+        // The first internal node presented as a leaf with the upper half as its sibling
+        let mut forged = transactions[0].clone();
+        forged.id = hex::encode(&tree.nodes[4]);
+        let proof = Proof { path: vec![tree.nodes[5].clone()], right_sibling: vec![true], leaf_index: 0 };
+
+        assert!(!MerkleTree::validate_proof(&proof, forged, &tree.merkle_root, MerkleScheme::Tagged));
+    }
+
+    #[test]
+    fn test_multi_proof() {
+        for scheme in [MerkleScheme::Tagged, MerkleScheme::Legacy] {
+            for n in [1, 2, 7, 100, 129] {
+                let transactions = generate_random_transactions(n);
+                let tree = MerkleTree::new(&transactions, scheme);
+
+                let subset: Vec<Transaction> = transactions.iter().step_by(3).cloned().collect();
+                let proof = tree.generate_multi_proof(&subset).unwrap();
+
+                assert!(MerkleTree::validate_multi_proof(&proof, &subset, &tree.merkle_root, scheme));
+
+                let all = tree.generate_multi_proof(&transactions).unwrap();
+                assert!(all.hashes.is_empty());
+                assert!(MerkleTree::validate_multi_proof(&all, &transactions, &tree.merkle_root, scheme));
+            }
+        }
+    }
+
+    #[test]
+    fn test_multi_proof_shares_siblings() {
+        let transactions = generate_random_transactions(64);
+        let tree = MerkleTree::new(&transactions, MerkleScheme::Tagged);
+
+        let subset = transactions[..8].to_vec();
+        let proof = tree.generate_multi_proof(&subset).unwrap();
+        let single_total: usize = subset.iter().map(|tx| tree.generate_proof(tx).path.len()).sum();
+
+        assert_eq!(proof.hashes.len(), 3);
+        assert!(proof.hashes.len() < single_total);
+    }
+
+    #[test]
+    fn test_tampered_multi_proof() {
+        let transactions = generate_random_transactions(10);
+        let tree = MerkleTree::new(&transactions, MerkleScheme::Tagged);
+
+        let subset = vec![transactions[1].clone(), transactions[6].clone()];
+        let proof = tree.generate_multi_proof(&subset).unwrap();
+
+        let swapped = vec![transactions[6].clone(), transactions[1].clone()];
+        assert!(!MerkleTree::validate_multi_proof(&proof, &swapped, &tree.merkle_root, MerkleScheme::Tagged));
+
+        let mut extra = proof.clone();
+        extra.hashes.push(tree.nodes[0].clone());
+        assert!(!MerkleTree::validate_multi_proof(&extra, &subset, &tree.merkle_root, MerkleScheme::Tagged));
+
+        let mut duplicate = proof.clone();
+        duplicate.leaf_indices[1] = 1;
+        assert!(!MerkleTree::validate_multi_proof(&duplicate, &subset, &tree.merkle_root, MerkleScheme::Tagged));
+    }
+
+    #[test]
+    fn test_multi_proof_targets() {
+        let transactions = generate_random_transactions(10);
+        let tree = MerkleTree::new(&transactions, MerkleScheme::Tagged);
+
+        let repeated = vec![transactions[1].clone(), transactions[6].clone(), transactions[1].clone()];
+        let proof = tree.generate_multi_proof(&repeated).unwrap();
+        assert_eq!(proof.leaf_indices, vec![1, 6]);
+        assert!(MerkleTree::validate_multi_proof(&proof, &repeated, &tree.merkle_root, MerkleScheme::Tagged));
+        assert!(MerkleTree::validate_multi_proof(&proof, &repeated[..2], &tree.merkle_root, MerkleScheme::Tagged));
+
+        let outside = generate_random_transactions(1);
+        assert_eq!(tree.generate_multi_proof(&outside).unwrap_err(), ProofError::NotInTree(outside[0].id.clone()));
+
+        let mut malformed = transactions[2].clone();
+        malformed.id = String::from("not hex");
+        assert_eq!(tree.generate_multi_proof(&[malformed]).unwrap_err(), ProofError::InvalidId(String::from("not hex")));
+    }
 }
\ No newline at end of file
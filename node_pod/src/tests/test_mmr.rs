@@ -0,0 +1,92 @@
+#[cfg(test)]
+mod tests {
+    use crate::definitions::mmr_header::{MerkleMountainRange, MmrMethods};
+    use rand::{thread_rng, Rng};
+
+    fn generate_random_hashes(n: usize) -> Vec<Vec<u8>> {
+        let mut rng = thread_rng();
+        (0..n).map(|_| (0..32).map(|_| rng.gen::<u8>()).collect()).collect()
+    }
+
+    #[test]
+    fn test_empty_root() {
+        let mmr = MerkleMountainRange::new();
+
+        assert_eq!(mmr.leaf_count, 0);
+        assert!(mmr.root().is_empty());
+    }
+
+    #[test]
+    fn test_append_size() {
+        let mut mmr = MerkleMountainRange::new();
+
+        for (i, leaf) in generate_random_hashes(11).iter().enumerate() {
+            mmr.append(leaf);
+            let leaves = i + 1;
+            assert_eq!(mmr.nodes.len(), 2 * leaves - leaves.count_ones() as usize);
+        }
+    }
+
+    #[test]
+    fn test_root_at_prefix() {
+        let leaves = generate_random_hashes(20);
+        let mut mmr = MerkleMountainRange::new();
+        let mut roots = vec![];
+
+        for leaf in &leaves {
+            mmr.append(leaf);
+            roots.push(mmr.root());
+        }
+
+        for (i, root) in roots.iter().enumerate() {
+            assert_eq!(mmr.root_at(i + 1), *root);
+        }
+    }
+
+    #[test]
+    fn test_generate_proof_and_validate() {
+        let leaves = generate_random_hashes(37);
+        let mut mmr = MerkleMountainRange::new();
+        for leaf in &leaves {
+            mmr.append(leaf);
+        }
+
+        for leaf_count in [1, 2, 3, 8, 21, 37] {
+            let root = mmr.root_at(leaf_count);
+            for (i, leaf) in leaves.iter().enumerate().take(leaf_count) {
+                let proof = mmr.generate_proof(i, leaf_count).unwrap();
+                assert!(MerkleMountainRange::validate_proof(&proof, leaf, &root));
+            }
+        }
+
+        assert!(mmr.generate_proof(37, 37).is_none());
+        assert!(mmr.generate_proof(0, 38).is_none());
+    }
+
+    #[test]
+    fn test_tampered_proof() {
+        let leaves = generate_random_hashes(13);
+        let mut mmr = MerkleMountainRange::new();
+        for leaf in &leaves {
+            mmr.append(leaf);
+        }
+        let root = mmr.root();
+
+        let proof = mmr.generate_proof(5, 13).unwrap();
+        assert!(!MerkleMountainRange::validate_proof(&proof, &leaves[6], &root));
+
+        let mut moved = proof.clone();
+        moved.leaf_index = 4;
+        assert!(!MerkleMountainRange::validate_proof(&moved, &leaves[5], &root));
+
+        let mut short = proof.clone();
+        short.peaks.pop();
+        assert!(!MerkleMountainRange::validate_proof(&short, &leaves[5], &root));
+
+        let mut huge = proof.clone();
+        huge.leaf_count = usize::MAX;
+        assert!(!MerkleMountainRange::validate_proof(&huge, &leaves[5], &root));
+        huge.leaf_index = usize::MAX - 1;
+        assert!(!MerkleMountainRange::validate_proof(&huge, &leaves[5], &root));
+    }
+}
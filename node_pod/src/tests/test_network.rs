@@ -0,0 +1,155 @@
+#[cfg(test)]
+mod tests {
+    use crate::definitions::transaction_header::Transaction;
+    use crate::definitions::block_header::{BlockChain, BlockChainMethods};
+    use crate::definitions::network_header::{ClientReply, ClientReplyMethods, MessageType, MessageTypeMethods, NodeMessage, NodeMessageMethods};
+    use crate::definitions::signer_header::{Phase, PhaseMethods, SignerError, SigningRequest};
+    use crate::definitions::node_header::{Node, NodeMethods};
+    use crate::definitions::consensus_header::{Stake, StakeMethods, Validator, ValidatorMethods, ValidatorSet, ValidatorSetMethods};
+    use crate::signer::possession_message;
+    use crate::consensus::{epoch_of, signed_in_epoch, stake_message};
+    use bls_signatures::{Serialize, Signature};
+
+    fn transfer(id: usize) -> Transaction {
+        Transaction {
+            id: format!("{:064x}", id),
+            from: String::from("alice"),
+            to: String::from("bob"),
+            timestamp: 0,
+            amount: 1.0,
+            fee: 0.01,
+            signature: String::new(),
+            governance: None,
+            nonce: None,
+        }
+    }
+
+    #[test]
+    fn test_client_reply() {
+        let node = Node::new();
+        let block = BlockChain::new().new_block(vec![transfer(1), transfer(2)], node.id.clone(), String::new(), 3);
+
+        let reply = ClientReply::new(&node, &block).unwrap();
+        let body = reply.body().unwrap();
+
+        assert_eq!(body.height, 1);
+        assert_eq!(body.view, 3);
+        assert_eq!(body.block_hash, block.hash);
+        assert_eq!(body.tx_ids, vec![transfer(1).id, transfer(2).id]);
+        assert!(reply.verify(&node.signer.public_key()));
+
+        let other = Node::new();
+        assert!(!reply.verify(&other.signer.public_key()));
+
+        let mut forged = reply.clone();
+        let mut forged_body = body.clone();
+        forged_body.tx_ids.push(transfer(3).id);
+        forged.msg_type = MessageType::Reply(Phase::Reply.message(&serde_json::to_string(&forged_body).unwrap()));
+        assert!(!forged.verify(&node.signer.public_key()));
+
+        let mut commit = reply.clone();
+        commit.msg_type = MessageType::Commit(reply.msg_type.unwrap());
+        assert!(commit.body().is_none());
+        assert!(!commit.verify(&node.signer.public_key()));
+    }
+
+    #[test]
+    fn test_no_second_block_per_phase() {
+        let node = Node::new();
+        let mut chain = BlockChain::new();
+        let block = chain.new_block(vec![transfer(1)], node.id.clone(), String::new(), 0);
+        let conflicting = chain.new_block(vec![transfer(2)], node.id.clone(), String::new(), 0);
+
+        let prepare = NodeMessage::new(&node, &block, String::from("Prepare"), 0).unwrap();
+        assert!(prepare.verify_message(node.signer.public_key()));
+        assert!(NodeMessage::new(&node, &block, String::from("Prepare"), 0).is_ok());
+
+        assert_eq!(NodeMessage::new(&node, &conflicting, String::from("Prepare"), 0).err(),
+            Some(SignerError::DoubleSign { height: 1, view: 0, phase: Phase::Prepare }));
+        assert!(NodeMessage::new(&node, &block, String::from("Commit"), 0).is_ok());
+        assert!(ClientReply::new(&node, &conflicting).is_ok());
+        assert!(ClientReply::new(&node, &block).is_err());
+    }
+
+    fn member(node: &Node) -> Validator {
+        let stake = Stake::new(node, 1.0, 0).unwrap();
+        Validator { node_id: stake.node_id, public_key: stake.public_key, stake: stake.stake, proof_of_possession: stake.proof_of_possession }
+    }
+
+    #[test]
+    fn test_validator_set() {
+        // Pinned in master_pod's test_stake too, both must hash sets the same way
+        let validator = |node_id: &str, stake: f64| Validator { node_id: node_id.to_string(), public_key: node_id.repeat(2), stake,
+            proof_of_possession: String::new() };
+        let set = ValidatorSet::new(7, vec![validator("b", 2.5), validator("a", 1.0)]);
+        assert_eq!(set.hash, "ff40e2cab3ba69dda65c533ddb071e3250c2b399c39963c1dbef83060a1730f6");
+        assert_eq!(ValidatorSet::new(7, vec![validator("a", 1.0), validator("b", 2.5)]).hash, set.hash);
+        assert_ne!(ValidatorSet::new(8, set.validators.clone()).hash, set.hash);
+
+        assert_eq!(<ValidatorSet as ValidatorSetMethods>::deserialize(&set.serialize()), Some(set.clone()));
+        let mut tampered = set.clone();
+        tampered.validators[0].stake = 9.0;
+        assert!(<ValidatorSet as ValidatorSetMethods>::deserialize(&tampered.serialize()).is_none());
+
+        assert_eq!((epoch_of(1, 10), epoch_of(10, 10), epoch_of(11, 10), epoch_of(0, 10)), (0, 0, 1, 0));
+    }
+
+    #[test]
+    fn test_messages_from_other_epochs() {
+        let (node, outsider) = (Node::new(), Node::new());
+        let current = ValidatorSet::new(2, vec![member(&node)]);
+        let previous = ValidatorSet::new(1, vec![member(&node)]);
+        let mut chain = BlockChain::new();
+
+        let block = chain.new_epoch_block(vec![transfer(1)], node.id.clone(), &current, 0);
+        let message = NodeMessage::new(&node, &block, String::from("Prepare"), 0).unwrap();
+        assert!(signed_in_epoch(&current, &message));
+        assert!(!signed_in_epoch(&previous, &message));
+
+        let stale = chain.new_block(vec![transfer(1)], node.id.clone(), previous.hash.clone(), 0);
+        let message = NodeMessage::new(&node, &stale, String::from("Commit"), 0).unwrap();
+        assert!(!signed_in_epoch(&current, &message));
+
+        // Dressing the unsigned copy up as the current epoch does not change the signed block
+        let mut dressed = message.clone();
+        dressed.block = block.clone();
+        assert!(!signed_in_epoch(&current, &dressed));
+
+        let message = NodeMessage::new(&outsider, &block, String::from("Prepare"), 0).unwrap();
+        assert!(!signed_in_epoch(&current, &message));
+    }
+
+    #[test]
+    fn test_proof_of_possession() {
+        let (node, attacker) = (Node::new(), Node::new());
+        let validator = member(&node);
+        assert!(validator.verify_possession());
+
+        // Registering someone else's key needs a proof only the holder of that key can sign
+        let rogue = Validator { proof_of_possession: member(&attacker).proof_of_possession, ..validator.clone() };
+        assert!(!rogue.verify_possession());
+        let renamed = Validator { node_id: attacker.id.clone(), ..validator.clone() };
+        assert!(!renamed.verify_possession());
+        let unproven = Validator { proof_of_possession: String::new(), ..validator.clone() };
+        assert!(!unproven.verify_possession());
+
+        let foreign = SigningRequest { phase: Phase::Possession, message: possession_message(&attacker.signer.public_key()) };
+        assert!(matches!(node.signer.sign(&foreign), Err(SignerError::Message(_))));
+    }
+
+    #[test]
+    fn test_signed_stake() {
+        let node = Node::new();
+        let stake = Stake::new(&node, 12.5, 4).unwrap();
+        let signature = Signature::from_bytes(&hex::decode(&stake.signature).unwrap()).unwrap();
+        assert!(node.signer.public_key().verify(signature, stake_message(&stake)));
+
+        let raised = Stake { stake: 500.0, ..stake.clone() };
+        assert!(!node.signer.public_key().verify(signature, stake_message(&raised)));
+
+        // Pinned in master_pod's test_stake too, both must rebuild the same message
+        let fixed = Stake { node_id: String::from("ab"), stake: 12.5, committee_size: Some(4), epoch: 3, nonce: 99, ..stake };
+        assert_eq!(stake_message(&fixed), "node_pod stake:ab:12.5:3:99:4");
+        assert_eq!(stake_message(&Stake { committee_size: None, ..fixed }), "node_pod stake:ab:12.5:3:99:none");
+    }
+}
@@ -0,0 +1,111 @@
+#[cfg(test)]
+mod tests {
+    use crate::definitions::transaction_header::Transaction;
+    use crate::definitions::node_header::BlockPolicy;
+    use crate::node::fill_pool;
+    use crate::definitions::governance_header::Governance;
+    use crate::definitions::state_header::Account;
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+    use std::collections::HashMap;
+    use std::time::UNIX_EPOCH;
+
+    fn generate_signed_transactions(n: usize, user_base: &mut HashMap<String, Account>, nonced: bool) -> Vec<Transaction> {
+        let rng = SystemRandom::new();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let from = hex::encode(key_pair.public_key().as_ref());
+
+        user_base.insert(from.clone(), Account { balance: 1000.0, nonce: 0 });
+
+        let mut transactions = Vec::new();
+        for i in 0..n {
+            let timestamp = std::time::SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            let mut tx = Transaction {
+                id: format!("{:064x}", i),
+                from: from.clone(),
+                to: String::from("receiver"),
+                timestamp,
+                amount: 10.0,
+                fee: 0.1,
+                signature: String::new(),
+                governance: None,
+                nonce: nonced.then_some(i as u64 + 1),
+            };
+            let msg = serde_json::to_string_pretty(&tx).unwrap();
+            tx.signature = hex::encode(key_pair.sign(msg.as_bytes()).as_ref());
+            transactions.push(tx);
+        }
+        transactions
+    }
+
+    #[test]
+    fn test_fill_pool_by_size() {
+        let mut user_base = HashMap::new();
+        let mut residual = generate_signed_transactions(10, &mut user_base, false);
+        let policy = BlockPolicy { max_size: 4, max_bytes: usize::MAX, max_wait: 0, min_fee: 0.0 };
+
+        let mut pool = vec![];
+        let mut pool_bytes = 0;
+        let (full, checked) = fill_pool(&mut pool, &mut pool_bytes, &mut residual, &mut user_base, &mut Governance::default(), &[], &policy);
+
+        assert!(full);
+        assert_eq!(checked, 4.0);
+        assert_eq!(pool.len(), 4);
+        assert_eq!(residual.len(), 6);
+    }
+
+    #[test]
+    fn test_fill_pool_by_bytes() {
+        let mut user_base = HashMap::new();
+        let mut residual = generate_signed_transactions(10, &mut user_base, false);
+        let tx_bytes = serde_json::to_vec(&residual[0]).unwrap().len();
+        let policy = BlockPolicy { max_size: 10, max_bytes: 3 * tx_bytes + 1, max_wait: 0, min_fee: 0.0 };
+
+        let mut pool = vec![];
+        let mut pool_bytes = 0;
+        let (full, _) = fill_pool(&mut pool, &mut pool_bytes, &mut residual, &mut user_base, &mut Governance::default(), &[], &policy);
+
+        assert!(full);
+        assert_eq!(pool.len(), 3);
+        assert!(pool_bytes <= policy.max_bytes);
+        assert_eq!(residual.len(), 7);
+    }
+
+    #[test]
+    fn test_fill_pool_partial() {
+        let mut user_base = HashMap::new();
+        let mut residual = generate_signed_transactions(3, &mut user_base, false);
+        residual[1].amount = 20.0;
+        let policy = BlockPolicy { max_size: 8, max_bytes: usize::MAX, max_wait: 0, min_fee: 0.0 };
+
+        let mut pool = vec![];
+        let mut pool_bytes = 0;
+        let (full, checked) = fill_pool(&mut pool, &mut pool_bytes, &mut residual, &mut user_base, &mut Governance::default(), &[], &policy);
+
+        assert!(!full);
+        assert_eq!(checked, 3.0);
+        assert_eq!(pool.len(), 2);
+        assert!(residual.is_empty());
+    }
+
+    #[test]
+    fn test_fill_pool_skips_out_of_order_nonces() {
+        let mut user_base = HashMap::new();
+        let mut residual = generate_signed_transactions(3, &mut user_base, true);
+        residual.swap(0, 1);
+        let policy = BlockPolicy { max_size: 8, max_bytes: usize::MAX, max_wait: 0, min_fee: 0.0 };
+
+        let mut pool = vec![];
+        let mut pool_bytes = 0;
+        fill_pool(&mut pool, &mut pool_bytes, &mut residual, &mut user_base, &mut Governance::default(), &[], &policy);
+
+        // The second transaction arrives first and is dropped, so the third no longer follows
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool[0].nonce, Some(1));
+        assert_eq!(user_base[&pool[0].from].nonce, 1);
+    }
+}
@@ -0,0 +1,217 @@
+#[cfg(test)]
+mod tests {
+    use crate::definitions::transaction_header::Transaction;
+    use crate::definitions::block_header::{BlockChain, BlockChainMethods};
+    use crate::definitions::node_header::{Node, NodeMethods, NodeState};
+    use crate::definitions::rpc_header::{Event, Rejection, RpcMethods, RpcRequest, RpcState, Subscription};
+    use crate::definitions::state_header::{SparseMerkleTree, StateMethods};
+    use crate::definitions::transaction_header::TransactionMethods;
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+    fn transfer(id: &str, from: &str, to: &str, amount: f64) -> Transaction {
+        Transaction {
+            id: id.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            timestamp: 0,
+            amount,
+            fee: 0.01 * amount,
+            signature: String::new(),
+            governance: None,
+            nonce: None,
+        }
+    }
+
+    fn request(method: &str, params: Value) -> RpcRequest {
+        RpcRequest { jsonrpc: String::from("2.0"), method: method.to_string(), params, id: json!(1) }
+    }
+
+    fn rpc_state(node: &Node) -> (RpcState, UnboundedReceiver<Transaction>) {
+        let (submissions, queue) = unbounded_channel();
+        (RpcState::new(node, submissions), queue)
+    }
+
+    fn signed_transfer(key_pair: &Ed25519KeyPair, to: &str, amount: f64) -> Transaction {
+        signed_transfer_with_nonce(key_pair, to, amount, None)
+    }
+
+    fn signed_transfer_with_nonce(key_pair: &Ed25519KeyPair, to: &str, amount: f64, nonce: Option<u64>) -> Transaction {
+        let mut tx = transfer("", &hex::encode(key_pair.public_key().as_ref()), to, amount);
+        tx.timestamp = 1;
+        tx.nonce = nonce;
+        tx.id = tx.compute_id();
+        let msg = serde_json::to_string_pretty(&tx).unwrap();
+        tx.signature = hex::encode(key_pair.sign(msg.as_bytes()).as_ref());
+        tx
+    }
+
+    fn node_with_blocks() -> Node {
+        let balances: HashMap<String, f64> = [(String::from("alice"), 100.0)].into_iter().collect();
+        let node = Node::new();
+        *node.block_chain.write().unwrap() = BlockChain::from_genesis_state(SparseMerkleTree::from_balances(&balances));
+
+        for (i, amount) in [10.0, 20.0].iter().enumerate() {
+            let mut chain = node.block_chain.write().unwrap();
+            let block = chain.new_block(vec![transfer(&format!("{:064x}", i), "alice", "bob", *amount)],
+                String::new(), String::new(), 0);
+            chain.add_block(block);
+        }
+        node
+    }
+
+    #[test]
+    fn test_chain_queries() {
+        let node = node_with_blocks();
+        let (state, _queue) = rpc_state(&node);
+
+        let chain = node.block_chain.read().unwrap().clone();
+        let tip = state.handle(request("chain_tip", json!({}))).result.unwrap();
+        assert_eq!(tip["height"], 2);
+        assert_eq!(tip["hash"], chain.headers[2].hash);
+
+        let block = state.handle(request("block_by_height", json!({ "height": 1 }))).result.unwrap();
+        assert_eq!(block["hash"], chain.headers[1].hash);
+
+        let by_hash = state.handle(request("block_by_hash", json!({ "hash": chain.headers[1].hash }))).result.unwrap();
+        assert_eq!(by_hash, block);
+        assert_eq!(state.handle(request("block_by_height", json!({ "height": 9 }))).result, Some(Value::Null));
+
+        let receipt = state.handle(request("transaction", json!({ "id": format!("{:064x}", 1) }))).result.unwrap();
+        assert_eq!(receipt["height"], 2);
+        assert!(receipt["proof"].is_object());
+
+        let bob = state.handle(request("account_balance", json!({ "account": "bob" }))).result.unwrap();
+        assert_eq!(bob["balance"], 30.0);
+        assert_eq!(state.handle(request("account_balance", json!({ "account": "carol" }))).result, Some(Value::Null));
+    }
+
+    #[test]
+    fn test_node_queries() {
+        let mut node = node_with_blocks();
+        let (mut state, _queue) = rpc_state(&node);
+
+        assert_eq!(state.handle(request("mempool_size", Value::Null)).result, Some(json!(0)));
+        assert_eq!(state.handle(request("node_state", Value::Null)).result.unwrap()["state"], json!(NodeState::Idle));
+
+        let mut chain = node.block_chain.write().unwrap();
+        let block = chain.new_block(vec![], String::new(), String::new(), 0);
+        chain.add_block(block);
+        drop(chain);
+        node.state = NodeState::Done;
+        let pending = vec![transfer(&format!("{:064x}", 7), "alice", "bob", 1.0), transfer(&format!("{:064x}", 8), "alice", "bob", 1.0)];
+        state.publish(&node, &pending);
+
+        assert_eq!(state.height, 4);
+        assert_eq!(state.handle(request("chain_tip", Value::Null)).result.unwrap()["height"], 3);
+        assert_eq!(state.handle(request("mempool_size", Value::Null)).result, Some(json!(2)));
+        assert_eq!(state.handle(request("node_state", Value::Null)).result.unwrap()["state"], json!(NodeState::Done));
+    }
+
+    #[test]
+    fn test_rpc_errors() {
+        let (state, _queue) = rpc_state(&node_with_blocks());
+
+        assert_eq!(state.handle(request("unknown", Value::Null)).error.unwrap().code, -32601);
+        assert_eq!(state.handle(request("block_by_height", json!({ "height": "one" }))).error.unwrap().code, -32602);
+
+        let mut old = request("chain_tip", Value::Null);
+        old.jsonrpc = String::from("1.0");
+        let response = state.handle(old);
+        assert_eq!(response.error.unwrap().code, -32600);
+        assert!(response.result.is_none());
+    }
+
+    #[test]
+    fn test_submit_transaction() {
+        let key_pair = Ed25519KeyPair::from_pkcs8(Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap().as_ref()).unwrap();
+        let sender = hex::encode(key_pair.public_key().as_ref());
+
+        let node = Node::new();
+        let balances: HashMap<String, f64> = [(sender.clone(), 50.0)].into_iter().collect();
+        *node.block_chain.write().unwrap() = BlockChain::from_genesis_state(SparseMerkleTree::from_balances(&balances));
+        let (mut state, mut queue) = rpc_state(&node);
+
+        let tx = signed_transfer(&key_pair, "bob", 20.0);
+        let response = state.handle_submission(request("submit_transaction", json!({ "transaction": tx })));
+        assert_eq!(response.result.unwrap()["accepted"], true);
+        assert_eq!(queue.try_recv().unwrap(), tx);
+        assert_eq!(state.handle(request("mempool_size", Value::Null)).result, Some(json!(1)));
+
+        assert_eq!(state.check_transaction(&tx), Err(Rejection::Replay));
+        let replay = state.submit(tx.clone());
+        assert!(!replay.accepted);
+        assert_eq!(replay.reason, Some(Rejection::Replay.to_string()));
+        assert!(queue.try_recv().is_err());
+
+        let mut tampered = tx.clone();
+        tampered.amount = 30.0;
+        assert_eq!(state.check_transaction(&tampered), Err(Rejection::Id));
+        tampered.id = tampered.compute_id();
+        assert_eq!(state.check_transaction(&tampered), Err(Rejection::Signature));
+
+        assert_eq!(state.check_transaction(&signed_transfer(&key_pair, "bob", 60.0)), Err(Rejection::Balance));
+        assert!(matches!(state.check_transaction(&transfer("zz", &sender, "bob", 1.0)), Err(Rejection::Format(_))));
+
+        let stranger = Ed25519KeyPair::from_pkcs8(Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap().as_ref()).unwrap();
+        assert_eq!(state.check_transaction(&signed_transfer(&stranger, "bob", 1.0)), Err(Rejection::UnknownSender));
+
+        let mut chain = node.block_chain.write().unwrap();
+        let block = chain.new_block(vec![tx.clone()], String::new(), String::new(), 0);
+        chain.add_block(block);
+        drop(chain);
+        state.publish(&node, &[]);
+        assert!(state.submitted.is_empty());
+        assert_eq!(state.check_transaction(&tx), Err(Rejection::Replay));
+
+        // The committed transfer moved the sender's nonce to 1
+        assert_eq!(state.check_transaction(&signed_transfer_with_nonce(&key_pair, "bob", 1.0, Some(1))), Err(Rejection::Nonce));
+        assert_eq!(state.check_transaction(&signed_transfer_with_nonce(&key_pair, "bob", 1.0, Some(3))), Err(Rejection::Nonce));
+        assert_eq!(state.check_transaction(&signed_transfer_with_nonce(&key_pair, "bob", 1.0, Some(2))), Ok(()));
+    }
+
+    #[test]
+    fn test_subscription_events() {
+        let node = node_with_blocks();
+        let (mut state, _queue) = rpc_state(&node);
+        let mut tips = state.tips.subscribe();
+
+        let blocks: Subscription = serde_json::from_str(r#"{"subscribe":"blocks","from_height":1}"#).unwrap();
+        assert_eq!(blocks, Subscription::Blocks { from_height: Some(1) });
+        let heights: Vec<usize> = state.events(&blocks, 1, 3).iter().map(|event| match event {
+            Event::Block { height, .. } => *height,
+            _ => panic!["Expected block events"]
+        }).collect();
+        assert_eq!(heights, vec![1, 2]);
+
+        let id = format!("{:064x}", 3);
+        let confirmation = Subscription::Transaction { id: id.clone() };
+        assert!(state.events(&confirmation, 0, 3).is_empty());
+
+        let mut chain = node.block_chain.write().unwrap();
+        let block = chain.new_block(vec![transfer(&id, "bob", "carol", 5.0)], String::new(), String::new(), 0);
+        chain.add_block(block);
+        drop(chain);
+        state.publish(&node, &[]);
+        assert_eq!(tips.try_recv().unwrap(), 4);
+
+        match &state.events(&confirmation, 0, 4)[..] {
+            [Event::Confirmation { receipt }] => assert_eq!(receipt.height, 3),
+            _ => panic!["Expected a confirmation"]
+        }
+
+        let bob = Subscription::Account { account: String::from("bob"), from_height: None };
+        let balances: Vec<(usize, f64)> = state.events(&bob, 0, 4).iter().map(|event| match event {
+            Event::Balance { height, balance, .. } => (*height, *balance),
+            _ => panic!["Expected balance events"]
+        }).collect();
+        assert_eq!(balances, vec![(1, 10.0), (2, 30.0), (3, 24.95)]);
+        assert_eq!(state.events(&bob, 3, 4).len(), 1);
+
+        state.publish(&node, &[]);
+        assert!(tips.try_recv().is_err());
+    }
+}
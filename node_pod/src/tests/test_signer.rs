@@ -0,0 +1,172 @@
+#[cfg(test)]
+mod tests {
+    use crate::definitions::transaction_header::{Transaction, TransactionMethods};
+    use crate::definitions::block_header::{Block, BlockChain, BlockChainMethods, BlockMethods};
+    use crate::definitions::signer_header::{LocalSigner, LocalSignerMethods, Phase, PhaseMethods, RemoteSigner,
+        RemoteSignerMethods, Signer, SignerError, SigningGuard, SigningGuardMethods, SigningRequest};
+    use crate::definitions::network_header::ReplyBody;
+    use crate::definitions::consensus_header::STAKE_DOMAIN;
+    use crate::definitions::governance_header::{GovernanceAction, GovernanceTransactionMethods};
+    use bls_signatures::PrivateKey;
+    use std::time::Duration;
+
+    fn block_at(height: u64, view: u64, tx: usize) -> Block {
+        let mut chain = BlockChain::new();
+        for _ in 1..height {
+            let block = chain.new_block(vec![], String::new(), String::new(), 0);
+            chain.add_block(block);
+        }
+        let transaction = Transaction {
+            id: format!("{:064x}", tx),
+            from: String::from("alice"),
+            to: String::from("bob"),
+            timestamp: 0,
+            amount: 1.0,
+            fee: 0.01,
+            signature: String::new(),
+            governance: None,
+            nonce: None,
+        };
+        chain.new_block(vec![transaction], String::new(), String::new(), view)
+    }
+
+    fn request(phase: Phase, block: &Block) -> SigningRequest {
+        SigningRequest { phase, message: phase.message(&block.serialize_block()) }
+    }
+
+    #[test]
+    fn test_guard() {
+        let mut guard = SigningGuard::new(2);
+        let (block, conflicting) = (block_at(1, 0, 1), block_at(1, 0, 2));
+
+        assert!(guard.check(&request(Phase::Prepare, &block)).is_ok());
+        assert!(guard.check(&request(Phase::Prepare, &block)).is_ok());
+        assert_eq!(guard.check(&request(Phase::Prepare, &conflicting)),
+            Err(SignerError::DoubleSign { height: 1, view: 0, phase: Phase::Prepare }));
+
+        // Another phase or a later view may carry a different block
+        assert!(guard.check(&request(Phase::Commit, &conflicting)).is_ok());
+        assert!(guard.check(&request(Phase::Prepare, &block_at(1, 1, 2))).is_ok());
+
+        assert!(guard.check(&request(Phase::Prepare, &block_at(4, 0, 1))).is_ok());
+        assert_eq!(guard.floor, 2);
+        assert!(guard.signed.keys().all(|(height, _, _)| *height >= 2));
+        assert_eq!(guard.check(&request(Phase::Prepare, &block)), Err(SignerError::Stale(1)));
+
+        let garbage = SigningRequest { phase: Phase::Reply, message: String::from("{}") };
+        assert!(matches!(guard.check(&garbage), Err(SignerError::Message(_))));
+
+        // A conflicting block can not borrow the hash of one already signed
+        let mut posing = block_at(4, 0, 2);
+        posing.hash = block_at(4, 0, 1).hash;
+        assert!(matches!(guard.check(&request(Phase::Prepare, &posing)), Err(SignerError::Message(_))));
+    }
+
+    #[test]
+    fn test_phase_domains() {
+        let signer = LocalSigner::new(PrivateKey::generate(&mut rand::thread_rng()));
+        let (block, conflicting) = (block_at(5, 0, 1), block_at(5, 0, 2));
+        assert!(signer.sign(&request(Phase::Commit, &block)).is_ok());
+        assert!(matches!(signer.sign(&request(Phase::Commit, &conflicting)), Err(SignerError::DoubleSign { .. })));
+
+        // A block can not be signed under another phase's domain, bare, or as a reply body
+        let as_reply = SigningRequest { phase: Phase::Reply, message: Phase::Commit.message(&conflicting.serialize_block()) };
+        assert!(matches!(signer.sign(&as_reply), Err(SignerError::Message(_))));
+        let bare = SigningRequest { phase: Phase::Reply, message: conflicting.serialize_block() };
+        assert!(matches!(signer.sign(&bare), Err(SignerError::Message(_))));
+        let polyglot = SigningRequest { phase: Phase::Reply, message: Phase::Reply.message(&conflicting.serialize_block()) };
+        assert!(matches!(signer.sign(&polyglot), Err(SignerError::Message(_))));
+
+        let body = ReplyBody { view: 0, height: 5, block_hash: block.hash.clone(), tx_ids: vec![] };
+        let mut padded = serde_json::to_value(&body).unwrap();
+        padded["index"] = serde_json::json!(5);
+        let padded = SigningRequest { phase: Phase::Reply, message: Phase::Reply.message(&padded.to_string()) };
+        assert!(matches!(signer.sign(&padded), Err(SignerError::Message(_))));
+        let reply = SigningRequest { phase: Phase::Reply, message: Phase::Reply.message(&serde_json::to_string(&body).unwrap()) };
+        assert!(signer.sign(&reply).is_ok());
+    }
+
+    #[test]
+    fn test_guard_survives_restart() {
+        let path = std::env::temp_dir().join(format!("guard-{}.json", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let _ = std::fs::remove_file(&path);
+
+        let mut guard = SigningGuard::open(&path, 1024).unwrap();
+        assert!(guard.check(&request(Phase::Prepare, &block_at(2, 0, 1))).is_ok());
+        assert!(guard.check(&request(Phase::Prepare, &block_at(3, 1, 1))).is_ok());
+
+        let mut restarted = SigningGuard::open(&path, 1024).unwrap();
+        assert_eq!(restarted.restored.get(&Phase::Prepare).map(|mark| (mark.height, mark.view)), Some((3, 1)));
+        assert!(restarted.check(&request(Phase::Prepare, &block_at(3, 1, 1))).is_ok());
+        assert_eq!(restarted.check(&request(Phase::Prepare, &block_at(3, 1, 2))),
+            Err(SignerError::DoubleSign { height: 3, view: 1, phase: Phase::Prepare }));
+        assert_eq!(restarted.check(&request(Phase::Prepare, &block_at(2, 0, 2))), Err(SignerError::Stale(2)));
+        assert!(restarted.check(&request(Phase::Commit, &block_at(2, 0, 2))).is_ok());
+        assert!(restarted.check(&request(Phase::Prepare, &block_at(4, 0, 2))).is_ok());
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(matches!(SigningGuard::open(&path, 1024), Err(SignerError::Guard(_))));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_unguarded_phases() {
+        let signer = LocalSigner::new(PrivateKey::generate(&mut rand::thread_rng()));
+        let block = block_at(1, 0, 1);
+        assert!(signer.sign(&request(Phase::Prepare, &block)).is_ok());
+
+        let conflicting = block_at(1, 0, 2);
+        let posing = SigningRequest { phase: Phase::Stake, message: Phase::Prepare.message(&conflicting.serialize_block()) };
+        assert!(matches!(signer.sign(&posing), Err(SignerError::Message(_))));
+        let stake = SigningRequest { phase: Phase::Stake, message: format!("{}ab:1:0:1:none", STAKE_DOMAIN) };
+        assert!(signer.sign(&stake).is_ok());
+
+        // A governance signature only ever covers a message behind the governance domain
+        let posing = SigningRequest { phase: Phase::Governance, message: Phase::Commit.message(&conflicting.serialize_block()) };
+        assert!(matches!(signer.sign(&posing), Err(SignerError::Message(_))));
+        let bare = SigningRequest { phase: Phase::Governance, message: block.transactions[0].serialize_tx() };
+        assert!(matches!(signer.sign(&bare), Err(SignerError::Message(_))));
+        let action = GovernanceAction::Vote { proposal: String::from("p"), approve: true };
+        let vote = Transaction::governance(&signer, action, 1).unwrap();
+        assert!(vote.verify_sender());
+
+        // The same transaction signed without the domain does not verify
+        let mut unbound = vote.clone();
+        unbound.signature = String::new();
+        let signature = signer.private_key.sign(unbound.serialize_tx());
+        unbound.signature = hex::encode(bls_signatures::Serialize::as_bytes(&signature));
+        assert!(!unbound.verify_sender());
+    }
+
+    #[test]
+    fn test_remote_signer() {
+        let socket = std::env::temp_dir().join(format!("signer-{}.sock", std::process::id()));
+        let socket = socket.to_str().unwrap().to_string();
+
+        let private_key = PrivateKey::generate(&mut rand::thread_rng());
+        let public_key = private_key.public_key();
+        let path = socket.clone();
+        std::thread::spawn(move || RemoteSigner::serve(&LocalSigner::new(private_key), &path));
+
+        let mut remote = RemoteSigner::connect(&socket);
+        for _ in 0..50 {
+            if remote.is_ok() { break; }
+            std::thread::sleep(Duration::from_millis(20));
+            remote = RemoteSigner::connect(&socket);
+        }
+        let remote = remote.unwrap();
+        assert_eq!(remote.public_key(), public_key);
+
+        let (block, conflicting) = (block_at(2, 0, 1), block_at(2, 0, 2));
+        let signature = remote.sign(&request(Phase::PrePrepare, &block)).unwrap();
+        assert!(public_key.verify(signature, Phase::PrePrepare.message(&block.serialize_block())));
+
+        // The guard lives in the signer process, so a fresh connection is refused just the same
+        let reconnected = RemoteSigner::connect(&socket).unwrap();
+        assert_eq!(reconnected.sign(&request(Phase::PrePrepare, &conflicting)).err(),
+            Some(SignerError::DoubleSign { height: 2, view: 0, phase: Phase::PrePrepare }));
+
+        let _ = std::fs::remove_file(&socket);
+    }
+}
@@ -1,7 +1,10 @@
-use serde_json::{self, to_string_pretty};
+use serde_json::{self, to_string, to_string_pretty};
+use ring::signature::ED25519;
 use ring::signature::UnparsedPublicKey;
 use openssl::sha;
+use bls_signatures::{PublicKey, Serialize, Signature};
 use crate::definitions::transaction_header::{Transaction, TransactionMethods};
+use crate::definitions::signer_header::{Phase, PhaseMethods};
 
 impl TransactionMethods for Transaction {
 
@@ -25,6 +28,9 @@ impl TransactionMethods for Transaction {
         hasher.update(&self.amount.to_be_bytes());
         hasher.update(&self.fee.to_be_bytes());
         hasher.update(&self.signature.as_bytes());
+        if let Some(action) = &self.governance {
+            hasher.update(&serde_json::to_vec(action).expect("Failed to serialize governance action"));
+        }
 
         hasher.finish()
     }
@@ -37,6 +43,7 @@ impl TransactionMethods for Transaction {
         predicate = predicate && (self.amount == tx.amount);
         predicate = predicate && (self.fee == tx.fee);
         predicate = predicate && (self.signature == tx.signature);
+        predicate = predicate && (self.governance == tx.governance);
 
         predicate
     }
@@ -53,4 +60,37 @@ impl TransactionMethods for Transaction {
             Err(_) => false
         }
     }
-}
\ No newline at end of file
+
+    // The sender is the hex encoded public key, malformed keys or signatures fail instead of panicking.
+    // Governance transactions come from a validator and carry a BLS signature over the same message.
+    fn verify_sender(&self) -> bool {
+        if self.governance.is_some() {
+            let mut temp_tx = self.clone();
+            temp_tx.signature = String::new();
+            let msg = Phase::Governance.message(&temp_tx.serialize_tx());
+
+            let public_key = hex::decode(&self.from).ok().and_then(|bytes| PublicKey::from_bytes(&bytes).ok());
+            let signature = hex::decode(&self.signature).ok().and_then(|bytes| Signature::from_bytes(&bytes).ok());
+            return match (public_key, signature) {
+                (Some(public_key), Some(signature)) => public_key.verify(signature, msg),
+                _ => false
+            };
+        }
+
+        match (hex::decode(&self.from), hex::decode(&self.signature)) {
+            (Ok(public_key), Ok(_)) => self.verify_transaction(UnparsedPublicKey::new(&ED25519, public_key)),
+            _ => false
+        }
+    }
+
+    // Same digest the transaction pod assigns, over the compact JSON without id or signature
+    fn compute_id(&self) -> String {
+        let mut temp_tx = self.clone();
+        temp_tx.id = String::new();
+        temp_tx.signature = String::new();
+
+        let mut hasher = sha::Sha256::new();
+        hasher.update(to_string(&temp_tx).expect("Failed to serialize transaction").as_bytes());
+        hex::encode(hasher.finish())
+    }
+}
@@ -0,0 +1,115 @@
+use serde::{de::DeserializeOwned, Serialize};
+use serde_yaml::{Mapping, Value};
+
+mod test_config;
+
+// Configuration is read from a YAML file and then overridden from the environment. The file
+// is the one given with `--config <path>`, else the one named by an env var, else the default.
+// Any field can be overridden with `<PREFIX>__<SECTION>__<FIELD>`, e.g. NODE__PERFORMANCE__BLOCK_SIZE,
+// and aliases such as BOOTSTRAP_SERVERS fan out to several fields before those overrides apply.
+
+pub const DEFAULT_PATH: &str = "src/config.yaml";
+
+/// Splits `--config <path>` out of the command line, returning it and the remaining arguments
+pub fn parse_args(args: impl Iterator<Item = String>) -> (Option<String>, Vec<String>) {
+    let mut path = None;
+    let mut rest = vec![];
+    let mut args = args.peekable();
+
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix("--config") {
+            Some("") => path = args.next(),
+            Some(value) if value.starts_with('=') => path = Some(value[1..].to_string()),
+            _ => rest.push(arg)
+        }
+    }
+    (path, rest)
+}
+
+pub fn resolve_path(flag: Option<String>, env_var: &str) -> String {
+    flag.or_else(|| std::env::var(env_var).ok().filter(|path| !path.is_empty()))
+        .unwrap_or(DEFAULT_PATH.to_string())
+}
+
+// Overrides keep the type of the value they replace, so "false" stays a string where the file
+// quotes it, and are parsed as YAML scalars where the field is missing from the file
+
+fn set(root: &mut Value, path: &[String], raw: &str) {
+    let (last, parents) = path.split_last().expect("Empty override path");
+    let mut node = root;
+    for key in parents {
+        if !node.is_mapping() {
+            *node = Value::Mapping(Mapping::new());
+        }
+        let map = node.as_mapping_mut().unwrap();
+        node = map.entry(Value::String(key.clone())).or_insert(Value::Mapping(Mapping::new()));
+    }
+    if !node.is_mapping() {
+        *node = Value::Mapping(Mapping::new());
+    }
+
+    let map = node.as_mapping_mut().unwrap();
+    let key = Value::String(last.clone());
+    let value = match map.get(&key) {
+        Some(Value::String(_)) => Value::String(raw.to_string()),
+        _ => serde_yaml::from_str(raw).unwrap_or(Value::String(raw.to_string()))
+    };
+    map.insert(key, value);
+}
+
+/// Applies aliases, then `<prefix>__...` variables, to the parsed file
+pub fn apply_overrides(root: &mut Value, prefix: &str, aliases: &[(&str, &[&str])],
+    vars: impl Iterator<Item = (String, String)>) -> Vec<String> {
+    let vars: Vec<(String, String)> = vars.collect();
+    let mut applied = vec![];
+
+    for (name, fields) in aliases {
+        if let Some((_, raw)) = vars.iter().find(|(var, _)| var == name) {
+            for field in fields.iter() {
+                set(root, &field.split('.').map(String::from).collect::<Vec<String>>(), raw);
+            }
+            applied.push(name.to_string());
+        }
+    }
+
+    let marker = format!("{}__", prefix);
+    let mut prefixed: Vec<&(String, String)> = vars.iter().filter(|(var, _)| var.starts_with(&marker)).collect();
+    prefixed.sort();
+    for (var, raw) in prefixed {
+        let path: Vec<String> = var[marker.len()..].split("__").map(|key| key.to_lowercase()).collect();
+        if path.iter().any(|key| key.is_empty()) {
+            continue;
+        }
+        set(root, &path, raw);
+        applied.push(var.clone());
+    }
+    applied
+}
+
+/// Reads the file at `path`, applies the environment and deserializes the result. The error
+/// names the file and the overrides that were in effect.
+pub fn load<T: DeserializeOwned>(path: &str, prefix: &str, aliases: &[(&str, &[&str])]) -> Result<T, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("Failed to read config {}: {}", path, e))?;
+    let mut root: Value = serde_yaml::from_str(&content).map_err(|e| format!("Failed to parse config {}: {}", path, e))?;
+    if root.is_null() {
+        root = Value::Mapping(Mapping::new());
+    }
+
+    let applied = apply_overrides(&mut root, prefix, aliases, std::env::vars());
+    serde_yaml::from_value(root).map_err(|e| match applied.is_empty() {
+        true => format!("Invalid config {}: {}", path, e),
+        false => format!("Invalid config {} with overrides {}: {}", path, applied.join(", "), e)
+    })
+}
+
+/// Turns the problems found by a validation into one error, or passes the config through
+pub fn check<T>(config: T, path: &str, problems: Vec<String>) -> Result<T, String> {
+    match problems.is_empty() {
+        true => Ok(config),
+        false => Err(format!("Invalid config {}:\n  {}", path, problems.join("\n  ")))
+    }
+}
+
+pub fn effective<T: Serialize>(config: &T) -> String {
+    serde_yaml::to_string(config).expect("Failed to serialize config")
+}
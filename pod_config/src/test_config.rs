@@ -0,0 +1,60 @@
+#[cfg(test)]
+mod tests {
+    use crate::{apply_overrides, check, parse_args, DEFAULT_PATH, resolve_path};
+    use serde_yaml::Value;
+
+    fn vars(pairs: &[(&str, &str)]) -> impl Iterator<Item = (String, String)> {
+        pairs.iter().map(|(var, value)| (var.to_string(), value.to_string())).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = |line: &str| line.split(' ').map(String::from).collect::<Vec<String>>().into_iter();
+
+        assert_eq!(parse_args(args("--config node.yaml identity")), (Some(String::from("node.yaml")), vec![String::from("identity")]));
+        assert_eq!(parse_args(args("identity --config=node.yaml")), (Some(String::from("node.yaml")), vec![String::from("identity")]));
+        assert_eq!(parse_args(args("identity")).0, None);
+        assert_eq!(resolve_path(Some(String::from("node.yaml")), "TEST_CONFIG_UNSET"), "node.yaml");
+        assert_eq!(resolve_path(None, "TEST_CONFIG_UNSET"), DEFAULT_PATH);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut root: Value = serde_yaml::from_str("
+consumer:
+  server: \"a:9092\"
+  autocommit: \"false\"
+performance:
+  block_size: 512
+").unwrap();
+
+        let applied = apply_overrides(&mut root, "NODE", &[("BOOTSTRAP_SERVERS", &["consumer.server", "producer.server"])], vars(&[
+            ("BOOTSTRAP_SERVERS", "b:9092"),
+            ("NODE__PERFORMANCE__BLOCK_SIZE", "64"),
+            ("NODE__CONSUMER__AUTOCOMMIT", "true"),
+            ("NODE__RPC__ADDRESS", "0.0.0.0:9000"),
+            ("NODE__", "ignored"),
+            ("NODE_KEY_PASSPHRASE", "ignored"),
+        ]));
+
+        assert_eq!(applied.len(), 4);
+        assert_eq!(root["consumer"]["server"], Value::from("b:9092"));
+        assert_eq!(root["producer"]["server"], Value::from("b:9092"));
+        assert_eq!(root["performance"]["block_size"], Value::from(64));
+        assert_eq!(root["consumer"]["autocommit"], Value::from("true"));
+        assert_eq!(root["rpc"]["address"], Value::from("0.0.0.0:9000"));
+        assert!(root.get("key_passphrase").is_none());
+
+        // An explicit override beats the alias
+        apply_overrides(&mut root, "NODE", &[("BOOTSTRAP_SERVERS", &["consumer.server"])],
+            vars(&[("BOOTSTRAP_SERVERS", "b:9092"), ("NODE__CONSUMER__SERVER", "c:9092")]));
+        assert_eq!(root["consumer"]["server"], Value::from("c:9092"));
+    }
+
+    #[test]
+    fn test_check() {
+        assert_eq!(check(1, "node.yaml", vec![]), Ok(1));
+        assert_eq!(check(1, "node.yaml", vec![String::from("a"), String::from("b")]),
+            Err(String::from("Invalid config node.yaml:\n  a\n  b")));
+    }
+}
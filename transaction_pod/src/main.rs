@@ -1,22 +1,29 @@
 use rand::{distributions::Uniform, rngs::StdRng, SeedableRng};
 use rdkafka::{producer::{BaseProducer,  BaseRecord}, ClientConfig};
+use rdkafka::consumer::{Consumer, StreamConsumer};
+use rdkafka::Message;
+use futures::StreamExt;
+use std::collections::HashSet;
 use simulate::User;
 use rand::Rng;
 use std::time::Instant;
 use std::time::Duration;
-use serde::Deserialize;
-use serde_yaml;
+use serde::{Deserialize, Serialize};
 use std::thread;
-use tokio::fs;
 
+pub use pod_config as config;
 pub mod transaction;
 pub mod test_transaction;
 pub mod simulate;
 pub mod tx_mod;
+pub mod reply;
+pub mod reply_mod;
+pub mod test_reply;
 
 use crate::tx_mod::Transaction;
+use crate::reply_mod::{ClientReply, ReplyMethods, ReplyTracker, ValidatorSet};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct ProducerConfig {
     pub server: String,
     pub autocommit: String,
@@ -26,22 +33,96 @@ pub struct ProducerConfig {
     pub acks: String
 }
 
-#[derive(Deserialize)]
+/// Committee size the f + 1 reply threshold is computed from and how long to wait for replies
+#[derive(Deserialize, Serialize)]
+pub struct ReplyConfig {
+    pub validators: usize,
+    pub wait: u64
+}
+
+#[derive(Deserialize, Serialize)]
 pub struct Config {
     pub user_thro: u64,
     pub user_size: usize,
     pub tx_size: u64,
-    producer: ProducerConfig
+    producer: ProducerConfig,
+    #[serde(default)]
+    replies: Option<ReplyConfig>
+}
+
+impl Config {
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
+
+        if self.producer.server.is_empty() {
+            problems.push(String::from("producer.server must name the Kafka bootstrap servers"));
+        }
+        if self.user_size == 0 {
+            problems.push(String::from("user_size must create at least 1 user"));
+        }
+        if self.tx_size == 0 {
+            problems.push(String::from("tx_size must send at least 1 transaction"));
+        }
+        if let Some(replies) = &self.replies {
+            if replies.validators == 0 {
+                problems.push(String::from("replies.validators must count at least 1 validator"));
+            }
+            if replies.wait == 0 {
+                problems.push(String::from("replies.wait must be at least 1 millisecond"));
+            }
+        }
+        problems
+    }
 }
 
-pub async fn load_config() -> Option<Config> {
-    let path = "src/config.yaml";
+pub fn load_config(path: &str) -> Result<Config, String> {
+    let config: Config = config::load(path, "TX", &[("BOOTSTRAP_SERVERS", &["producer.server"])])?;
+    let problems = config.validate();
+    config::check(config, path, problems)
+}
 
-    let config_content = fs::read_to_string(&path).await.expect("Failed to read file");
+/// Listens for validator keys and replies until every transaction in `tx_ids` is final or the
+/// wait runs out, returning how many of them became final
+pub async fn await_replies(server: &str, reply_config: &ReplyConfig, tx_ids: &HashSet<String>) -> usize {
+    let consumer: StreamConsumer = ClientConfig::new()
+        .set("bootstrap.servers", server)
+        .set("group.id", "sim-replies")
+        .set("enable.auto.commit", "false")
+        .set("auto.offset.reset", "earliest")
+        .create()
+        .expect("Failed to create consumer");
+
+    consumer.subscribe(&["Validators", "Reply"]).expect("Failed to subscribe to topics");
 
-    let config: Config =serde_yaml::from_str(&config_content).expect("Failed to parse yaml file");
+    let mut tracker = ReplyTracker::new(reply_config.validators);
+    let mut finalized = 0;
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(reply_config.wait);
+    let mut stream = consumer.stream();
 
-    Some(config)
+    while finalized < tx_ids.len() {
+        let message = match tokio::time::timeout_at(deadline, stream.next()).await {
+            Ok(Some(Ok(message))) => message,
+            Ok(Some(Err(_))) => continue,
+            _ => break
+        };
+
+        let payload = match message.payload() {
+            Some(payload) => payload,
+            None => continue
+        };
+
+        let newly_final = match message.topic() {
+            "Validators" => serde_json::from_slice::<ValidatorSet>(payload)
+                .map(|set| set.validators.iter().flat_map(|validator| tracker.add_validator(validator)).collect())
+                .unwrap_or_default(),
+            _ => serde_json::from_slice::<ClientReply>(payload)
+                .map(|reply| tracker.record(&reply)).unwrap_or_default()
+        };
+
+        finalized += newly_final.iter().filter(|id| tx_ids.contains(*id)).count();
+    }
+
+    finalized
 }
 
 #[tokio::main]
@@ -51,7 +132,17 @@ async fn main() {
     let mut user_base_rec = vec![];
     let mut user_ids: Vec<String> = vec![];
     let topic = String::from("Users");
-    let config = load_config().await.unwrap();
+    let (config_flag, _) = config::parse_args(std::env::args().skip(1));
+    let config_path = config::resolve_path(config_flag, "TX_CONFIG");
+    let config = match load_config(&config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    println!("Effective config from {}:\n{}", config_path, config::effective(&config));
 
     for _ in 0..config.user_size{
         let user = User::new();
@@ -108,12 +199,14 @@ async fn main() {
 
     let mut transaction_batch: Vec<Transaction> = vec![];
     let mut transactions: Vec<String> = vec![];    
+    let mut tx_ids: HashSet<String> = HashSet::new();
     
     for _ in 0..config.tx_size {
         let idx = rng.sample(dist);
         transaction_batch.push(user_base[idx].simulate_transaction(user_ids.clone()));
         if transaction_batch.len() % 64 == 0 {
             let record = serde_json::to_string(&transaction_batch).expect("Failed to serialize");
+            tx_ids.extend(transaction_batch.iter().map(|tx| tx.id.clone()));
             transactions.push(record);
             transaction_batch = vec![];
         }
@@ -135,4 +228,9 @@ async fn main() {
 
     let thro = ((config.tx_size as f64) / (start.elapsed().as_millis() as f64)) * 1000.0;
     println!("Throughput: {}", thro);
-}
\ No newline at end of file
+
+    if let Some(reply_config) = &config.replies {
+        let finalized = await_replies(&config.producer.server, reply_config, &tx_ids).await;
+        println!("Final transactions: {}/{}", finalized, tx_ids.len());
+    }
+}
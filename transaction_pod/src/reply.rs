@@ -0,0 +1,79 @@
+use bls_signatures::{PublicKey, Serialize, Signature};
+use std::collections::HashMap;
+use crate::reply_mod::{ClientReply, MessageType, ReplyBody, ReplyMethods, ReplyTracker, Validator, REPLY_DOMAIN};
+
+fn verify_reply(reply: &ClientReply, public_key: &PublicKey) -> Option<ReplyBody> {
+    let MessageType::Reply(message) = &reply.msg_type;
+    let body_json = message.strip_prefix(REPLY_DOMAIN)?;
+
+    let signature = Signature::from_bytes(&hex::decode(&reply.signature).ok()?).ok()?;
+    if !public_key.verify(signature, message) {
+        return None;
+    }
+
+    serde_json::from_str(body_json).ok()
+}
+
+impl ReplyMethods for ReplyTracker {
+    fn new(committee_size: usize) -> Self {
+        ReplyTracker { committee_size, validators: HashMap::new(), votes: HashMap::new(),
+            finalized: HashMap::new(), unverified: vec![] }
+    }
+
+    fn threshold(&self) -> usize {
+        (self.committee_size.saturating_sub(1)) / 3 + 1
+    }
+
+    // Replies can arrive before the validator list, they are checked once the key is known
+    fn add_validator(&mut self, validator: &Validator) -> Vec<String> {
+        let public_key = match hex::decode(&validator.public_key).ok().and_then(|bytes| PublicKey::from_bytes(&bytes).ok()) {
+            Some(public_key) => public_key,
+            None => { return vec![]; }
+        };
+        self.validators.insert(validator.node_id.clone(), public_key);
+
+        let (ready, waiting): (Vec<ClientReply>, Vec<ClientReply>) = self.unverified.drain(..)
+            .partition(|reply| reply.sender_id == validator.node_id);
+        self.unverified = waiting;
+
+        ready.iter().flat_map(|reply| self.record(reply)).collect()
+    }
+
+    fn record(&mut self, reply: &ClientReply) -> Vec<String> {
+        let body = match self.validators.get(&reply.sender_id) {
+            Some(public_key) => match verify_reply(reply, public_key) {
+                Some(body) => body,
+                None => { return vec![]; }
+            },
+            None => {
+                self.unverified.push(reply.clone());
+                return vec![];
+            }
+        };
+
+        let threshold = self.threshold();
+        let block = (body.height, body.block_hash.clone());
+        let mut newly_final = vec![];
+
+        for tx_id in body.tx_ids {
+            if self.finalized.contains_key(&tx_id) {
+                continue;
+            }
+
+            let voters = self.votes.entry(tx_id.clone()).or_default().entry(block.clone()).or_default();
+            voters.insert(reply.sender_id.clone());
+
+            if voters.len() >= threshold {
+                self.votes.remove(&tx_id);
+                self.finalized.insert(tx_id.clone(), block.clone());
+                newly_final.push(tx_id);
+            }
+        }
+
+        newly_final
+    }
+
+    fn is_final(&self, tx_id: &str) -> bool {
+        self.finalized.contains_key(tx_id)
+    }
+}
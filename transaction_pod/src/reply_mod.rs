@@ -0,0 +1,68 @@
+use serde::{Serialize, Deserialize};
+use bls_signatures::PublicKey;
+use std::collections::{HashMap, HashSet};
+
+// Mirrors of the node pod's reply messages, only the Reply variant is ever sent to clients
+#[derive(Serialize, Deserialize, Clone)]
+pub enum MessageType {
+    Reply(String),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ClientReply {
+    pub msg_type: MessageType,
+    pub signature: String,
+    pub sender_id: String
+}
+
+/// Prefix node_pod signs a reply body behind, so no other signed message passes for a reply
+pub const REPLY_DOMAIN: &str = "node_pod reply:";
+
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ReplyBody {
+    pub view: u64,
+    pub height: u64,
+    pub block_hash: String,
+    pub tx_ids: Vec<String>
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Validator {
+    pub node_id: String,
+    pub public_key: String
+}
+
+/// Committee of an epoch as the master publishes it on the Validators topic
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ValidatorSet {
+    pub epoch: u64,
+    pub validators: Vec<Validator>
+}
+
+/// Counts signed replies per transaction and block. A transaction is final once f + 1
+/// validators of a committee of `committee_size` replied with the same block, so at least
+/// one honest validator vouches for it.
+pub struct ReplyTracker {
+    pub committee_size: usize,
+    pub validators: HashMap<String, PublicKey>,
+    /// Voters for each transaction id, by the (height, block hash) they named
+    pub votes: HashMap<String, HashMap<(u64, String), HashSet<String>>>,
+    pub finalized: HashMap<String, (u64, String)>,
+    /// Replies from senders whose key has not arrived yet
+    pub unverified: Vec<ClientReply>
+}
+
+pub trait ReplyMethods {
+
+    fn new(committee_size: usize) -> Self;
+
+    fn threshold(&self) -> usize;
+
+    fn add_validator(&mut self, validator: &Validator) -> Vec<String>;
+
+    fn record(&mut self, reply: &ClientReply) -> Vec<String>;
+
+    fn is_final(&self, tx_id: &str) -> bool;
+
+}
@@ -0,0 +1,62 @@
+#[cfg(test)]
+mod tests {
+    use crate::reply_mod::{ClientReply, MessageType, ReplyBody, ReplyMethods, ReplyTracker, Validator, REPLY_DOMAIN};
+    use bls_signatures::{PrivateKey, Serialize};
+
+    fn validator(key: &PrivateKey, id: &str) -> Validator {
+        Validator { node_id: id.to_string(), public_key: hex::encode(key.public_key().as_bytes()) }
+    }
+
+    fn reply(key: &PrivateKey, id: &str, block_hash: &str, tx_ids: &[&str]) -> ClientReply {
+        let body = ReplyBody { view: 0, height: 1, block_hash: block_hash.to_string(),
+            tx_ids: tx_ids.iter().map(|tx_id| tx_id.to_string()).collect() };
+        let message = format!("{}{}", REPLY_DOMAIN, serde_json::to_string(&body).unwrap());
+        let signature = hex::encode(key.sign(&message).as_bytes());
+        ClientReply { msg_type: MessageType::Reply(message), signature, sender_id: id.to_string() }
+    }
+
+    #[test]
+    fn test_final_after_f_plus_one_matching_replies() {
+        let mut rng = rand::thread_rng();
+        let keys: Vec<PrivateKey> = (0..4).map(|_| PrivateKey::generate(&mut rng)).collect();
+        let mut tracker = ReplyTracker::new(4);
+        for (i, key) in keys.iter().enumerate() {
+            tracker.add_validator(&validator(key, &format!("v{}", i)));
+        }
+
+        assert_eq!(tracker.threshold(), 2);
+
+        assert!(tracker.record(&reply(&keys[0], "v0", "aa", &["t1", "t2"])).is_empty());
+        assert!(tracker.record(&reply(&keys[0], "v0", "aa", &["t1", "t2"])).is_empty());
+        assert!(tracker.record(&reply(&keys[1], "v1", "bb", &["t1"])).is_empty());
+        assert!(!tracker.is_final("t1"));
+
+        assert_eq!(tracker.record(&reply(&keys[2], "v2", "aa", &["t1", "t2"])), vec!["t1", "t2"]);
+        assert!(tracker.is_final("t1"));
+        assert!(tracker.record(&reply(&keys[3], "v3", "aa", &["t1"])).is_empty());
+    }
+
+    #[test]
+    fn test_rejects_unsigned_replies() {
+        let mut rng = rand::thread_rng();
+        let keys: Vec<PrivateKey> = (0..2).map(|_| PrivateKey::generate(&mut rng)).collect();
+        let mut tracker = ReplyTracker::new(1);
+        tracker.add_validator(&validator(&keys[0], "v0"));
+
+        assert!(tracker.record(&reply(&keys[1], "v0", "aa", &["t1"])).is_empty());
+
+        // A body signed without the reply domain, as a block would be, is not a reply
+        let mut bare = reply(&keys[0], "v0", "aa", &["t1"]);
+        let MessageType::Reply(message) = &bare.msg_type;
+        let body_json = message.strip_prefix(REPLY_DOMAIN).unwrap().to_string();
+        bare.signature = hex::encode(keys[0].sign(&body_json).as_bytes());
+        bare.msg_type = MessageType::Reply(body_json);
+        assert!(tracker.record(&bare).is_empty());
+
+        let early = reply(&keys[1], "v1", "aa", &["t1"]);
+        assert!(tracker.record(&early).is_empty());
+        assert_eq!(tracker.unverified.len(), 1);
+        assert_eq!(tracker.add_validator(&validator(&keys[1], "v1")), vec!["t1"]);
+        assert!(tracker.unverified.is_empty());
+    }
+}